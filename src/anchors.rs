@@ -0,0 +1,398 @@
+//! Heading anchor slugs and intra-document link checking
+
+use crate::{AnchorStyle, Diagnostic, MdFormatConfig, RuleMode};
+use fancy_regex::{Captures, Regex};
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+
+lazy_static! {
+    // ATX heading: 1: opening '#' sequence, 2: heading text
+    static ref RE_ATX_HEADING: Regex = Regex::new(r"^(#{1,6})(?:[ \t]+(.*))?$").unwrap();
+    // Optional closing sequence of an ATX heading
+    static ref RE_CLOSING_HASHES: Regex = Regex::new(r"(?:^|[ \t]+)#+[ \t]*$").unwrap();
+    // Trailing attribute block of a heading: {#id .class key=value}
+    static ref RE_HEADING_ATTRIBUTES: Regex = Regex::new(r"(?:^|[ \t]+)\{([^{}]*)\}[ \t]*$").unwrap();
+    static ref RE_ATTRIBUTE_ID: Regex = Regex::new(r"(?:^|\s)#([^\s}]+)").unwrap();
+    // Inline markup removed from heading text before slugifying
+    static ref RE_IMAGE_OR_LINK: Regex = Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap();
+    static ref RE_HTML_TAG: Regex = Regex::new(r"<!--.*?-->|</?[A-Za-z][^>]*>").unwrap();
+    static ref RE_CODE_SPAN: Regex = Regex::new(r"`+([^`]*)`+").unwrap();
+    static ref RE_UNDERSCORE_EMPHASIS: Regex =
+        Regex::new(r"(?<!\w)(_{1,3})(?=\S)(.+?)(?<=\S)\1(?!\w)").unwrap();
+    // Intra-document link: "](#anchor)" or reference definition "]: #anchor"
+    static ref RE_ANCHOR_LINK: Regex = Regex::new(r"(\]\(|\]:[ \t]*)#([^)\s]*)").unwrap();
+}
+
+/// Remove inline Markdown/HTML markup, keeping the text a renderer displays
+pub fn plain_text(text: &str) -> String {
+    let text = RE_IMAGE_OR_LINK.replace_all(text, "$1");
+    let text = RE_HTML_TAG.replace_all(&text, "");
+    let text = RE_CODE_SPAN.replace_all(&text, "$1");
+    RE_UNDERSCORE_EMPHASIS.replace_all(&text, "$2").to_string()
+}
+
+/// Generate a GitHub-compatible anchor slug (without duplicate suffix)
+///
+/// The text is lowercased, everything except letters, marks, numbers,
+/// spaces, hyphens and underscores is removed, and each space becomes a
+/// hyphen. CJK characters are kept as-is.
+pub fn github_slug(text: &str) -> String {
+    plain_text(text)
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' || is_mark(c) => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generate a GitLab-compatible anchor slug (without duplicate suffix)
+///
+/// Like GitHub, but only word characters are kept and runs of hyphens are
+/// collapsed into one.
+pub fn gitlab_slug(text: &str) -> String {
+    let slug = plain_text(text)
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' | '-' => Some('-'),
+            c if c.is_alphanumeric() || c == '_' || is_mark(c) => Some(c),
+            _ => None,
+        })
+        .collect::<String>();
+    collapse_hyphens(&slug)
+}
+
+/// Generate an Azure DevOps wiki anchor slug (without duplicate suffix)
+///
+/// Azure DevOps keeps punctuation: the text is lowercased and spaces become
+/// hyphens, only characters not allowed in a URL fragment are removed.
+pub fn azure_slug(text: &str) -> String {
+    plain_text(text)
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '#' | '%' | '"' | '<' | '>' | '`' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Generate a Pandoc `auto_identifiers` slug (without duplicate suffix)
+///
+/// Keeps alphanumerics, underscores, hyphens and periods, turns spaces into
+/// hyphens and drops everything before the first letter. Falls back to
+/// "section" if nothing is left.
+pub fn pandoc_slug(text: &str) -> String {
+    let slug = plain_text(text)
+        .trim()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .filter(|&c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .skip_while(|c| !c.is_alphabetic())
+        .collect::<String>();
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Generate an anchor slug with the given algorithm (without duplicate suffix)
+pub fn slug(text: &str, style: AnchorStyle) -> String {
+    match style {
+        AnchorStyle::Github => github_slug(text),
+        AnchorStyle::Gitlab => gitlab_slug(text),
+        AnchorStyle::Azure => azure_slug(text),
+        AnchorStyle::Pandoc => pandoc_slug(text),
+    }
+}
+
+/// Replace runs of hyphens with a single hyphen
+fn collapse_hyphens(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c != '-' || !result.ends_with('-') {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Whether the character is a combining mark (kept by GitHub's slugger)
+fn is_mark(c: char) -> bool {
+    matches!(c as u32, 0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F)
+        || matches!(c as u32, 0x3099..=0x309A)
+}
+
+/// Generates unique slugs, adding "-1", "-2"... suffixes to duplicates
+pub struct Slugger {
+    style: AnchorStyle,
+    seen: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn new(style: AnchorStyle) -> Self {
+        Self {
+            style,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Mark a custom ID as used, so generated slugs don't collide with it
+    pub fn reserve(&mut self, id: &str) {
+        self.seen.entry(id.to_string()).or_insert(0);
+    }
+
+    /// Slug of the next heading with the given text
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = slug(text, self.style);
+        let mut slug = base.clone();
+        // Same as github-slugger: the counter of the base slug is increased
+        // until an unused slug is found
+        while self.seen.contains_key(&slug) {
+            let count = self.seen.entry(base.clone()).or_insert(0);
+            *count += 1;
+            slug = format!("{}-{}", base, count);
+        }
+        self.seen.insert(slug.clone(), 0);
+        slug
+    }
+}
+
+/// A heading of the formatted document
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadingAnchor {
+    /// Index of the heading line
+    pub index: usize,
+    pub level: usize,
+    /// Heading text without the closing '#' sequence and attribute block
+    pub text: String,
+    /// Anchor of the heading: the custom ID if set, the generated slug otherwise
+    pub slug: String,
+    /// Whether the heading has a custom `{#id}` attribute
+    pub custom_id: bool,
+}
+
+/// Split a heading text into the text and its custom `{#id}`, if any
+fn split_heading_id(text: &str) -> (String, Option<String>) {
+    if let Ok(Some(caps)) = RE_HEADING_ATTRIBUTES.captures(text)
+        && let Ok(Some(id)) = RE_ATTRIBUTE_ID.captures(&caps[1])
+    {
+        let start = caps.get(0).unwrap().start();
+        return (text[..start].trim_end().to_string(), Some(id[1].to_string()));
+    }
+    (text.to_string(), None)
+}
+
+/// Collect the ATX headings with their anchors, skipping fenced code blocks
+pub fn collect_heading_anchors<S: AsRef<str>>(lines: &[S], style: AnchorStyle) -> Vec<HeadingAnchor> {
+    let mut headings = Vec::new();
+    let mut in_code = false;
+
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        if let Ok(Some(caps)) = RE_ATX_HEADING.captures(line) {
+            let text = caps.get(2).map_or("", |m| m.as_str());
+            let (text, id) = split_heading_id(text);
+            let text = RE_CLOSING_HASHES.replace(&text, "").trim().to_string();
+            if text.is_empty() && id.is_none() {
+                continue;
+            }
+            headings.push(HeadingAnchor {
+                index,
+                level: caps.get(1).unwrap().as_str().len(),
+                custom_id: id.is_some(),
+                slug: id.unwrap_or_default(),
+                text,
+            });
+        }
+    }
+
+    // Custom IDs are reserved first, generated slugs avoid them
+    let mut slugger = Slugger::new(style);
+    for heading in headings.iter().filter(|h| h.custom_id) {
+        slugger.reserve(&heading.slug);
+    }
+    for heading in headings.iter_mut().filter(|h| !h.custom_id) {
+        heading.slug = slugger.slug(&heading.text);
+    }
+
+    headings
+}
+
+/// Append a `{#id}` attribute with the generated anchor to headings without one
+pub fn add_heading_ids(text: &str, style: AnchorStyle) -> String {
+    let mut lines = text.lines().map(|line| line.to_string()).collect::<Vec<_>>();
+    for heading in collect_heading_anchors(&lines, style) {
+        if !heading.custom_id && !heading.slug.is_empty() {
+            let line = &mut lines[heading.index];
+            *line = format!("{} {{#{}}}", line.trim_end(), heading.slug);
+        }
+    }
+
+    let mut ret = lines.join("\n");
+    if text.ends_with('\n') {
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Decode %XX escapes of an anchor or link destination
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| text.to_string())
+}
+
+/// Loose form of an anchor used to find the heading a stale anchor meant
+fn loose_anchor(anchor: &str) -> String {
+    percent_decode(anchor)
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// Line number (1-based) of the n-th occurrence of `needle` in the input,
+/// falling back to the given line
+fn input_line(input: &str, needle: &str, occurrence: usize, fallback: usize) -> usize {
+    input
+        .match_indices(needle)
+        .nth(occurrence)
+        .map_or(fallback, |(pos, _)| input[..pos].matches('\n').count() + 1)
+}
+
+/// Check intra-document `#anchor` links against the heading anchors,
+/// reporting broken links or fixing them when a heading clearly matches
+pub fn check_anchor_links(
+    text: &str,
+    input: &str,
+    config: &MdFormatConfig,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let mode = config.anchors.check_links;
+    if mode == RuleMode::Ignore {
+        return text.to_string();
+    }
+
+    let lines = text.lines().collect::<Vec<_>>();
+    let headings = collect_heading_anchors(&lines, config.anchors.style);
+    let slugs = headings.iter().map(|h| h.slug.as_str()).collect::<HashSet<_>>();
+
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut result = Vec::with_capacity(lines.len());
+    let mut in_code = false;
+
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        if in_code || !line.contains('#') {
+            result.push(line.to_string());
+            continue;
+        }
+
+        let fixed = RE_ANCHOR_LINK.replace_all(line, |caps: &Captures| {
+            let anchor = &caps[2];
+            let link = format!("{}#{}", &caps[1], anchor);
+            let occurrence = occurrences.entry(link.clone()).or_insert(0);
+            let line_number = input_line(input, &link, *occurrence, index + 1);
+            *occurrence += 1;
+
+            if anchor.is_empty() || slugs.contains(anchor) || slugs.contains(percent_decode(anchor).as_str()) {
+                return caps[0].to_string();
+            }
+
+            // Find the heading the anchor was meant for
+            let loose = loose_anchor(anchor);
+            let candidates = headings
+                .iter()
+                .filter(|h| loose_anchor(&h.slug) == loose)
+                .collect::<Vec<_>>();
+            let fixed = mode == RuleMode::Fix && candidates.len() == 1;
+            diagnostics.push(Diagnostic {
+                line: line_number,
+                rule: "anchor-link",
+                message: format!("link to missing anchor '#{}'", anchor),
+                fixed,
+            });
+            if fixed {
+                format!("{}#{}", &caps[1], candidates[0].slug)
+            } else {
+                caps[0].to_string()
+            }
+        });
+        result.push(fixed.to_string());
+    }
+
+    let mut ret = result.join("\n");
+    if text.ends_with('\n') {
+        ret.push('\n');
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_slug() {
+        assert_eq!(github_slug("Getting Started"), "getting-started");
+        assert_eq!(github_slug("What's new in v1.2?"), "whats-new-in-v12");
+        assert_eq!(github_slug("中文标题 API"), "中文标题-api");
+        assert_eq!(github_slug("Use `foo_bar` and [links](http://x.y)"), "use-foo_bar-and-links");
+        assert_eq!(github_slug("_Emphasis_ and snake_case"), "emphasis-and-snake_case");
+        assert_eq!(github_slug("A  --  B"), "a------b");
+    }
+
+    #[test]
+    fn test_alternative_slugs() {
+        assert_eq!(gitlab_slug("A  --  B: What's new?"), "a-b-whats-new");
+        assert_eq!(gitlab_slug("中文标题 API"), "中文标题-api");
+        assert_eq!(azure_slug("Hello World! (v1.2)"), "hello-world!-(v1.2)");
+        assert_eq!(pandoc_slug("1.2 Getting Started: v1.2!"), "getting-started-v1.2");
+        assert_eq!(pandoc_slug("2024"), "section");
+        assert_eq!(pandoc_slug("中文 标题"), "中文-标题");
+    }
+
+    #[test]
+    fn test_slugger_duplicates() {
+        let mut slugger = Slugger::new(AnchorStyle::Github);
+        assert_eq!(slugger.slug("Intro"), "intro");
+        assert_eq!(slugger.slug("Intro"), "intro-1");
+        assert_eq!(slugger.slug("Intro"), "intro-2");
+        assert_eq!(slugger.slug("Intro 1"), "intro-1-1");
+    }
+}
@@ -0,0 +1,55 @@
+//! Markdown embedded in the line comments of source code: contiguous comment
+//! blocks are formatted as Markdown and written back with their prefix, the
+//! code being left untouched
+
+use crate::{MdFormatConfig, format_markdown};
+
+/// Indentation and text of the line when it is a comment: the prefix followed
+/// by a space or the end of the line, so that `#include` or `///` are not
+/// taken for `#` or `//` comments
+fn comment_text<'a>(line: &'a str, prefix: &str) -> Option<(&'a str, &'a str)> {
+    let text = line.trim_start();
+    let indent = &line[..line.len() - text.len()];
+    let text = text.strip_prefix(prefix)?;
+    if text.is_empty() {
+        return Some((indent, text));
+    }
+    text.strip_prefix(' ').map(|text| (indent, text))
+}
+
+/// Format the comment blocks, runs of comment lines with the same indentation,
+/// of the source code
+pub fn format_comments(content: &str, prefix: &str, config: &MdFormatConfig) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some((indent, _)) = comment_text(lines[i], prefix) else {
+            result.push(lines[i].to_string());
+            i += 1;
+            continue;
+        };
+        let block: Vec<&str> = lines[i..]
+            .iter()
+            .map_while(|line| comment_text(line, prefix).filter(|(other, _)| *other == indent).map(|(_, text)| text))
+            .collect();
+        i += block.len();
+        if block.iter().all(|text| text.trim().is_empty()) {
+            result.extend(block.iter().map(|_| format!("{}{}", indent, prefix)));
+            continue;
+        }
+        let formatted = format_markdown(&block.join("\n"), config);
+        for line in formatted.trim_end_matches('\n').lines() {
+            match line.is_empty() {
+                true => result.push(format!("{}{}", indent, prefix)),
+                false => result.push(format!("{}{} {}", indent, prefix, line)),
+            }
+        }
+    }
+
+    let mut output = result.join("\n");
+    if content.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
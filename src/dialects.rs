@@ -0,0 +1,174 @@
+//! Markdown dialects embedding foreign syntax: the parts that must be passed
+//! through untouched while the surrounding Markdown is formatted
+
+use clap::ValueEnum;
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+use std::path::Path;
+
+lazy_static! {
+    // MDX ESM statement starting a paragraph
+    static ref RE_MDX_ESM: Regex = Regex::new(r"^(?:import|export)\s").unwrap();
+    // JSX element or fragment starting a line: 1: component name
+    static ref RE_JSX_START: Regex = Regex::new(r"^<(?:([A-Z][\w.]*)(?=[\s/>]|$)|>)").unwrap();
+    // MDX expression, with one level of nested braces: {props.x}, {/* comment */}
+    static ref RE_MDX_EXPRESSION: Regex = Regex::new(r"\{[^{}\n]*(?:\{[^{}\n]*\}[^{}\n]*)*\}").unwrap();
+    // R Markdown inline code: `r expr`
+    static ref RE_RMD_INLINE_CODE: Regex = Regex::new(r"`r [^`\n]+`").unwrap();
+    // Quarto shortcode ({{< video ... >}}, {{% ... %}}), span attributes
+    // ([text]{.mark}) and inline code (`{python} expr`, `r expr`)
+    static ref RE_QUARTO_INLINE: Regex =
+        Regex::new(r"\{\{[<%].*?[%>]\}\}|(?<=\])\{[^{}\n]*\}|`(?:r |\{\w+\} ?)[^`\n]*`").unwrap();
+    // Fenced div marker, with its attributes: ::: {.callout-note}
+    static ref RE_DIV_FENCE: Regex = Regex::new(r"^\s*:{3,}").unwrap();
+    // Opening fence of an executable chunk: 1: indentation, 2: fence
+    static ref RE_CHUNK_START: Regex = Regex::new(r"^(\s*)(`{3,})\{[^{}]*\}\s*$").unwrap();
+}
+
+/// Dialect of the input, deciding which foreign syntax is protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Dialect {
+    /// Plain (CommonMark/GFM) Markdown
+    #[default]
+    Markdown,
+    /// MDX: ESM statements, JSX components and `{expressions}`
+    Mdx,
+    /// R Markdown: knitr chunks and inline `r` code
+    Rmd,
+    /// Quarto: executable chunks, shortcodes, fenced divs and span attributes
+    Quarto,
+}
+
+impl Dialect {
+    /// Dialect of the file, from its extension
+    pub fn from_path(path: Option<&Path>) -> Self {
+        let extension = path.and_then(Path::extension).and_then(|ext| ext.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "mdx" => Dialect::Mdx,
+            "rmd" => Dialect::Rmd,
+            "qmd" => Dialect::Quarto,
+            _ => Dialect::Markdown,
+        }
+    }
+}
+
+/// Inline syntax of the dialect passed through untouched
+pub fn inline_pattern(dialect: Dialect) -> Option<&'static Regex> {
+    match dialect {
+        Dialect::Markdown => None,
+        Dialect::Mdx => Some(&RE_MDX_EXPRESSION),
+        Dialect::Rmd => Some(&RE_RMD_INLINE_CODE),
+        Dialect::Quarto => Some(&RE_QUARTO_INLINE),
+    }
+}
+
+/// Whether the line is passed through verbatim: fenced div markers of Quarto
+pub fn is_verbatim_line(line: &str, dialect: Dialect) -> bool {
+    dialect == Dialect::Quarto && RE_DIV_FENCE.is_match(line).unwrap_or(false)
+}
+
+/// Number of lines of the foreign block starting at `lines[start]`, if any
+pub fn block_len(lines: &[&str], start: usize, dialect: Dialect) -> Option<usize> {
+    match dialect {
+        Dialect::Markdown => None,
+        Dialect::Mdx => mdx_block_len(lines, start),
+        Dialect::Rmd | Dialect::Quarto => indented_chunk_len(lines, start),
+    }
+}
+
+/// Number of lines of the executable chunk (```` ```{r} ````, ```` ```{python} ````) starting at
+/// `lines[start]` when it is indented, e.g. in a list item; chunks at the start
+/// of lines are already left alone like any fenced code
+fn indented_chunk_len(lines: &[&str], start: usize) -> Option<usize> {
+    let caps = RE_CHUNK_START.captures(lines[start]).ok().flatten()?;
+    let indent = caps.get(1).map_or("", |m| m.as_str());
+    let fence = caps.get(2).map_or("", |m| m.as_str());
+    if indent.is_empty() {
+        return None;
+    }
+    let end = lines[start + 1..].iter().position(|line| {
+        let line = line.trim();
+        line.starts_with(fence) && line.trim_start_matches('`').is_empty()
+    })?;
+    Some(end + 2)
+}
+
+/// Number of lines of the MDX ESM statements (`import`/`export` lines and
+/// their indented or closing-bracket continuations) or JSX element starting at
+/// `lines[start]`
+fn mdx_block_len(lines: &[&str], start: usize) -> Option<usize> {
+    let line = lines[start];
+    if RE_MDX_ESM.is_match(line).unwrap_or(false) {
+        let continued = |line: &&&str| {
+            RE_MDX_ESM.is_match(line).unwrap_or(false) || line.starts_with([' ', '\t', '}', ']', ')'])
+        };
+        return Some(1 + lines[start + 1..].iter().take_while(continued).count());
+    }
+    let caps = RE_JSX_START.captures(line).ok().flatten()?;
+    let name = caps.get(1).map_or("", |m| m.as_str());
+    let text = lines[start..].join("\n");
+    let end = jsx_element_end(&text, name)?;
+    // The element must end its line
+    let line_end = text[end..].find('\n').map_or(text.len(), |i| end + i);
+    if !text[end..line_end].trim().is_empty() {
+        return None;
+    }
+    Some(text[..end].matches('\n').count() + 1)
+}
+
+/// End of the JSX element named `name` (empty for a fragment) opening the
+/// text: after its closing tag, or its opening tag when self-closing
+fn jsx_element_end(text: &str, name: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        let closing = format!("</{}>", name);
+        if rest.starts_with(&closing) {
+            depth -= 1;
+            i += closing.len();
+            if depth == 0 {
+                return Some(i);
+            }
+            continue;
+        }
+        let after = rest.strip_prefix('<').and_then(|rest| rest.strip_prefix(name));
+        let opening = match after {
+            Some(after) if name.is_empty() => after.starts_with('>'),
+            Some(after) => after.starts_with([' ', '\t', '\n', '>', '/']),
+            None => false,
+        };
+        if opening {
+            // Find the end of the tag, skipping attribute strings and expressions
+            let mut braces = 0;
+            let mut quote = None;
+            let mut end = None;
+            for (j, c) in rest.char_indices().skip(1) {
+                match c {
+                    _ if quote == Some(c) => quote = None,
+                    _ if quote.is_some() => {}
+                    '"' | '\'' if braces == 0 => quote = Some(c),
+                    '{' => braces += 1,
+                    '}' => braces -= 1,
+                    '>' if braces == 0 => {
+                        end = Some(j);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            let end = end?;
+            i += end + 1;
+            if rest[..end].ends_with('/') {
+                if depth == 0 {
+                    return Some(i);
+                }
+            } else {
+                depth += 1;
+            }
+            continue;
+        }
+        i += c.len_utf8();
+    }
+    None
+}
@@ -0,0 +1,82 @@
+//! Pandoc fenced divs (`::: {.warning}` ... `:::`): the content is formatted
+//! recursively and the fences are normalized, outer divs getting longer fences
+//! than the divs nested in them
+
+use crate::{MdFormatConfig, format_markdown};
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // Opening fence: 1: attributes, an attribute block or a single class name
+    static ref RE_DIV_OPEN: Regex = Regex::new(r"^ {0,3}:{3,}\s*(\{[^{}]*\}|[^\s{}:]+)\s*:*\s*$").unwrap();
+    // Closing fence, which never has attributes
+    static ref RE_DIV_CLOSE: Regex = Regex::new(r"^ {0,3}:{3,}\s*$").unwrap();
+}
+
+/// Whether the line is a code fence, div fences inside code being ignored
+fn is_code_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Number of lines of the fenced div opening at `lines[start]`, up to its
+/// closing fence
+pub fn div_len(lines: &[&str], start: usize) -> Option<usize> {
+    if !RE_DIV_OPEN.is_match(lines[start]).unwrap_or(false) {
+        return None;
+    }
+    let mut depth = 0;
+    let mut in_code = false;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        if is_code_fence(line) {
+            in_code = !in_code;
+        } else if in_code {
+            continue;
+        } else if RE_DIV_OPEN.is_match(line).unwrap_or(false) {
+            depth += 1;
+        } else if RE_DIV_CLOSE.is_match(line).unwrap_or(false) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i - start + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Format the fenced div made of `lines`: its content is formatted without
+/// blank lines next to the fences, which are one colon longer than those of
+/// the divs it contains
+pub fn format_div(lines: &[&str], config: &MdFormatConfig) -> String {
+    let attributes = RE_DIV_OPEN
+        .captures(lines[0])
+        .ok()
+        .flatten()
+        .and_then(|caps| caps.get(1))
+        .map_or("", |m| m.as_str());
+    let body = &lines[1..lines.len() - 1];
+    let formatted = match body.iter().all(|line| line.trim().is_empty()) {
+        true => String::new(),
+        false => format_markdown(&body.join("\n"), config).trim_matches('\n').to_string(),
+    };
+
+    // Fences of the nested divs, already normalized
+    let mut in_code = false;
+    let mut inner = 0;
+    for line in formatted.lines() {
+        if is_code_fence(line) {
+            in_code = !in_code;
+        } else if !in_code && RE_DIV_CLOSE.is_match(line).unwrap_or(false) {
+            inner = inner.max(line.trim().len());
+        }
+    }
+    let fence = ":".repeat(if inner == 0 { 3 } else { inner + 1 });
+
+    let mut ret = format!("{} {}\n", fence, attributes);
+    if !formatted.is_empty() {
+        ret.push_str(&formatted);
+        ret.push('\n');
+    }
+    ret.push_str(&fence);
+    ret
+}
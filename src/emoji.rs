@@ -0,0 +1,272 @@
+//! Emoji shortcodes
+
+use fancy_regex::{Captures, Regex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    // Emoji shortcode like :smile: or :+1:, not part of a time like 10:30:45
+    pub static ref RE_SHORTCODE: Regex = Regex::new(r"(?<![A-Za-z0-9_:]):[a-z0-9_+-]+:(?![A-Za-z0-9_:])").unwrap();
+    // Emoji of the table, with or without variation selector, longest first,
+    // not part of a ZWJ sequence or followed by a skin tone modifier
+    static ref RE_EMOJI: Regex = {
+        let mut emoji = EMOJI.iter().map(|(emoji, _)| emoji.trim_end_matches('\u{FE0F}')).collect::<Vec<_>>();
+        emoji.sort_by_key(|emoji| std::cmp::Reverse(emoji.len()));
+        let alternatives = emoji.iter().map(|emoji| fancy_regex::escape(emoji)).collect::<Vec<_>>();
+        Regex::new(&format!(
+            r"(?<!\x{{200D}})(?:{})\x{{FE0F}}?(?![\x{{200D}}\x{{1F3FB}}-\x{{1F3FF}}])",
+            alternatives.join("|")
+        ))
+        .unwrap()
+    };
+}
+
+/// How emoji are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiStyle {
+    /// Left as written
+    Preserve,
+    /// GitHub shortcodes like `:tada:`
+    Shortcode,
+    /// Unicode emoji like 🎉
+    Unicode,
+}
+
+/// GitHub emoji and their shortcodes, the preferred shortcode first
+const EMOJI: &[(&str, &[&str])] = &[
+    ("😄", &["smile"]),
+    ("😃", &["smiley"]),
+    ("😀", &["grinning"]),
+    ("😊", &["blush"]),
+    ("😉", &["wink"]),
+    ("😍", &["heart_eyes"]),
+    ("😘", &["kissing_heart"]),
+    ("😜", &["stuck_out_tongue_winking_eye"]),
+    ("😛", &["stuck_out_tongue"]),
+    ("😁", &["grin"]),
+    ("😆", &["laughing", "satisfied"]),
+    ("😅", &["sweat_smile"]),
+    ("😂", &["joy"]),
+    ("🤣", &["rofl"]),
+    ("🙂", &["slightly_smiling_face"]),
+    ("🙃", &["upside_down_face"]),
+    ("😇", &["innocent"]),
+    ("😎", &["sunglasses"]),
+    ("🤔", &["thinking"]),
+    ("😐", &["neutral_face"]),
+    ("😑", &["expressionless"]),
+    ("😏", &["smirk"]),
+    ("😒", &["unamused"]),
+    ("🙄", &["roll_eyes"]),
+    ("😬", &["grimacing"]),
+    ("😌", &["relieved"]),
+    ("😔", &["pensive"]),
+    ("😴", &["sleeping"]),
+    ("😷", &["mask"]),
+    ("😵", &["dizzy_face"]),
+    ("🤯", &["exploding_head"]),
+    ("😕", &["confused"]),
+    ("😟", &["worried"]),
+    ("😮", &["open_mouth"]),
+    ("😲", &["astonished"]),
+    ("😳", &["flushed"]),
+    ("😢", &["cry"]),
+    ("😭", &["sob"]),
+    ("😱", &["scream"]),
+    ("😞", &["disappointed"]),
+    ("😓", &["sweat"]),
+    ("😩", &["weary"]),
+    ("😤", &["triumph"]),
+    ("😡", &["rage", "pout"]),
+    ("😠", &["angry"]),
+    ("💀", &["skull"]),
+    ("💩", &["hankey", "poop", "shit"]),
+    ("🤡", &["clown_face"]),
+    ("👻", &["ghost"]),
+    ("👽", &["alien"]),
+    ("🤖", &["robot"]),
+    ("🙈", &["see_no_evil"]),
+    ("❤️", &["heart"]),
+    ("💔", &["broken_heart"]),
+    ("💯", &["100"]),
+    ("💥", &["boom", "collision"]),
+    ("💤", &["zzz"]),
+    ("💬", &["speech_balloon"]),
+    ("💭", &["thought_balloon"]),
+    ("👋", &["wave"]),
+    ("✋", &["raised_hand", "hand"]),
+    ("👌", &["ok_hand"]),
+    ("✌️", &["v"]),
+    ("🤞", &["crossed_fingers"]),
+    ("👈", &["point_left"]),
+    ("👉", &["point_right"]),
+    ("👆", &["point_up_2"]),
+    ("👇", &["point_down"]),
+    ("☝️", &["point_up"]),
+    ("👍", &["+1", "thumbsup"]),
+    ("👎", &["-1", "thumbsdown"]),
+    ("✊", &["fist_raised", "fist"]),
+    ("👊", &["fist_oncoming", "facepunch", "punch"]),
+    ("👏", &["clap"]),
+    ("🙌", &["raised_hands"]),
+    ("🙏", &["pray"]),
+    ("💪", &["muscle"]),
+    ("👀", &["eyes"]),
+    ("🙋", &["raising_hand"]),
+    ("🤷", &["shrug"]),
+    ("🤦", &["facepalm"]),
+    ("👤", &["bust_in_silhouette"]),
+    ("👥", &["busts_in_silhouette"]),
+    ("👕", &["shirt", "tshirt"]),
+    ("👞", &["mans_shoe", "shoe"]),
+    ("🎉", &["tada"]),
+    ("🎊", &["confetti_ball"]),
+    ("🎈", &["balloon"]),
+    ("🎁", &["gift"]),
+    ("🏆", &["trophy"]),
+    ("🎨", &["art"]),
+    ("🎵", &["musical_note"]),
+    ("⭐", &["star"]),
+    ("🌟", &["star2"]),
+    ("✨", &["sparkles"]),
+    ("⚡", &["zap"]),
+    ("🔥", &["fire"]),
+    ("🌈", &["rainbow"]),
+    ("☀️", &["sunny"]),
+    ("☁️", &["cloud"]),
+    ("❄️", &["snowflake"]),
+    ("🌙", &["crescent_moon"]),
+    ("🌍", &["earth_africa"]),
+    ("🌐", &["globe_with_meridians"]),
+    ("🍺", &["beer"]),
+    ("🍻", &["beers"]),
+    ("☕", &["coffee"]),
+    ("🍕", &["pizza"]),
+    ("🍰", &["cake"]),
+    ("🍎", &["apple"]),
+    ("🐛", &["bug"]),
+    ("🐞", &["lady_beetle", "beetle"]),
+    ("🐝", &["bee", "honeybee"]),
+    ("🐳", &["whale"]),
+    ("🐧", &["penguin"]),
+    ("🐍", &["snake"]),
+    ("🦀", &["crab"]),
+    ("🐱", &["cat"]),
+    ("🐶", &["dog"]),
+    ("🚀", &["rocket"]),
+    ("🚗", &["car", "red_car"]),
+    ("✈️", &["airplane"]),
+    ("⛵", &["sailboat", "boat"]),
+    ("🏠", &["house"]),
+    ("🏮", &["izakaya_lantern", "lantern"]),
+    ("⏰", &["alarm_clock"]),
+    ("⌛", &["hourglass"]),
+    ("📅", &["date"]),
+    ("📌", &["pushpin"]),
+    ("📎", &["paperclip"]),
+    ("📝", &["memo", "pencil"]),
+    ("✏️", &["pencil2"]),
+    ("📖", &["book", "open_book"]),
+    ("📚", &["books"]),
+    ("📦", &["package"]),
+    ("📧", &["email", "e-mail"]),
+    ("☎️", &["phone", "telephone"]),
+    ("💻", &["computer"]),
+    ("💾", &["floppy_disk"]),
+    ("💡", &["bulb"]),
+    ("🔋", &["battery"]),
+    ("🔌", &["electric_plug"]),
+    ("🔍", &["mag"]),
+    ("🔒", &["lock"]),
+    ("🔓", &["unlock"]),
+    ("🔑", &["key"]),
+    ("🔧", &["wrench"]),
+    ("🔨", &["hammer"]),
+    ("🛠️", &["hammer_and_wrench"]),
+    ("⚙️", &["gear"]),
+    ("🔗", &["link"]),
+    ("🧪", &["test_tube"]),
+    ("🔪", &["hocho", "knife"]),
+    ("💰", &["moneybag"]),
+    ("📈", &["chart_with_upwards_trend"]),
+    ("📉", &["chart_with_downwards_trend"]),
+    ("📊", &["bar_chart"]),
+    ("📁", &["file_folder"]),
+    ("📂", &["open_file_folder"]),
+    ("📄", &["page_facing_up"]),
+    ("📋", &["clipboard"]),
+    ("🗑️", &["wastebasket"]),
+    ("🚧", &["construction"]),
+    ("🚨", &["rotating_light"]),
+    ("🚫", &["no_entry_sign"]),
+    ("⛔", &["no_entry"]),
+    ("⚠️", &["warning"]),
+    ("❗", &["exclamation", "heavy_exclamation_mark"]),
+    ("❓", &["question"]),
+    ("✅", &["white_check_mark"]),
+    ("✔️", &["heavy_check_mark"]),
+    ("❌", &["x"]),
+    ("➕", &["heavy_plus_sign"]),
+    ("➖", &["heavy_minus_sign"]),
+    ("ℹ️", &["information_source"]),
+    ("🆕", &["new"]),
+    ("🆗", &["ok"]),
+    ("🔴", &["red_circle"]),
+    ("🟢", &["green_circle"]),
+    ("🔵", &["large_blue_circle"]),
+    ("⬆️", &["arrow_up"]),
+    ("⬇️", &["arrow_down"]),
+    ("⬅️", &["arrow_left"]),
+    ("➡️", &["arrow_right"]),
+    ("🔄", &["arrows_counterclockwise"]),
+    ("♻️", &["recycle"]),
+    ("🏁", &["checkered_flag"]),
+    ("🚩", &["triangular_flag_on_post"]),
+    ("🇨🇳", &["cn"]),
+    ("🇬🇧", &["gb", "uk"]),
+    ("🇯🇵", &["jp"]),
+    ("🇺🇸", &["us"]),
+];
+
+/// Shortcode names of the emoji table sharing the name of `shortcode`
+fn names_of(shortcode: &str) -> Option<&'static [&'static str]> {
+    let name = shortcode.strip_prefix(':')?.strip_suffix(':')?;
+    EMOJI
+        .iter()
+        .map(|(_, names)| *names)
+        .find(|names| names.contains(&name))
+}
+
+/// Preferred spelling of a shortcode, like `:+1:` for `:thumbsup:`; unknown
+/// shortcodes are returned unchanged
+pub fn canonical_shortcode(shortcode: &str) -> String {
+    match names_of(shortcode) {
+        Some(names) => format!(":{}:", names[0]),
+        None => shortcode.to_string(),
+    }
+}
+
+/// Unicode emoji of a known shortcode, like 🎉 for `:tada:`; unknown
+/// shortcodes are returned unchanged
+pub fn shortcode_to_emoji(shortcode: &str) -> String {
+    let name = shortcode.trim_matches(':');
+    match EMOJI.iter().find(|(_, names)| names.contains(&name)) {
+        Some((emoji, _)) => emoji.to_string(),
+        None => shortcode.to_string(),
+    }
+}
+
+/// Replace the known Unicode emoji of the text with their preferred shortcode
+pub fn emoji_to_shortcodes(text: &str) -> String {
+    RE_EMOJI
+        .replace_all(text, |caps: &Captures| {
+            let emoji = caps[0].trim_end_matches('\u{FE0F}');
+            let (_, names) = EMOJI
+                .iter()
+                .find(|(e, _)| e.trim_end_matches('\u{FE0F}') == emoji)
+                .unwrap();
+            format!(":{}:", names[0])
+        })
+        .to_string()
+}
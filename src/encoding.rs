@@ -0,0 +1,59 @@
+//! Detection of the text encoding of legacy (GBK, Big5, Shift_JIS...) input
+//! files, and re-encoding of the formatted output
+
+use anyhow::Result;
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Parse an encoding label such as "gbk", "big5" or "shift_jis"
+pub fn parse_label(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding '{}'", label))
+}
+
+/// Decode the input in the given encoding, failing on malformed input
+pub fn decode_as(bytes: &[u8], encoding: &'static Encoding) -> Result<String> {
+    let (text, malformed) = encoding.decode_with_bom_removal(bytes);
+    if malformed {
+        anyhow::bail!("The input is not valid {}", encoding.name());
+    }
+    Ok(text.into_owned())
+}
+
+/// Decode the input, guessing its encoding unless it is valid UTF-8 or starts
+/// with a byte order mark
+pub fn decode(bytes: &[u8]) -> (String, &'static Encoding) {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), UTF_8);
+    }
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        None => {
+            let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+            detector.feed(bytes, true);
+            detector.guess(None, Utf8Detection::Allow)
+        }
+    };
+    let (text, _, _) = encoding.decode(bytes);
+    (text.into_owned(), encoding)
+}
+
+/// Encode the output, failing if the encoding can't represent some characters
+pub fn encode(text: &str, encoding: &'static Encoding) -> Result<Vec<u8>> {
+    // encoding_rs only decodes UTF-16, so it is written here with its byte order mark
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut bytes = if encoding == UTF_16LE { vec![0xFF, 0xFE] } else { vec![0xFE, 0xFF] };
+        for unit in text.encode_utf16() {
+            let unit = if encoding == UTF_16LE { unit.to_le_bytes() } else { unit.to_be_bytes() };
+            bytes.extend_from_slice(&unit);
+        }
+        return Ok(bytes);
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        anyhow::bail!(
+            "The output contains characters that can't be encoded in {} (use --to-utf8 or --output-encoding)",
+            encoding.name()
+        );
+    }
+    Ok(bytes.into_owned())
+}
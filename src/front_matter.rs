@@ -0,0 +1,120 @@
+//! Formatting of YAML front matter: keys quoted only when needed, 2-space
+//! indentation and the top-level keys ordered as configured
+//!
+//! The front matter is rewritten only when the result parses back to the same
+//! data; comments and anchors, which a rewrite would lose, keep it untouched.
+
+use crate::FrontMatterOptions;
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+use serde_yaml::{Mapping, Value};
+
+lazy_static! {
+    // Comment, anchor or alias, lost when the data is written back
+    static ref RE_UNSAFE: Regex = Regex::new(r"(?:^|\s)#|(?:^|[\s:\[{,-])[&*][^\s*]").unwrap();
+}
+
+/// Format the YAML front matter made of `lines`, including its delimiters,
+/// returning None when it must be kept as is
+pub fn format_front_matter(lines: &[&str], options: &FrontMatterOptions) -> Option<String> {
+    let (opening, body, closing) = (lines[0], &lines[1..lines.len() - 1], lines[lines.len() - 1]);
+    if opening != "---" || body.iter().any(|line| RE_UNSAFE.is_match(line).unwrap_or(true)) {
+        return None;
+    }
+    let source = body.iter().map(|line| format!("{}\n", line)).collect::<String>();
+    let Value::Mapping(mapping) = serde_yaml::from_str::<Value>(&source).ok()? else {
+        return None;
+    };
+
+    let data = Value::Mapping(order_keys(mapping, options));
+    let mut yaml = String::new();
+    write_value(&data, 0, &mut yaml);
+    // Round trip: the formatted front matter must hold the same data
+    if serde_yaml::from_str::<Value>(&yaml).ok()? != data {
+        return None;
+    }
+    Some(format!("{}\n{}{}", opening, yaml, closing))
+}
+
+/// Put the keys of the configured order first, followed by the others, sorted
+/// if enabled
+fn order_keys(mapping: Mapping, options: &FrontMatterOptions) -> Mapping {
+    let rank = |key: &Value| {
+        let position = key.as_str().and_then(|key| options.key_order.iter().position(|k| k == key));
+        position.unwrap_or(options.key_order.len())
+    };
+    let mut entries = mapping.into_iter().collect::<Vec<_>>();
+    // The sort is stable, keeping the original order of unsorted keys
+    entries.sort_by(|(a, _), (b, _)| {
+        let ordering = rank(a).cmp(&rank(b));
+        match options.sort_keys && rank(a) == options.key_order.len() {
+            true => ordering.then_with(|| scalar(a).cmp(&scalar(b))),
+            false => ordering,
+        }
+    });
+    entries.into_iter().collect()
+}
+
+/// YAML text of a scalar, quoted only when needed
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        _ => serde_yaml::to_string(value).unwrap_or_default().trim_end().to_string(),
+    }
+}
+
+/// Write a mapping or sequence value, its lines indented by `indent` spaces
+fn write_value(value: &Value, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                out.push_str(&format!("{}{}:", pad, scalar(key)));
+                write_nested(value, indent, out);
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                out.push_str(&format!("{}-", pad));
+                match item {
+                    // The first entry of a mapping item shares the line of its dash
+                    Value::Mapping(mapping) if !mapping.is_empty() => {
+                        let mut nested = String::new();
+                        write_value(item, indent + 2, &mut nested);
+                        out.push(' ');
+                        out.push_str(&nested[indent + 2..]);
+                    }
+                    _ => write_nested(item, indent, out),
+                }
+            }
+        }
+        _ => out.push_str(&format!("{}{}\n", pad, scalar(value))),
+    }
+}
+
+/// Write the value of a key or sequence item after its `:` or `-`, on the
+/// following lines when it is a non-empty collection
+fn write_nested(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Mapping(mapping) if !mapping.is_empty() => {
+            out.push('\n');
+            write_value(value, indent + 2, out);
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            out.push('\n');
+            write_value(value, indent + 2, out);
+        }
+        Value::Null => out.push('\n'),
+        _ => {
+            // Continuation lines of block scalars follow the indentation
+            let pad = " ".repeat(indent);
+            for (i, line) in scalar(value).lines().enumerate() {
+                match (i, line.is_empty()) {
+                    (0, _) => out.push_str(&format!(" {}\n", line)),
+                    (_, true) => out.push('\n'),
+                    (_, false) => out.push_str(&format!("{}{}\n", pad, line)),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,431 @@
+//! Heading lint rules, applied to the raw document lines before formatting
+
+use crate::{Diagnostic, HeadingCase, MdFormatConfig, RuleMode, front_matter_end};
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+lazy_static! {
+    // ATX heading: 1: opening '#' sequence, 2: heading text
+    static ref RE_ATX_HEADING: Regex = Regex::new(r"^(#{1,6})(?:[ \t]+(.*))?$").unwrap();
+    // Title of YAML (title: ...) or TOML (title = ...) front matter
+    static ref RE_FRONT_MATTER_TITLE: Regex = Regex::new(r"^title[ \t]*[:=][ \t]*(.+)$").unwrap();
+    // Optional closing sequence of an ATX heading
+    static ref RE_CLOSING_HASHES: Regex = Regex::new(r"(?:^|[ \t]+)#+[ \t]*$").unwrap();
+    // Line made of a single bold span: 1: bold text
+    static ref RE_BOLD_LINE: Regex = Regex::new(r"^(?:\*\*(?!\s)((?:(?!\*\*).)+?)(?<!\s)\*\*|__(?!\s)((?:(?!__).)+?)(?<!\s)__)$").unwrap();
+    // Closing hashes and attribute blocks at the end of a heading text
+    static ref RE_HEADING_SUFFIX: Regex = Regex::new(r"(?:[ \t]+#+|[ \t]*\{[^}]*\})*[ \t]*$").unwrap();
+    // Spans of a heading text left alone by the capitalization rule:
+    // code spans, link destinations, attribute blocks, HTML tags and URLs
+    static ref RE_CASE_PROTECTED: Regex =
+        Regex::new(r"`[^`]*`|\]\([^)]*\)|\{[^}]*\}|<[^>]*>|https?://\S+").unwrap();
+    // Manual numbering prefix of a heading text: "1.2.3 ", "1) ", "第三章 ", "一、", "(2) "
+    static ref RE_NUMBERING_PREFIX: Regex = Regex::new(concat!(
+        r"^(?:\d+(?:\.\d+)*\.?[ \t]+",
+        r"|\d+[)、][ \t]*",
+        r"|第[0-9一二三四五六七八九十百千零〇两]+[章节篇部条回卷][ \t、.．:：]*",
+        r"|[一二三四五六七八九十]+[、.．][ \t]*",
+        r"|[(（][0-9一二三四五六七八九十]+[)）][ \t]*)"
+    ))
+    .unwrap();
+}
+
+/// An ATX heading of the document
+struct Heading {
+    /// Index of the heading line
+    index: usize,
+    /// Heading level 1-6
+    level: usize,
+}
+
+/// Find ATX headings outside front matter and fenced code blocks
+fn find_headings(lines: &[String]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut in_code = false;
+    let start = front_matter_end(lines).map_or(0, |end| end + 1);
+
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        if let Ok(Some(caps)) = RE_ATX_HEADING.captures(line) {
+            headings.push(Heading {
+                index,
+                level: caps.get(1).unwrap().as_str().len(),
+            });
+        }
+    }
+
+    headings
+}
+
+/// Find paragraphs made of a single bold line, returning (index, bold text)
+fn find_bold_lines(lines: &[String]) -> Vec<(usize, String)> {
+    let mut bold_lines = Vec::new();
+    let mut in_code = false;
+    let start = front_matter_end(lines).map_or(0, |end| end + 1);
+    let is_blank = |index: Option<usize>| index.and_then(|i| lines.get(i)).is_none_or(|line| line.trim().is_empty());
+
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || !is_blank(index.checked_sub(1).filter(|&i| i >= start)) || !is_blank(Some(index + 1)) {
+            continue;
+        }
+        if let Ok(Some(caps)) = RE_BOLD_LINE.captures(line) {
+            let text = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            bold_lines.push((index, text.to_string()));
+        }
+    }
+
+    bold_lines
+}
+
+/// Replace the level of the heading line
+fn set_level(line: &str, old_level: usize, new_level: usize) -> String {
+    format!("{}{}", "#".repeat(new_level), &line[old_level..])
+}
+
+/// Text of a heading line with the closing sequence removed and whitespace
+/// collapsed, used to compare headings
+fn heading_text(line: &str, level: usize) -> String {
+    RE_CLOSING_HASHES
+        .replace(&line[level..], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Report headings with the same text and level as an earlier heading
+fn check_duplicates(
+    lines: &[String],
+    headings: &[Heading],
+    line_offset: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut seen: HashMap<(usize, String), usize> = HashMap::new();
+    for heading in headings {
+        let text = heading_text(&lines[heading.index], heading.level);
+        if text.is_empty() {
+            continue;
+        }
+        let line = line_offset + heading.index + 1;
+        if let Some(first) = seen.get(&(heading.level, text.clone())) {
+            diagnostics.push(Diagnostic {
+                line,
+                rule: "duplicate-heading",
+                message: format!(
+                    "duplicate H{} heading '{}' (first at line {})",
+                    heading.level, text, first
+                ),
+                fixed: false,
+            });
+        } else {
+            seen.insert((heading.level, text), line);
+        }
+    }
+}
+
+/// Title from the front matter, if any
+fn front_matter_title(lines: &[String]) -> Option<String> {
+    let end = front_matter_end(lines)?;
+    lines[1..end].iter().find_map(|line| {
+        let caps = RE_FRONT_MATTER_TITLE.captures(line).ok()??;
+        let title = caps[1].trim();
+        let title = title
+            .strip_prefix('"')
+            .and_then(|t| t.strip_suffix('"'))
+            .or_else(|| title.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))
+            .unwrap_or(title);
+        (!title.is_empty()).then(|| title.to_string())
+    })
+}
+
+/// Enforce that the document starts with exactly one H1
+///
+/// In fix mode, sections of extra H1 headings are demoted one level, and a
+/// missing H1 from the front matter title (if enabled) is returned as the
+/// (index, line) to insert.
+fn check_single_h1(
+    lines: &mut [String],
+    config: &MdFormatConfig,
+    line_offset: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<(usize, String)> {
+    let mode = config.headings.single_h1;
+    let headings = find_headings(lines);
+    let body_start = front_matter_end(lines).map_or(0, |end| end + 1);
+    let first_line = (body_start..lines.len()).find(|&i| !lines[i].is_empty());
+    let h1s = headings.iter().filter(|h| h.level == 1).collect::<Vec<_>>();
+
+    // Extra H1 headings
+    if let Some(first) = h1s.first() {
+        for heading in &h1s[1..] {
+            diagnostics.push(Diagnostic {
+                line: line_offset + heading.index + 1,
+                rule: "single-h1",
+                message: format!(
+                    "multiple H1 headings (first at line {})",
+                    line_offset + first.index + 1
+                ),
+                fixed: mode == RuleMode::Fix,
+            });
+        }
+        if mode == RuleMode::Fix
+            && let Some(second) = h1s.get(1)
+        {
+            for heading in headings.iter().filter(|h| h.index >= second.index && h.level < 6) {
+                lines[heading.index] = set_level(&lines[heading.index], heading.level, heading.level + 1);
+            }
+        }
+    }
+
+    // The first line must be the H1
+    let first_line = first_line?;
+    if h1s.first().is_some_and(|h| h.index == first_line) {
+        return None;
+    }
+    let title = if mode == RuleMode::Fix && h1s.is_empty() && config.headings.front_matter_title {
+        front_matter_title(lines)
+    } else {
+        None
+    };
+    diagnostics.push(Diagnostic {
+        line: line_offset + first_line + 1,
+        rule: "first-line-h1",
+        message: "document should start with an H1 heading".to_string(),
+        fixed: title.is_some(),
+    });
+    title.map(|title| (first_line, format!("# {}", title)))
+}
+
+/// Remove the manual numbering prefix of a heading line
+fn strip_numbering(line: &str, level: usize) -> Option<String> {
+    let text = line[level..].trim_start();
+    let m = RE_NUMBERING_PREFIX.find(text).ok()??;
+    // Keep headings that consist of a number only
+    if m.end() == text.len() {
+        return None;
+    }
+    Some(format!("{} {}", &line[..level], &text[m.end()..]))
+}
+
+/// Remove trailing punctuation from the text of a heading line, keeping its
+/// closing sequence and attributes
+fn strip_trailing_punctuation(line: &str, level: usize, chars: &str) -> Option<String> {
+    let text = &line[level..];
+    let suffix = RE_HEADING_SUFFIX.find(text).ok()??.start();
+    let body = text[..suffix].trim_end_matches(|c| chars.contains(c));
+    // Keep headings made of punctuation only
+    if body.len() == suffix || body.trim().is_empty() {
+        return None;
+    }
+    Some(format!("{}{}{}", &line[..level], body.trim_end(), &text[suffix..]))
+}
+
+/// Minor words kept lowercase in title case (unless first or last)
+const TITLE_CASE_MINOR_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "per", "so",
+    "the", "to", "up", "via", "vs", "yet",
+];
+
+/// Uppercase the first letter of a word, keeping leading punctuation
+fn capitalize_word(word: &str) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((i, c)) => format!("{}{}{}", &word[..i], c.to_uppercase(), &word[i + c.len_utf8()..]),
+        None => word.to_string(),
+    }
+}
+
+/// Whether the word has capitals after its first letter (acronyms,
+/// camelCase names), which are kept as written
+fn has_inner_capitals(word: &str) -> bool {
+    word.chars()
+        .filter(|c| c.is_alphabetic())
+        .skip(1)
+        .any(|c| c.is_uppercase())
+}
+
+/// Apply the configured capitalization to a heading line
+fn capitalize_heading(line: &str, level: usize, config: &MdFormatConfig) -> String {
+    let exceptions = &config.headings.capitalization_exceptions;
+    let title_case = config.headings.capitalization == HeadingCase::Title;
+
+    // Split the text into words, protected spans are kept as single tokens
+    let text = &line[level..];
+    let mut tokens: Vec<(bool, &str)> = Vec::new();
+    let mut last = 0;
+    for m in RE_CASE_PROTECTED.find_iter(text).flatten() {
+        tokens.extend(text[last..m.start()].split_inclusive(char::is_whitespace).map(|w| (false, w)));
+        tokens.push((true, m.as_str()));
+        last = m.end();
+    }
+    tokens.extend(text[last..].split_inclusive(char::is_whitespace).map(|w| (false, w)));
+
+    let word_positions = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, (protected, word))| !protected && word.chars().any(char::is_alphabetic))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let (first, last) = (word_positions.first().copied(), word_positions.last().copied());
+
+    let mut result = line[..level].to_string();
+    for (i, (protected, token)) in tokens.iter().enumerate() {
+        if *protected || !word_positions.contains(&i) {
+            result.push_str(token);
+            continue;
+        }
+        let word = token.trim_end();
+        let space = &token[word.len()..];
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+        let fixed = if let Some(exception) = exceptions.iter().find(|e| e.eq_ignore_ascii_case(bare)) {
+            word.replacen(bare, exception, 1)
+        } else if has_inner_capitals(word) {
+            word.to_string()
+        } else if Some(i) == first {
+            capitalize_word(&word.to_lowercase())
+        } else if !title_case
+            || (Some(i) != last && TITLE_CASE_MINOR_WORDS.contains(&bare.to_lowercase().as_str()))
+        {
+            word.to_lowercase()
+        } else {
+            capitalize_word(&word.to_lowercase())
+        };
+        result.push_str(&fixed);
+        result.push_str(space);
+    }
+    result
+}
+
+/// Apply the enabled heading rules, fixing lines in place or reporting issues
+///
+/// `line_offset` is the number of input lines before `lines[0]`, used to
+/// report line numbers of the original input.
+pub fn apply_heading_rules(
+    lines: &mut Vec<String>,
+    config: &MdFormatConfig,
+    line_offset: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if config.headings.strip_numbering {
+        for heading in find_headings(lines) {
+            if let Some(line) = strip_numbering(&lines[heading.index], heading.level) {
+                lines[heading.index] = line;
+            }
+        }
+    }
+
+    let bold_headings = config.headings.bold_headings;
+    if bold_headings != RuleMode::Ignore {
+        for (index, text) in find_bold_lines(lines) {
+            diagnostics.push(Diagnostic {
+                line: line_offset + index + 1,
+                rule: "bold-heading",
+                message: format!("bold line used as heading: '{}'", text),
+                fixed: bold_headings == RuleMode::Fix,
+            });
+            if bold_headings == RuleMode::Fix {
+                lines[index] = format!("{} {}", "#".repeat(config.headings.bold_heading_level as usize), text);
+            }
+        }
+    }
+
+    // Inserted lines are added last, so other rules report input line numbers
+    let missing_h1 = if config.headings.single_h1 != RuleMode::Ignore {
+        check_single_h1(lines, config, line_offset, diagnostics)
+    } else {
+        None
+    };
+
+    let headings = find_headings(lines);
+
+    if config.headings.check_duplicates {
+        check_duplicates(lines, &headings, line_offset, diagnostics);
+    }
+
+    let skipped_levels = config.headings.skipped_levels;
+    if skipped_levels != RuleMode::Ignore {
+        for pair in headings.windows(2) {
+            if pair[1].level > pair[0].level + 1 {
+                diagnostics.push(Diagnostic {
+                    line: line_offset + pair[1].index + 1,
+                    rule: "heading-increment",
+                    message: format!(
+                        "heading level skipped: H{} -> H{}",
+                        pair[0].level, pair[1].level
+                    ),
+                    fixed: skipped_levels == RuleMode::Fix,
+                });
+            }
+        }
+    }
+    if skipped_levels == RuleMode::Fix {
+        // Stack of (original level, new level) of the enclosing sections
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+        for heading in &headings {
+            while stack.last().is_some_and(|&(level, _)| level >= heading.level) {
+                stack.pop();
+            }
+            let new_level = stack
+                .last()
+                .map_or(heading.level, |&(_, parent)| (parent + 1).min(heading.level));
+            if new_level != heading.level {
+                lines[heading.index] = set_level(&lines[heading.index], heading.level, new_level);
+            }
+            stack.push((heading.level, new_level));
+        }
+    }
+
+    let trailing_punctuation = config.headings.trailing_punctuation;
+    if trailing_punctuation != RuleMode::Ignore {
+        for heading in &headings {
+            let chars = &config.headings.trailing_punctuation_chars;
+            if let Some(line) = strip_trailing_punctuation(&lines[heading.index], heading.level, chars) {
+                diagnostics.push(Diagnostic {
+                    line: line_offset + heading.index + 1,
+                    rule: "heading-punctuation",
+                    message: "heading ends with punctuation".to_string(),
+                    fixed: trailing_punctuation == RuleMode::Fix,
+                });
+                if trailing_punctuation == RuleMode::Fix {
+                    lines[heading.index] = line;
+                }
+            }
+        }
+    }
+
+    if config.headings.capitalization != HeadingCase::Off {
+        for heading in &headings {
+            let line = &lines[heading.index];
+            let fixed = capitalize_heading(line, heading.level, config);
+            if fixed != *line {
+                diagnostics.push(Diagnostic {
+                    line: line_offset + heading.index + 1,
+                    rule: "heading-case",
+                    message: format!(
+                        "heading should be in {} case: '{}'",
+                        match config.headings.capitalization {
+                            HeadingCase::Title => "title",
+                            _ => "sentence",
+                        },
+                        fixed[heading.level..].trim()
+                    ),
+                    fixed: true,
+                });
+                lines[heading.index] = fixed;
+            }
+        }
+    }
+
+    if let Some((index, line)) = missing_h1 {
+        lines.insert(index, line);
+    }
+}
@@ -0,0 +1,615 @@
+//! Inline span rules applied to prose by `format_text`
+
+use crate::{
+    AmountSpacing, CjkPunctuation, CjkScript, Diagnostic, MdFormatConfig, PunctuationStyle, QuoteStyle, RuleMode, SpacingOptions,
+    front_matter_end, protect_spans, restore_spans,
+};
+use fancy_regex::{Captures, Regex};
+use lazy_static::lazy_static;
+use unicode_script::{Script, UnicodeScript};
+
+lazy_static! {
+    // Link destinations (inline and in reference definitions), autolinks, bare URLs
+    // and HTML tags, never rewritten by inline rules
+    static ref RE_URL: Regex = Regex::new(concat!(
+        r"\]\([^)]*\)|\]:[ \t]*(?:<[^>]*>|\S+)|<[a-zA-Z][a-zA-Z0-9+.-]*:[^>\s]*>|https?://[^\s<>)\]]+",
+        r"|</?[a-zA-Z][^<>]*>|<!--.*?-->"
+    ))
+    .unwrap();
+    // Straight double-quoted span: 1: content
+    static ref RE_DOUBLE_QUOTED: Regex = Regex::new(r#""([^"]*)""#).unwrap();
+    // Straight single-quoted span, not an apostrophe: 1: content
+    static ref RE_SINGLE_QUOTED: Regex = Regex::new(r"(?<![A-Za-z0-9'])'(?!\s)([^']*?)(?<!\s)'(?![A-Za-z0-9'])").unwrap();
+    // Apostrophe before a decade ('90s)
+    static ref RE_DECADE: Regex = Regex::new(r"'(?=\d0s\b)").unwrap();
+    // Apostrophe inside or at the end of a word
+    static ref RE_APOSTROPHE: Regex = Regex::new(r"(?<=[A-Za-z0-9])'").unwrap();
+    // Em dash "---" and en dash "--" between spaces or digits (not "--flag" or "<!--")
+    static ref RE_EM_DASH: Regex = Regex::new(r"(?<=[\w\s])---(?=\s|$)|(?<=\w)---(?=\w)").unwrap();
+    static ref RE_EN_DASH: Regex = Regex::new(r"(?<=\d)--(?=\d)|(?<=\s)--(?=\s)").unwrap();
+    // Three dots not part of a longer run
+    static ref RE_ELLIPSIS: Regex = Regex::new(r"(?<!\.)\.\.\.(?!\.)").unwrap();
+    // Spaced dots ". . ." not part of a longer run
+    static ref RE_SPACED_ELLIPSIS: Regex = Regex::new(r"(?<!\.)(?<!\. )\. \. \.(?! ?\.)").unwrap();
+    // Code span, with a backtick run of any length
+    static ref RE_CODE_SPAN: Regex = Regex::new(r"(`+).+?(?<!`)\1(?!`)").unwrap();
+    // Spaces before or after full-width punctuation, never at the start of a line
+    static ref RE_FULLWIDTH_PUNCTUATION_SPACE: Regex = Regex::new(
+        r"(?<=\S)[ \t]+(?=[，。、；：？！「」『』（）【】《》〈〉〔〕．])|(?<=[，。、；：？！「」『』（）【】《》〈〉〔〕．])[ \t]+(?=\S)"
+    )
+    .unwrap();
+    // Parenthesized text without nested parentheses: 1: content
+    static ref RE_PARENTHESIZED: Regex = Regex::new(r"\(([^()\n]*)\)").unwrap();
+    // GFM strikethrough: 1: content
+    static ref RE_STRIKETHROUGH: Regex = Regex::new(r"~~(?![\s~])(.+?)(?<![\s~])~~").unwrap();
+    // Strong and emphasis spans with either marker: 1-4: content
+    static ref RE_EMPHASIS: Regex = Regex::new(concat!(
+        r"(?<![\\*])\*\*(?![\s*])(.+?)(?<![\s\\*])\*\*(?!\*)",
+        r"|(?<![\\_])__(?![\s_])(.+?)(?<![\s\\_])__(?!_)",
+        r"|(?<![\\*])\*(?![\s*])(.+?)(?<![\s\\*])\*(?!\*)",
+        r"|(?<![\\_A-Za-z0-9])_(?![\s_])(.+?)(?<![\s\\_])_(?![_A-Za-z0-9])"
+    ))
+    .unwrap();
+    // Link or image with its destination protected by `outside_urls`, or with a
+    // reference label: 1: text
+    static ref RE_LINK_SPAN: Regex =
+        Regex::new(r"!?\[([^\[\]\x00]+)(?:\x00MDURL\d+\x00|\]\[[^\[\]]*\]|\])").unwrap();
+    // Obsidian highlight: 1: content
+    static ref RE_HIGHLIGHT: Regex = Regex::new(r"(?<!=)==(?![\s=])(.+?)(?<![\s=])==(?!=)").unwrap();
+    // CriticMarkup addition, deletion, substitution, comment or highlight
+    pub static ref RE_CRITIC_MARKUP: Regex =
+        Regex::new(r"\{(?:\+\+.*?\+\+|--.*?--|~~.*?~>.*?~~|>>.*?<<|==.*?==)\}").unwrap();
+    // Placeholder of a protected CriticMarkup span: 1: index
+    static ref RE_CRITIC_PLACEHOLDER: Regex = Regex::new(r"\x00MDCRITIC(\d+)\x00").unwrap();
+    // Pandoc superscript and subscript, which can't contain unescaped spaces: 1: content
+    pub static ref RE_SUB_SUPERSCRIPT: Regex = Regex::new(concat!(
+        r"(?<![\\^])\^((?:[^\s^\\]|\\.)+)\^",
+        r"|(?<![\\~])~(?!~)((?:[^\s~\\]|\\.)+)~(?!~)"
+    ))
+    .unwrap();
+    // Inline math: $...$ (not a price like $5), $$...$$ and \(...\)
+    pub static ref RE_MATH: Regex = Regex::new(concat!(
+        r"(?<![\\$])\$\$(?!\s)[^$\n]+?(?<!\s)\$\$",
+        r"|(?<![\\$])\$(?![\s$])(?:[^$\\\n]|\\.)+?(?<![\s\\])\$(?![\d$])",
+        r"|\\\(.+?\\\)"
+    ))
+    .unwrap();
+    // Placeholder of a protected math span
+    static ref RE_MATH_PLACEHOLDER: Regex = Regex::new(r"\x00MDMATH\d+\x00").unwrap();
+    // Obsidian embeds (![[image.png|200]]), Obsidian image sizes (![alt|300x200](...))
+    // and kramdown attribute lists ({: width="300"}), which must stay as written
+    pub static ref RE_MEDIA_ATTRIBUTE: Regex =
+        Regex::new(r"!?\[\[[^\[\]]+\]\]|\|\d+(?:x\d+)?(?=\]\()|\{:[^{}]*\}").unwrap();
+    // Strong emphasis with either marker, not inside a word: 1: marker, 2: content
+    static ref RE_STRONG: Regex =
+        Regex::new(r"(?<![\w\\*_])(\*\*|__)(?![\s*_])(.+?)(?<![\s\\*_])\1(?![\w*_])").unwrap();
+}
+
+/// Apply `rule` to the text with URLs and HTML tags protected
+pub fn outside_urls(text: &str, rule: impl Fn(&str) -> String) -> String {
+    let mut text = text.to_string();
+    let urls = protect_spans(&mut text, &RE_URL, "MDURL");
+    let mut text = rule(&text);
+    restore_spans(&mut text, &urls, "MDURL");
+    text
+}
+
+/// Rewrite strong emphasis with the given marker (`**` or `__`)
+///
+/// Spans whose content starts or ends with emphasis markers (`**_x_**`) are
+/// left alone so nested emphasis stays unambiguous.
+pub fn normalize_strong(text: &str, marker: &str) -> String {
+    outside_urls(text, |text| {
+        RE_STRONG
+            .replace_all(text, |caps: &Captures| format!("{}{}{}", marker, &caps[2], marker))
+            .to_string()
+    })
+}
+
+/// Whether CJK-ASCII spacing separates the two adjacent characters, CJK
+/// characters being those of `scripts`
+pub fn needs_space(a: char, b: char, scripts: &[CjkScript]) -> bool {
+    let cjk = |c: char| scripts.iter().any(|script| script.contains(c));
+    (cjk(a) && b.is_ascii_alphanumeric()) || (a.is_ascii_alphanumeric() && cjk(b))
+}
+
+/// Remove single spaces (or inserted separators) between CJK characters of
+/// `scripts` and ASCII letters or digits, the inverse of CJK-ASCII spacing
+pub fn strip_cjk_ascii_spaces(text: &str, spacing: &SpacingOptions) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let single = matches!(c, ' ' | '\u{2009}' | '\u{00A0}') && i > 0 && i + 1 < chars.len();
+        if single && needs_space(chars[i - 1], chars[i + 1], &spacing.cjk_scripts) {
+            continue;
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Convert full-width letters and digits to their ASCII forms
+pub fn halfwidth_alphanumerics(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Whether the character is a full-width CJK punctuation mark, which is never
+/// separated from its neighbours by a space
+pub fn is_fullwidth_punctuation(c: char) -> bool {
+    "，。、；：？！「」『』（）【】《》〈〉〔〕．".contains(c)
+}
+
+/// Remove the spaces before and after full-width punctuation marks
+pub fn trim_around_fullwidth_punctuation(text: &str) -> String {
+    outside_urls(text, |text| RE_FULLWIDTH_PUNCTUATION_SPACE.replace_all(text, "").to_string())
+}
+
+/// Longest of the symbols starting the text
+fn starting_symbol<'a>(text: &str, symbols: &'a [String]) -> Option<&'a str> {
+    symbols
+        .iter()
+        .filter(|symbol| !symbol.is_empty() && text.starts_with(symbol.as_str()))
+        .max_by_key(|symbol| symbol.len())
+        .map(|symbol| symbol.as_str())
+}
+
+/// Join the configured unit symbols to the preceding number (`50 %` to `50%`)
+/// and currency symbols to the following one (`¥ 100` to `¥100`), spacing
+/// them from CJK characters like the number itself unless amounts are attached
+pub fn join_units(text: &str, spacing: &SpacingOptions) -> String {
+    let cjk = |c: char| spacing.cjk_scripts.iter().any(|script| script.contains(c));
+    let space = spacing.amounts == AmountSpacing::Space;
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(currency) = starting_symbol(rest, &spacing.currencies) {
+            let number = rest[currency.len()..].trim_start_matches([' ', '\t']);
+            if number.starts_with(|c: char| c.is_ascii_digit()) {
+                if space && result.chars().next_back().is_some_and(cjk) {
+                    result.push(spacing.cjk_separator.as_char());
+                }
+                result.push_str(currency);
+                rest = number;
+                continue;
+            }
+        }
+        if result.ends_with(|c: char| c.is_ascii_digit()) {
+            let trimmed = rest.trim_start_matches([' ', '\t']);
+            let unit = spacing
+                .units
+                .iter()
+                .filter(|unit| !unit.is_empty() && trimmed.starts_with(unit.as_str()))
+                .max_by_key(|unit| unit.len());
+            if let Some(unit) = unit {
+                result.push_str(unit);
+                rest = &trimmed[unit.len()..];
+                if space && rest.chars().next().is_some_and(cjk) {
+                    result.push(spacing.cjk_separator.as_char());
+                }
+                continue;
+            }
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+/// Length of the amount starting the text: a number with a currency symbol,
+/// a unit or a time (`3:00`, `3PM`)
+fn amount_len(text: &str, spacing: &SpacingOptions) -> Option<usize> {
+    let digits = |text: &str| text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let currency = starting_symbol(text, &spacing.currencies);
+    let mut len = currency.map_or(0, str::len);
+    let mut time = false;
+    let integer = digits(&text[len..]);
+    if integer == 0 {
+        return None;
+    }
+    len += integer;
+    while let Some(separator) = text[len..].chars().next().filter(|c| matches!(c, '.' | ',' | ':')) {
+        let fraction = digits(&text[len + 1..]);
+        if fraction == 0 {
+            break;
+        }
+        time |= separator == ':';
+        len += 1 + fraction;
+    }
+    let rest = &text[len..];
+    if ["AM", "PM", "am", "pm"].iter().any(|suffix| rest.starts_with(suffix))
+        && !rest[2..].starts_with(|c: char| c.is_ascii_alphanumeric())
+    {
+        time = true;
+        len += 2;
+    }
+    let unit = starting_symbol(&text[len..], &spacing.units);
+    len += unit.map_or(0, str::len);
+    (currency.is_some() || unit.is_some() || time).then_some(len)
+}
+
+/// Replace the amounts (`¥100`, `50%`, `25°C`, `3:00PM`) with placeholders,
+/// returning them for `restore_spans`
+pub fn protect_amounts(text: &mut String, spacing: &SpacingOptions, tag: &str) -> Vec<String> {
+    let mut amounts = Vec::new();
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    let mut prev = None;
+    while let Some(c) = rest.chars().next() {
+        let boundary = !prev.is_some_and(|prev: char| prev.is_ascii_alphanumeric() || prev == '.');
+        if let Some(len) = amount_len(rest, spacing).filter(|_| boundary) {
+            result.push_str(&format!("\x00{}{}\x00", tag, amounts.len()));
+            amounts.push(rest[..len].to_string());
+            prev = rest[..len].chars().next_back();
+            rest = &rest[len..];
+            continue;
+        }
+        result.push(c);
+        prev = Some(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    *text = result;
+    amounts
+}
+
+/// Replace each occurrence of the terms with a placeholder, longest terms
+/// first, returning the spans for `restore_spans`
+pub fn protect_terms(text: &mut String, terms: &[String], tag: &str) -> Vec<String> {
+    let mut order: Vec<usize> = (0..terms.len()).filter(|&i| !terms[i].is_empty()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(terms[i].len()));
+    for i in order {
+        *text = text.replace(&terms[i], &format!("\x00{}{}\x00", tag, i));
+    }
+    terms.to_vec()
+}
+
+/// Add CJK-ASCII spaces outside the markers of the spans matched by `re`,
+/// comparing the characters around the span with the first and last
+/// characters of its content (capture group 1)
+///
+/// Spaces are never inserted between the markers and the content.
+fn space_around_spans(text: &str, re: &Regex, spacing: &SpacingOptions) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in re.captures_iter(text).flatten() {
+        let span = caps.get(0).unwrap();
+        // Alternatives of the pattern capture the content in different groups
+        let content = caps.iter().skip(1).flatten().next().map_or("", |m| m.as_str());
+        let before = text[..span.start()].chars().next_back();
+        let after = text[span.end()..].chars().next();
+        let (first, end) = (content.chars().next(), content.chars().next_back());
+
+        result.push_str(&text[last..span.start()]);
+        if let (Some(before), Some(first)) = (before, first)
+            && needs_space(before, first, &spacing.cjk_scripts)
+        {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        result.push_str(span.as_str());
+        if let (Some(end), Some(after)) = (end, after)
+            && needs_space(end, after, &spacing.cjk_scripts)
+        {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        last = span.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Add CJK-ASCII spaces around `~~strikethrough~~` spans
+pub fn space_around_strikethrough(text: &str, spacing: &SpacingOptions) -> String {
+    space_around_spans(text, &RE_STRIKETHROUGH, spacing)
+}
+
+/// Add CJK-ASCII spaces around `**strong**` and `*emphasis*` spans
+pub fn space_around_emphasis(text: &str, spacing: &SpacingOptions) -> String {
+    space_around_spans(text, &RE_EMPHASIS, spacing)
+}
+
+/// Add CJK-ASCII spaces around links and images, comparing the text outside
+/// with the link text; the destinations must be protected by `outside_urls`
+pub fn space_around_links(text: &str, spacing: &SpacingOptions) -> String {
+    space_around_spans(text, &RE_LINK_SPAN, spacing)
+}
+
+/// Add CJK-ASCII spaces around `==highlight==` spans
+pub fn space_around_highlight(text: &str, spacing: &SpacingOptions) -> String {
+    space_around_spans(text, &RE_HIGHLIGHT, spacing)
+}
+
+/// Add CJK-ASCII spaces around Pandoc `^superscript^` and `~subscript~` spans
+pub fn space_around_sub_superscript(text: &str, spacing: &SpacingOptions) -> String {
+    space_around_spans(text, &RE_SUB_SUPERSCRIPT, spacing)
+}
+
+/// Add CJK-ASCII spaces around protected CriticMarkup spans, comparing the
+/// text outside with the first and last characters inside the markers
+pub fn space_around_critic_markup(text: &str, spans: &[String], spacing: &SpacingOptions) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in RE_CRITIC_PLACEHOLDER.captures_iter(text).flatten() {
+        let placeholder = caps.get(0).unwrap();
+        let Some(span) = caps[1].parse::<usize>().ok().and_then(|i| spans.get(i)) else {
+            continue;
+        };
+        // Markers are "{++" and "++}", "{~~" and "~~}"...
+        let content = &span[3..span.len() - 3];
+        let before = text[..placeholder.start()].chars().next_back();
+        let after = text[placeholder.end()..].chars().next();
+
+        result.push_str(&text[last..placeholder.start()]);
+        if let (Some(before), Some(first)) = (before, content.chars().next())
+            && needs_space(before, first, &spacing.cjk_scripts)
+        {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        result.push_str(placeholder.as_str());
+        if let (Some(end), Some(after)) = (content.chars().next_back(), after)
+            && needs_space(end, after, &spacing.cjk_scripts)
+        {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        last = placeholder.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Add CJK-ASCII spaces between protected math spans and CJK characters,
+/// math being typeset like Latin text whatever its content
+pub fn space_around_math(text: &str, spacing: &SpacingOptions) -> String {
+    let cjk = |c: Option<char>| c.is_some_and(|c| spacing.cjk_scripts.iter().any(|script| script.contains(c)));
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for m in RE_MATH_PLACEHOLDER.find_iter(text).flatten() {
+        result.push_str(&text[last..m.start()]);
+        if cjk(text[..m.start()].chars().next_back()) {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        result.push_str(m.as_str());
+        if cjk(text[m.end()..].chars().next()) {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        last = m.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Whether the text contains Chinese or Japanese characters
+fn has_cjk(text: &str) -> bool {
+    text.chars()
+        .any(|c| matches!(c.script(), Script::Han | Script::Hiragana | Script::Katakana))
+}
+
+/// Convert straight quotes to curly quotes (corner brackets around CJK
+/// text if enabled), `...` to `…`, `---` to `—` and `--` to `–`
+pub fn smart_punctuation(text: &str, cjk_quotes: QuoteStyle) -> String {
+    let corner = cjk_quotes == QuoteStyle::Corner;
+    outside_urls(text, |text| {
+        let text = RE_DECADE.replace_all(text, "’");
+        let text = RE_DOUBLE_QUOTED.replace_all(&text, |caps: &Captures| {
+            let (open, close) = if corner && has_cjk(&caps[1]) { ("「", "」") } else { ("“", "”") };
+            format!("{}{}{}", open, &caps[1], close)
+        });
+        let text = RE_SINGLE_QUOTED.replace_all(&text, |caps: &Captures| {
+            let (open, close) = if corner && has_cjk(&caps[1]) { ("『", "』") } else { ("‘", "’") };
+            format!("{}{}{}", open, &caps[1], close)
+        });
+        let text = RE_APOSTROPHE.replace_all(&text, "’");
+        let text = RE_EM_DASH.replace_all(&text, "—");
+        let text = RE_EN_DASH.replace_all(&text, "–");
+        RE_ELLIPSIS.replace_all(&text, "…").to_string()
+    })
+}
+
+/// Convert curly quotes back to straight quotes, `…` to `...`, `—` to `---`
+/// and `–` to `--`
+pub fn straight_punctuation(text: &str) -> String {
+    outside_urls(text, |text| {
+        let mut ret = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '“' | '”' | '„' => ret.push('"'),
+                '‘' | '’' | '‚' => ret.push('\''),
+                '—' => ret.push_str("---"),
+                '–' => ret.push_str("--"),
+                '…' => ret.push_str("..."),
+                _ => ret.push(c),
+            }
+        }
+        ret
+    })
+}
+
+/// Write en/em dashes as `--`/`---` (ASCII) or `–`/`—` (Unicode)
+pub fn normalize_dashes(text: &str, style: PunctuationStyle) -> String {
+    outside_urls(text, |text| match style {
+        PunctuationStyle::Preserve => text.to_string(),
+        PunctuationStyle::Ascii => text.replace('—', "---").replace('–', "--"),
+        PunctuationStyle::Unicode => {
+            let text = RE_EM_DASH.replace_all(text, "—");
+            RE_EN_DASH.replace_all(&text, "–").to_string()
+        }
+    })
+}
+
+/// Write ellipses (including spaced `. . .`) as `...` (ASCII) or `…` (Unicode)
+pub fn normalize_ellipsis(text: &str, style: PunctuationStyle) -> String {
+    let ellipsis = match style {
+        PunctuationStyle::Preserve => return text.to_string(),
+        PunctuationStyle::Ascii => "...",
+        PunctuationStyle::Unicode => "…",
+    };
+    outside_urls(text, |text| {
+        let text = RE_SPACED_ELLIPSIS.replace_all(text, ellipsis);
+        match style {
+            PunctuationStyle::Unicode => RE_ELLIPSIS.replace_all(&text, ellipsis).to_string(),
+            _ => text.replace('…', ellipsis),
+        }
+    })
+}
+
+/// Whether the character belongs to a Chinese or Japanese sentence
+fn is_cjk_sentence_char(c: char) -> bool {
+    matches!(c.script(), Script::Han | Script::Hiragana | Script::Katakana) || is_fullwidth_punctuation(c)
+}
+
+/// Write the ASCII punctuation of Chinese/Japanese sentences in full width
+///
+/// Only marks following a Han or kana character are converted, so numbers
+/// (`3.14`), file names (`说明.md`) and image markers (`![`) are untouched,
+/// as are parentheses around text without any Han or kana.
+pub fn fullwidth_punctuation(text: &str, style: CjkPunctuation) -> String {
+    outside_urls(text, |text| {
+        let text = RE_PARENTHESIZED.replace_all(text, |caps: &Captures| {
+            if has_cjk(&caps[1]) {
+                format!("（{}）", &caps[1])
+            } else {
+                caps[0].to_string()
+            }
+        });
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::with_capacity(text.len());
+        for (i, &c) in chars.iter().enumerate() {
+            let prev = result.chars().next_back();
+            let next = chars.get(i + 1).copied();
+            let converted = match c {
+                _ if !prev.is_some_and(is_cjk_sentence_char) => None,
+                ',' if style == CjkPunctuation::Japanese => Some('、'),
+                ',' => Some('，'),
+                '.' if next.is_none_or(|n| n.is_whitespace() || is_cjk_sentence_char(n)) => Some('。'),
+                '?' => Some('？'),
+                '!' if next != Some('[') => Some('！'),
+                ':' => Some('：'),
+                ';' => Some('；'),
+                _ => None,
+            };
+            result.push(converted.unwrap_or(c));
+        }
+        RE_FULLWIDTH_PUNCTUATION_SPACE.replace_all(&result, "").to_string()
+    })
+}
+
+/// Shorten runs of one repeated full-width punctuation mark (`。。`, `！！！`)
+/// to `max` marks
+pub fn collapse_repeated_punctuation(text: &str, max: usize) -> String {
+    outside_urls(text, |text| {
+        let mut result = String::with_capacity(text.len());
+        let mut run = 0;
+        let mut last = None;
+        for c in text.chars() {
+            run = if last == Some(c) { run + 1 } else { 1 };
+            last = Some(c);
+            // Brackets and quotes nest, so only sentence marks are collapsed
+            if run > max && "，。、；：？！．".contains(c) {
+                continue;
+            }
+            result.push(c);
+        }
+        result
+    })
+}
+
+/// Name of an invisible character removed by `strip_invisible_chars`
+fn invisible_char_name(c: char) -> Option<&'static str> {
+    let name = match c {
+        '\u{200B}' => "zero width space",
+        '\u{200C}' => "zero width non-joiner",
+        '\u{2060}' => "word joiner",
+        '\u{FEFF}' => "byte order mark",
+        '\u{061C}' => "arabic letter mark",
+        '\u{200E}' => "left-to-right mark",
+        '\u{200F}' => "right-to-left mark",
+        '\u{202A}'..='\u{202E}' => "bidi embedding or override",
+        '\u{2066}'..='\u{2069}' => "bidi isolate",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Convert ideographic spaces (U+3000) in prose to regular spaces, outside
+/// front matter, fenced code blocks and code spans
+pub fn convert_ideographic_spaces(lines: &mut [String]) {
+    let start = front_matter_end(lines).map_or(0, |end| end + 1);
+    let mut in_code = false;
+    for line in lines.iter_mut().skip(start) {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || !line.contains('\u{3000}') {
+            continue;
+        }
+
+        let code_spans = RE_CODE_SPAN
+            .find_iter(line)
+            .map(|m| m.unwrap().range())
+            .collect::<Vec<_>>();
+        let converted = line
+            .char_indices()
+            .map(|(pos, c)| match c {
+                '\u{3000}' if !code_spans.iter().any(|range| range.contains(&pos)) => ' ',
+                _ => c,
+            })
+            .collect::<String>();
+        *line = converted.trim_end().to_string();
+    }
+}
+
+/// Report (and remove in fix mode) zero-width and bidi control characters in
+/// prose, outside front matter, fenced code blocks and code spans. A byte
+/// order mark at the very start of the document is left alone.
+pub fn strip_invisible_chars(
+    lines: &mut [String],
+    config: &MdFormatConfig,
+    line_offset: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mode = config.formatting.invisible_chars;
+    if mode == RuleMode::Ignore {
+        return;
+    }
+
+    let start = front_matter_end(lines).map_or(0, |end| end + 1);
+    let mut in_code = false;
+    for (index, line) in lines.iter_mut().enumerate().skip(start) {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let code_spans = RE_CODE_SPAN
+            .find_iter(line)
+            .map(|m| m.unwrap().range())
+            .collect::<Vec<_>>();
+        let mut cleaned = String::with_capacity(line.len());
+        for (column, (pos, c)) in line.char_indices().enumerate() {
+            let name = invisible_char_name(c)
+                .filter(|_| !code_spans.iter().any(|range| range.contains(&pos)))
+                .filter(|_| !(c == '\u{FEFF}' && index == 0 && pos == 0 && line_offset == 0));
+            let Some(name) = name else {
+                cleaned.push(c);
+                continue;
+            };
+            diagnostics.push(Diagnostic {
+                line: line_offset + index + 1,
+                rule: "invisible-char",
+                message: format!("invisible character U+{:04X} ({}) at column {}", c as u32, name, column + 1),
+                fixed: mode == RuleMode::Fix,
+            });
+            if mode != RuleMode::Fix {
+                cleaned.push(c);
+            }
+        }
+        *line = cleaned;
+    }
+}
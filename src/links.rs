@@ -0,0 +1,805 @@
+//! Link style rules
+
+use crate::{
+    Diagnostic, MdFormatConfig, RE_CODE_SPAN_CONTENT, RuleMode, anchors, front_matter_end, protect_spans, restore_spans,
+};
+use fancy_regex::{Captures, Regex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+lazy_static! {
+    // Spans never autolinked: link reference definitions, bracketed link texts
+    // with their destination, link destinations and autolinks/HTML tags, or 1: a bare URL
+    static ref RE_BARE_URL: Regex = Regex::new(concat!(
+        r"^[ \t]*\[[^\]]+\]:.*$|\[[^\]]*\](?:\([^)]*\))?|\]\([^)]*\)|<[^<>]*>",
+        r"|(?<![\w/=])(https?://[^\s<>]+)"
+    ))
+    .unwrap();
+    // Autolink of a web URL: 1: URL
+    static ref RE_ANGLE_AUTOLINK: Regex = Regex::new(r"<(https?://[^\s<>]+)>").unwrap();
+    // Inline link (not an image) to a web URL, without title: 1: text, 2: URL
+    static ref RE_URL_LINK: Regex = Regex::new(r"(?<!!)\[([^\[\]]+)\]\((https?://[^\s()]+)\)").unwrap();
+    // Link reference definition: 1: label, 2: destination, 3: optional title
+    static ref RE_LINK_DEFINITION: Regex = Regex::new(
+        r#"^ {0,3}\[([^\[\]]+)\]:[ \t]*(<[^<>]*>|\S+)(?:[ \t]+("[^"]*"|'[^']*'|\([^()]*\)))?[ \t]*$"#
+    )
+    .unwrap();
+    // Inline link (not an image): 1: text, 2: destination, 3: optional title
+    static ref RE_INLINE_LINK: Regex = Regex::new(concat!(
+        r"(?<![!\]\\])\[((?:[^\[\]]|\[[^\[\]]*\])*)\]",
+        r#"\([ \t]*(<[^<>]*>|[^\s()]+)(?:[ \t]+("[^"]*"|'[^']*'))?[ \t]*\)"#
+    ))
+    .unwrap();
+    // Destination and title of an inline link or image: 1: "](" and destination, 2: title
+    static ref RE_LINK_TITLE: Regex = Regex::new(concat!(
+        r"(\]\([ \t]*(?:<[^<>]*>|[^\s()]+))[ \t]+",
+        r#"("(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|\((?:[^()\\]|\\.)*\))[ \t]*\)"#
+    ))
+    .unwrap();
+    // Inline link or image destination with literal spaces, before the optional title: 1: destination
+    static ref RE_SPACED_DESTINATION: Regex = Regex::new(concat!(
+        r#"(?<=\]\()[ \t]*([^\s()<>]+(?:[ \t]+[^\s()<>"']+)+)"#,
+        r#"(?=(?:[ \t]+(?:"[^"]*"|'[^']*'|\([^()]*\)))?[ \t]*\))"#
+    ))
+    .unwrap();
+    // Destination of an inline link or image: 1: destination
+    static ref RE_LINK_DESTINATION: Regex = Regex::new(r"\]\([ \t]*(<[^<>]*>|[^\s()]+)").unwrap();
+    // Destination that is not a relative file path: URL scheme, anchor, absolute or protocol-relative path
+    static ref RE_NON_RELATIVE: Regex = Regex::new(r"^(?:[A-Za-z][A-Za-z0-9+.-]*:|#|/)").unwrap();
+    // Image without alt text: 1: destination
+    static ref RE_IMAGE_WITHOUT_ALT: Regex = Regex::new(r"!\[[ \t]*\]\([ \t]*(<[^<>]*>|[^\s()]+)").unwrap();
+    // Line made only of images and linked images, like a row of README badges
+    static ref RE_IMAGE_LINE: Regex = Regex::new(
+        r"^[ \t]*(?:(?:\[!\[[^\[\]]*\]\([^()]*\)\]\([^()]*\)|!\[[^\[\]]*\]\([^()]*\))[ \t]*)+$"
+    )
+    .unwrap();
+    // Image URL of a badge service
+    static ref RE_BADGE_URL: Regex =
+        Regex::new(r"\]\([^()]*(?:shields\.io|badgen\.net|badge\.fury\.io|badge\.svg|/badges?[/?.])").unwrap();
+    // Full, collapsed or shortcut reference link or image (not followed by a
+    // destination or definition colon): 1: "!" of images, 2: text, 3: optional label
+    static ref RE_REFERENCE_LINK: Regex = Regex::new(
+        r"(?<![\]\\])(!?)\[((?:[^\[\]]|\[[^\[\]]*\])*)\](?:\[([^\[\]]*)\])?(?![(:\[])"
+    )
+    .unwrap();
+}
+
+/// How bare URLs in prose are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BareUrls {
+    /// Left as written
+    Preserve,
+    /// Wrapped in `<...>` so strict CommonMark renderers link them
+    Autolink,
+}
+
+/// How links whose text is their own URL are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutolinkStyle {
+    /// Left as written
+    Preserve,
+    /// `<https://example.com>`
+    Angle,
+    /// `[example.com](https://example.com)`
+    Inline,
+}
+
+/// How inline and reference links are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    /// Left as written
+    Preserve,
+    /// `[text][label]` with the definitions collected at the bottom
+    Reference,
+    /// `[text](url)`, resolving reference links and deleting their definitions
+    Inline,
+}
+
+/// Quotes around link and image titles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleQuotes {
+    /// Left as written
+    Preserve,
+    /// `"title"`
+    Double,
+    /// `'title'`
+    Single,
+    /// `(title)`
+    Parens,
+}
+
+/// How link destinations containing literal spaces are fixed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DestinationSpaces {
+    /// Left as written
+    Preserve,
+    /// Spaces percent-encoded as `%20`
+    Encode,
+    /// Destination wrapped in `<...>`
+    Angle,
+}
+
+/// How links to files of the repository are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepositoryLinks {
+    /// Left as written
+    Preserve,
+    /// Absolute links under the repository URL become relative links
+    Relative,
+    /// Relative links become absolute links under the repository URL
+    Absolute,
+}
+
+/// Labels of generated reference definitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceLabels {
+    /// `[1]`, `[2]`...
+    Numbered,
+    /// Slug of the link text, like `[getting-started]`
+    Slug,
+}
+
+/// A link reference definition
+struct Definition {
+    label: String,
+    destination: String,
+    title: Option<String>,
+}
+
+impl Definition {
+    fn parse(line: &str) -> Option<Self> {
+        let caps = RE_LINK_DEFINITION.captures(line).ok()??;
+        Some(Self {
+            label: caps[1].to_string(),
+            destination: caps[2].to_string(),
+            title: caps.get(3).map(|m| m.as_str().to_string()),
+        })
+    }
+}
+
+impl std::fmt::Display for Definition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}]: {}", self.label, self.destination)?;
+        if let Some(title) = &self.title {
+            write!(f, " {}", title)?;
+        }
+        Ok(())
+    }
+}
+
+/// Labels are matched case-insensitively, with whitespace collapsed
+fn normalize_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Split the trailing punctuation that ends the sentence, not the URL, off a
+/// bare URL: `.,;:!?`, quotes, emphasis markers and unbalanced `)`
+fn split_url_suffix(url: &str) -> (&str, &str) {
+    let mut end = url.len();
+    while let Some(c) = url[..end].chars().next_back() {
+        let unbalanced = c == ')' && url[..end].matches('(').count() < url[..end].matches(')').count();
+        if !(unbalanced || ".,;:!?'\"*_~".contains(c)) {
+            break;
+        }
+        end -= c.len_utf8();
+    }
+    url.split_at(end)
+}
+
+/// Wrap bare `http(s)://` URLs in `<...>`, leaving URLs inside links,
+/// autolinks and HTML tags alone
+pub fn autolink_bare_urls(text: &str) -> String {
+    RE_BARE_URL
+        .replace_all(text, |caps: &Captures| match caps.get(1) {
+            Some(url) => {
+                let (url, suffix) = split_url_suffix(url.as_str());
+                format!("<{}>{}", url, suffix)
+            }
+            None => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+/// URL without its `http://` or `https://` scheme
+fn strip_scheme(url: &str) -> &str {
+    url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")).unwrap_or(url)
+}
+
+/// Write web URL autolinks as `<...>` or as `[...](...)` inline links whose
+/// text is the URL without its scheme. Inline links are only turned into
+/// autolinks when their text is the URL, with or without scheme.
+pub fn convert_autolinks(text: &str, style: AutolinkStyle) -> String {
+    match style {
+        AutolinkStyle::Preserve => text.to_string(),
+        AutolinkStyle::Angle => RE_URL_LINK
+            .replace_all(text, |caps: &Captures| {
+                let url = &caps[2];
+                if caps[1] == *url || caps[1] == *strip_scheme(url) {
+                    format!("<{}>", url)
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .to_string(),
+        AutolinkStyle::Inline => RE_ANGLE_AUTOLINK
+            .replace_all(text, |caps: &Captures| format!("[{}]({})", strip_scheme(&caps[1]), &caps[1]))
+            .to_string(),
+    }
+}
+
+/// Link reference definitions outside fenced code blocks
+fn collect_definitions<S: AsRef<str>>(lines: &[S]) -> Vec<Definition> {
+    let mut definitions = Vec::new();
+    let mut in_code = false;
+    for line in lines {
+        let line = line.as_ref();
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code && let Some(definition) = Definition::parse(line) {
+            definitions.push(definition);
+        }
+    }
+    definitions
+}
+
+/// Rewrite the prose lines (outside fenced code blocks and definitions) with
+/// `rewrite(index, line)`, keeping code spans untouched
+fn rewrite_prose<S: AsRef<str>>(lines: &[S], mut rewrite: impl FnMut(usize, &str) -> String) -> Vec<String> {
+    let mut ret = Vec::with_capacity(lines.len());
+    let mut in_code = false;
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        if in_code || Definition::parse(line).is_some() {
+            ret.push(line.to_string());
+            continue;
+        }
+
+        let mut line = line.to_string();
+        let codes = protect_spans(&mut line, &RE_CODE_SPAN_CONTENT, "MDCODE");
+        line = rewrite(index, &line);
+        restore_spans(&mut line, &codes, "MDCODE");
+        ret.push(line);
+    }
+    ret
+}
+
+/// Delete the definitions matching `remove` and the blank lines they leave behind
+fn remove_definitions(lines: Vec<String>, remove: impl Fn(&Definition) -> bool) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::with_capacity(lines.len());
+    let mut removed = false;
+    let mut in_code = false;
+    for line in lines {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        let definition = Definition::parse(&line).filter(|_| !in_code);
+        if definition.is_some_and(|d| remove(&d)) {
+            removed = true;
+            continue;
+        }
+        if line.is_empty() && removed && ret.last().is_none_or(|prev| prev.is_empty()) {
+            continue;
+        }
+        removed = removed && line.is_empty();
+        ret.push(line);
+    }
+    while ret.last().is_some_and(|line| line.is_empty()) {
+        ret.pop();
+    }
+    ret
+}
+
+/// Label of a reference link match: the explicit label, or the text of
+/// collapsed and shortcut references
+fn reference_label<'a>(caps: &'a Captures) -> &'a str {
+    match caps.get(3) {
+        Some(label) if !label.as_str().is_empty() => label.as_str(),
+        _ => caps.get(2).unwrap().as_str(),
+    }
+}
+
+/// Convert inline links to reference links, reusing existing definitions with
+/// the same destination and title and appending new ones at the bottom
+pub fn to_reference_links(text: &str, labels: ReferenceLabels) -> String {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let mut definitions = collect_definitions(&lines);
+    let mut used = definitions.iter().map(|d| normalize_label(&d.label)).collect::<HashSet<_>>();
+    let existing = definitions.len();
+
+    let mut ret = rewrite_prose(&lines, |_, line| {
+        RE_INLINE_LINK
+            .replace_all(line, |caps: &Captures| {
+                let destination = &caps[2];
+                let title = caps.get(3).map(|m| m.as_str());
+                let found = definitions
+                    .iter()
+                    .find(|d| d.destination == destination && d.title.as_deref() == title);
+                let label = match found {
+                    Some(definition) => definition.label.clone(),
+                    None => {
+                        let base = match labels {
+                            ReferenceLabels::Numbered => String::new(),
+                            ReferenceLabels::Slug => anchors::github_slug(&anchors::plain_text(&caps[1])),
+                        };
+                        let label = (1..)
+                            .map(|n| match (base.is_empty(), n) {
+                                (true, _) => n.to_string(),
+                                (false, 1) => base.clone(),
+                                (false, _) => format!("{}-{}", base, n - 1),
+                            })
+                            .find(|label| !used.contains(&normalize_label(label)))
+                            .unwrap();
+                        used.insert(normalize_label(&label));
+                        definitions.push(Definition {
+                            label: label.clone(),
+                            destination: destination.to_string(),
+                            title: title.map(|t| t.to_string()),
+                        });
+                        label
+                    }
+                };
+                format!("[{}][{}]", &caps[1], label)
+            })
+            .to_string()
+    });
+
+    if definitions.len() > existing {
+        while ret.last().is_some_and(|line| line.is_empty()) {
+            ret.pop();
+        }
+        if existing == 0 || ret.last().is_none_or(|line| Definition::parse(line).is_none()) {
+            ret.push(String::new());
+        }
+        ret.extend(definitions[existing..].iter().map(|d| d.to_string()));
+    }
+    ret.join("\n")
+}
+
+/// Resolve reference links and images against their definitions, inline them
+/// and delete the definitions no longer referenced. Undefined labels are left
+/// alone.
+pub fn to_inline_links(text: &str) -> String {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let definitions = collect_definitions(&lines);
+    if definitions.is_empty() {
+        return text.to_string();
+    }
+
+    let mut resolved = HashSet::new();
+    let ret = rewrite_prose(&lines, |_, line| {
+        RE_REFERENCE_LINK
+            .replace_all(line, |caps: &Captures| {
+                let label = normalize_label(reference_label(caps));
+                let Some(definition) = definitions.iter().find(|d| normalize_label(&d.label) == label) else {
+                    return caps[0].to_string();
+                };
+                resolved.insert(label);
+                let title = definition.title.as_ref().map(|t| format!(" {}", t)).unwrap_or_default();
+                format!("{}[{}]({}{})", &caps[1], &caps[2], definition.destination, title)
+            })
+            .to_string()
+    });
+
+    remove_definitions(ret, |d| resolved.contains(&normalize_label(&d.label))).join("\n")
+}
+
+/// Labels referenced by reference links and images of the prose
+fn referenced_labels<S: AsRef<str>>(lines: &[S]) -> HashSet<String> {
+    let mut labels = HashSet::new();
+    rewrite_prose(lines, |_, line| {
+        for caps in RE_REFERENCE_LINK.captures_iter(line) {
+            labels.insert(normalize_label(reference_label(&caps.unwrap())));
+        }
+        line.to_string()
+    });
+    labels
+}
+
+/// Report references to undefined labels and definitions no longer
+/// referenced; shortcut references like `[text]` are never reported since
+/// they may be plain bracketed text
+pub fn check_references(input: &str, config: &MdFormatConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let mode = config.links.check_references;
+    if mode == RuleMode::Ignore {
+        return;
+    }
+
+    let lines = input.lines().collect::<Vec<_>>();
+    let defined = collect_definitions(&lines)
+        .iter()
+        .map(|d| normalize_label(&d.label))
+        .collect::<HashSet<_>>();
+    rewrite_prose(&lines, |index, line| {
+        for caps in RE_REFERENCE_LINK.captures_iter(line) {
+            let caps = caps.unwrap();
+            let label = reference_label(&caps);
+            if caps.get(3).is_some() && !defined.contains(&normalize_label(label)) {
+                diagnostics.push(Diagnostic {
+                    line: index + 1,
+                    rule: "link-reference",
+                    message: format!("undefined link reference [{}]", label),
+                    fixed: false,
+                });
+            }
+        }
+        line.to_string()
+    });
+
+    let used = referenced_labels(&lines);
+    let mut in_code = false;
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code
+            && let Some(definition) = Definition::parse(line)
+            && !used.contains(&normalize_label(&definition.label))
+        {
+            diagnostics.push(Diagnostic {
+                line: index + 1,
+                rule: "link-reference",
+                message: format!("unused link reference definition [{}]", definition.label),
+                fixed: mode == RuleMode::Fix,
+            });
+        }
+    }
+}
+
+/// Delete the link reference definitions no reference link or image uses
+pub fn remove_unused_definitions(text: &str) -> String {
+    let lines = text.split('\n').map(|line| line.to_string()).collect::<Vec<_>>();
+    let used = referenced_labels(&lines);
+    if collect_definitions(&lines).iter().all(|d| used.contains(&normalize_label(&d.label))) {
+        return text.to_string();
+    }
+    remove_definitions(lines, |d| !used.contains(&normalize_label(&d.label))).join("\n")
+}
+
+/// Group the link reference definitions at the bottom, sorted by label
+/// (numbers first). Repeated labels keep their first definition, and labels
+/// defined with the same destination and title are merged into the first one.
+pub fn sort_definitions(text: &str) -> String {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let definitions = collect_definitions(&lines);
+    if definitions.is_empty() {
+        return text.to_string();
+    }
+
+    let mut kept: Vec<Definition> = Vec::new();
+    let mut aliases = HashMap::new();
+    for definition in definitions {
+        let label = normalize_label(&definition.label);
+        if aliases.contains_key(&label) || kept.iter().any(|d| normalize_label(&d.label) == label) {
+            continue;
+        }
+        match kept
+            .iter()
+            .find(|d| d.destination == definition.destination && d.title == definition.title)
+        {
+            Some(first) => {
+                aliases.insert(label, first.label.clone());
+            }
+            None => kept.push(definition),
+        }
+    }
+
+    let ret = rewrite_prose(&lines, |_, line| {
+        RE_REFERENCE_LINK
+            .replace_all(line, |caps: &Captures| match aliases.get(&normalize_label(reference_label(caps))) {
+                Some(label) => format!("{}[{}][{}]", &caps[1], &caps[2], label),
+                None => caps[0].to_string(),
+            })
+            .to_string()
+    });
+    let mut ret = remove_definitions(ret, |_| true);
+
+    kept.sort_by_cached_key(|d| {
+        let number = d.label.parse::<u64>().ok();
+        (number.is_none(), number, normalize_label(&d.label))
+    });
+    if !ret.is_empty() {
+        ret.push(String::new());
+    }
+    ret.extend(kept.iter().map(|d| d.to_string()));
+    ret.join("\n")
+}
+
+/// Requote a link title (with its delimiters), escaping the new delimiters in it
+fn requote_title(title: &str, quotes: TitleQuotes) -> String {
+    let inner = &title[1..title.len() - 1];
+    let (open, close) = match quotes {
+        TitleQuotes::Preserve => return title.to_string(),
+        TitleQuotes::Double => ('"', '"'),
+        TitleQuotes::Single => ('\'', '\''),
+        TitleQuotes::Parens => ('(', ')'),
+    };
+    let mut ret = String::from(open);
+    let mut escaped = false;
+    for c in inner.chars() {
+        if !escaped && (c == open || c == close) {
+            ret.push('\\');
+        }
+        escaped = !escaped && c == '\\';
+        ret.push(c);
+    }
+    ret.push(close);
+    ret
+}
+
+/// Quote link, image and definition titles the same way, with a single space
+/// between the destination and the title
+pub fn normalize_titles(text: &str, quotes: TitleQuotes) -> String {
+    if quotes == TitleQuotes::Preserve {
+        return text.to_string();
+    }
+
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let ret = rewrite_prose(&lines, |_, line| {
+        RE_LINK_TITLE
+            .replace_all(line, |caps: &Captures| format!("{} {})", &caps[1], requote_title(&caps[2], quotes)))
+            .to_string()
+    });
+    let mut in_code = false;
+    ret.into_iter()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+            }
+            match Definition::parse(&line).filter(|_| !in_code) {
+                Some(mut definition) if definition.title.is_some() => {
+                    definition.title = definition.title.map(|title| requote_title(&title, quotes));
+                    definition.to_string()
+                }
+                _ => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Percent-encode the spaces of inline link and image destinations, or wrap
+/// these destinations in `<...>`
+pub fn fix_destination_spaces(text: &str, style: DestinationSpaces) -> String {
+    if style == DestinationSpaces::Preserve {
+        return text.to_string();
+    }
+
+    let lines = text.split('\n').collect::<Vec<_>>();
+    rewrite_prose(&lines, |_, line| {
+        RE_SPACED_DESTINATION
+            .replace_all(line, |caps: &Captures| match style {
+                DestinationSpaces::Angle => format!("<{}>", &caps[1]),
+                _ => caps[1].replace([' ', '\t'], "%20"),
+            })
+            .to_string()
+    })
+    .join("\n")
+}
+
+/// File path of a relative link destination, without its `<...>`, query and
+/// fragment, or `None` for URLs and anchors
+fn relative_target(destination: &str) -> Option<String> {
+    let destination = destination.trim_start_matches('<').trim_end_matches('>');
+    if destination.is_empty() || RE_NON_RELATIVE.is_match(destination).unwrap() {
+        return None;
+    }
+    let path = destination.split(['#', '?']).next().unwrap();
+    (!path.is_empty()).then(|| anchors::percent_decode(path))
+}
+
+/// Report relative link, image and definition targets missing on disk,
+/// resolving them against `base` (the directory of the document)
+pub fn check_link_targets(input: &str, base: &Path, diagnostics: &mut Vec<Diagnostic>) {
+    let mut report = |index: usize, destination: &str| {
+        if let Some(target) = relative_target(destination)
+            && !base.join(&target).exists()
+        {
+            diagnostics.push(Diagnostic {
+                line: index + 1,
+                rule: "link-target",
+                message: format!("link target not found: {}", target),
+                fixed: false,
+            });
+        }
+    };
+
+    let lines = input.lines().collect::<Vec<_>>();
+    let mut in_code = false;
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code && let Some(definition) = Definition::parse(line) {
+            report(index, &definition.destination);
+        }
+    }
+    rewrite_prose(&lines, |index, line| {
+        for caps in RE_LINK_DESTINATION.captures_iter(line) {
+            report(index, &caps.unwrap()[1]);
+        }
+        line.to_string()
+    });
+}
+
+/// Rewrite the destinations of links, images and definitions with `rewrite`,
+/// which receives them without their `<...>`
+fn map_destinations(text: &str, rewrite: impl Fn(&str) -> String) -> String {
+    let rewrite = |destination: &str| match destination.strip_prefix('<').and_then(|d| d.strip_suffix('>')) {
+        Some(url) => format!("<{}>", rewrite(url)),
+        None => rewrite(destination),
+    };
+
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let ret = rewrite_prose(&lines, |_, line| {
+        RE_LINK_DESTINATION
+            .replace_all(line, |caps: &Captures| format!("]({}", rewrite(&caps[1])))
+            .to_string()
+    });
+    let mut in_code = false;
+    ret.into_iter()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+            }
+            match Definition::parse(&line).filter(|_| !in_code) {
+                Some(mut definition) => {
+                    definition.destination = rewrite(&definition.destination);
+                    definition.to_string()
+                }
+                None => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rewrite the destinations of links, images and definitions starting with
+/// one of the `rules` prefixes; the longest matching prefix wins
+pub fn rewrite_destinations(text: &str, rules: &BTreeMap<String, String>) -> String {
+    if rules.is_empty() {
+        return text.to_string();
+    }
+    map_destinations(text, |url| {
+        let rule = rules
+            .iter()
+            .filter(|(prefix, _)| !prefix.is_empty() && url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        match rule {
+            Some((prefix, replacement)) => format!("{}{}", replacement, &url[prefix.len()..]),
+            None => url.to_string(),
+        }
+    })
+}
+
+/// Directory of the document relative to the root of its git repository (the
+/// closest parent with a `.git` entry), or an empty path outside a repository
+pub fn document_dir(input: Option<&Path>, working_dir: &Path) -> PathBuf {
+    let dir = match input {
+        Some(path) => working_dir.join(path).parent().map(Path::to_path_buf).unwrap_or_default(),
+        None => working_dir.to_path_buf(),
+    };
+    let dir = dir.canonicalize().unwrap_or(dir);
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .and_then(|root| dir.strip_prefix(root).ok())
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Split a destination into its path and its `?query`/`#fragment` suffix
+fn split_suffix(destination: &str) -> (&str, &str) {
+    destination.split_at(destination.find(['#', '?']).unwrap_or(destination.len()))
+}
+
+/// Convert links to files of the repository at `repository_url` between
+/// absolute and relative form, `dir` being the directory of the document
+/// in the repository
+pub fn convert_repository_links(text: &str, repository_url: &str, dir: &Path, style: RepositoryLinks) -> String {
+    let base = format!("{}/", repository_url.trim_end_matches('/'));
+    let dir = dir
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    match style {
+        RepositoryLinks::Preserve => text.to_string(),
+        RepositoryLinks::Relative => map_destinations(text, |url| {
+            let Some((path, suffix)) = url.strip_prefix(&base).map(split_suffix) else {
+                return url.to_string();
+            };
+            let parts = path.split('/').collect::<Vec<_>>();
+            let common = dir
+                .iter()
+                .zip(&parts[..parts.len() - 1])
+                .take_while(|(a, b)| a == b)
+                .count();
+            let mut relative = vec![".."; dir.len() - common];
+            relative.extend(&parts[common..]);
+            match relative.join("/") {
+                relative if relative.is_empty() => url.to_string(),
+                relative => format!("{}{}", relative, suffix),
+            }
+        }),
+        RepositoryLinks::Absolute => map_destinations(text, |url| {
+            let (path, suffix) = split_suffix(url);
+            if path.is_empty() || (RE_NON_RELATIVE.is_match(path).unwrap() && !path.starts_with('/')) {
+                return url.to_string();
+            }
+            let mut resolved = if path.starts_with('/') { Vec::new() } else { dir.iter().map(String::as_str).collect() };
+            for part in path.split('/').filter(|part| !part.is_empty() && *part != ".") {
+                if part != ".." {
+                    resolved.push(part);
+                } else if resolved.pop().is_none() {
+                    // Outside the repository
+                    return url.to_string();
+                }
+            }
+            let trailing = if path.ends_with('/') { "/" } else { "" };
+            format!("{}{}{}{}", base, resolved.join("/"), trailing, suffix)
+        }),
+    }
+}
+
+/// File name stem of an image destination, like `logo` for `img/logo.png?v=2`
+fn file_stem(destination: &str) -> String {
+    let destination = destination.trim_start_matches('<').trim_end_matches('>');
+    let (path, _) = split_suffix(destination);
+    let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    anchors::percent_decode(stem)
+}
+
+/// Report images with empty alt text, filling it with the file name stem of
+/// the image in fix mode
+pub fn check_image_alt(
+    lines: &mut [String],
+    config: &MdFormatConfig,
+    line_offset: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mode = config.links.image_alt;
+    if mode == RuleMode::Ignore {
+        return;
+    }
+
+    let start = front_matter_end(lines).map_or(0, |end| end + 1);
+    let rewritten = rewrite_prose(&lines[start..], |index, line| {
+        RE_IMAGE_WITHOUT_ALT
+            .replace_all(line, |caps: &Captures| {
+                let stem = file_stem(&caps[1]);
+                diagnostics.push(Diagnostic {
+                    line: line_offset + start + index + 1,
+                    rule: "image-alt",
+                    message: format!("image without alt text: {}", &caps[1]),
+                    fixed: mode == RuleMode::Fix && !stem.is_empty(),
+                });
+                match mode {
+                    RuleMode::Fix if !stem.is_empty() => format!("![{}]({}", stem, &caps[1]),
+                    _ => caps[0].to_string(),
+                }
+            })
+            .to_string()
+    });
+    for (line, new_line) in lines[start..].iter_mut().zip(rewritten) {
+        *line = new_line;
+    }
+}
+
+/// Whether the line is a row of badges: only images and linked images, at
+/// least one of them served by a badge service like shields.io
+pub fn is_badge_line(line: &str) -> bool {
+    RE_IMAGE_LINE.is_match(line).unwrap() && RE_BADGE_URL.is_match(line).unwrap()
+}
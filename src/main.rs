@@ -1,24 +1,108 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use fancy_regex::{Captures, Regex};
 use lazy_static::lazy_static;
 use log::debug;
-use markdown_table_formatter::format_tables;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{self, Read, Write},
     path::{Path, PathBuf},
 };
+use unicode_normalization::UnicodeNormalization;
+use unicode_script::{Script, UnicodeScript};
+
+mod anchors;
+mod comments;
+mod dialects;
+mod divs;
+mod emoji;
+mod encoding;
+mod front_matter;
+mod headings;
+mod inline;
+mod links;
+mod notebook;
+mod outline;
+mod tables;
+mod toc;
+mod wrap;
 
 /// Main configuration structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct MdFormatConfig {
+    /// Language presetting the CJK spacing and punctuation options (default: none)
+    pub locale: Locale,
     pub formatting: FormattingOptions,
     pub lists: ListOptions,
     pub headings: HeadingOptions,
+    pub blank_lines: BlankLineOptions,
     pub spacing: SpacingOptions,
+    pub punctuation: PunctuationOptions,
+    pub wrap: WrapOptions,
+    pub protect: ProtectOptions,
+    pub toc: TocOptions,
+    pub anchors: AnchorOptions,
+    pub tables: TableOptions,
+    pub links: LinkOptions,
+    pub emoji: EmojiOptions,
+    pub front_matter: FrontMatterOptions,
+}
+
+/// Language whose typesetting conventions preset the CJK options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Locale {
+    /// No preset
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// Simplified Chinese
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+    /// Traditional Chinese
+    #[serde(rename = "zh-TW")]
+    ZhTw,
+    /// Japanese
+    #[serde(rename = "ja")]
+    Ja,
+    /// Korean
+    #[serde(rename = "ko")]
+    Ko,
+}
+
+impl Locale {
+    /// Option values preset by the locale, for the options not set explicitly
+    fn profile(self) -> &'static str {
+        match self {
+            Locale::None => "",
+            Locale::ZhCn => {
+                r#"
+                spacing = { cjk_ascii = true, cjk_scripts = ["han"] }
+                punctuation = { cjk_width = "chinese", cjk_quotes = "curly" }
+                "#
+            }
+            Locale::ZhTw => {
+                r#"
+                spacing = { cjk_ascii = true, cjk_scripts = ["han"] }
+                punctuation = { cjk_width = "chinese", cjk_quotes = "corner" }
+                "#
+            }
+            Locale::Ja => {
+                r#"
+                spacing = { cjk_ascii = false, cjk_scripts = ["han", "hiragana", "katakana"] }
+                punctuation = { cjk_width = "japanese", cjk_quotes = "corner" }
+                "#
+            }
+            Locale::Ko => {
+                r#"
+                spacing = { cjk_ascii = true, cjk_scripts = ["hangul"] }
+                punctuation = { cjk_width = "preserve", cjk_quotes = "curly" }
+                "#
+            }
+        }
+    }
 }
 
 /// Formatting master switches
@@ -33,6 +117,64 @@ pub struct FormattingOptions {
     pub blank_lines: bool,
     /// Whether to merge consecutive blank lines (default: true)
     pub merge_blank_lines: bool,
+    /// Canonical form of thematic breaks (default: "---")
+    pub thematic_break: String,
+    /// Strong emphasis marker: "preserve", "**" or "__" (default: preserve)
+    pub strong_marker: String,
+    /// Whether to recognize Pandoc extensions like `^superscript^`, `~subscript~` and `:::` fenced divs (default: false)
+    pub pandoc: bool,
+    /// Whether to normalize prose to Unicode NFC (default: false)
+    pub unicode_nfc: bool,
+    /// Whether to convert full-width letters and digits to ASCII (default: false)
+    pub halfwidth_alphanumerics: bool,
+    /// Zero-width and bidi control characters in prose (default: ignore)
+    pub invisible_chars: RuleMode,
+    /// Ideographic spaces (U+3000): "preserve" or "space" (default: preserve)
+    pub ideographic_spaces: IdeographicSpaces,
+    /// Line endings of the output: "auto", "lf" or "crlf" (default: auto)
+    pub end_of_line: LineEnding,
+    /// Newlines at the end of the output: "single", "none" or "preserve" (default: single)
+    pub final_newline: FinalNewline,
+    /// Whether to keep the blank lines and whitespace around the document as is,
+    /// overriding `final_newline` (default: false)
+    pub preserve_outer_whitespace: bool,
+    /// Dialect of the input, from `--dialect` or the file extension
+    #[serde(skip)]
+    pub dialect: dialects::Dialect,
+}
+
+/// Newlines at the end of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalNewline {
+    /// Exactly one newline
+    Single,
+    /// No newline after the last line
+    None,
+    /// The newlines ending the input, including trailing blank lines
+    Preserve,
+}
+
+/// Line endings of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// Those of the input: CRLF when most of its lines end with it
+    Auto,
+    /// Unix line endings (\n)
+    Lf,
+    /// Windows line endings (\r\n)
+    Crlf,
+}
+
+/// How ideographic spaces (U+3000) are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdeographicSpaces {
+    /// Kept everywhere, even at the start of the document and the end of lines
+    Preserve,
+    /// Converted to regular spaces in prose
+    Space,
 }
 
 /// List formatting options
@@ -45,6 +187,21 @@ pub struct ListOptions {
     pub unordered_marker: String,
     /// Whether to renumber ordered lists (default: true)
     pub renumber_ordered: bool,
+    /// Columns between tab stops when measuring list indentation, lists mixing
+    /// tabs and spaces counting a tab as their smallest space indentation (default: 4)
+    pub tab_width: usize,
+    /// List indentation on output: "spaces" or "tabs" (default: spaces)
+    pub indent_style: IndentStyle,
+}
+
+/// Characters used for list indentation on output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    /// `lists.indent` spaces per level, tabs in list content expanded
+    Spaces,
+    /// One tab per level, tabs in list content kept
+    Tabs,
 }
 
 /// Heading formatting options
@@ -53,8 +210,86 @@ pub struct ListOptions {
 pub struct HeadingOptions {
     /// Heading numbering start level 0-6 (default: 0, 0 means no numbering)
     pub numbering_start_level: u8,
-    /// Whether to enforce blank line after headings (default: true)
+    /// Whether to enforce blank line after headings, otherwise blocks right below
+    /// them stay attached (default: true)
     pub blank_line_after: bool,
+    /// Number of blank lines after headings when `blank_line_after` is enabled (default: 1)
+    pub blank_lines_after: usize,
+    /// Number of blank lines before headings per level H1, H2..., the last value
+    /// applies to deeper levels (default: [], keep the blank lines of the input)
+    pub blank_lines_before: Vec<usize>,
+    /// How to handle skipped heading levels, e.g. H1 -> H3 (default: ignore)
+    pub skipped_levels: RuleMode,
+    /// Whether to remove manual numbering prefixes like "1.2.3" or "第三章" (default: false)
+    pub strip_numbering: bool,
+    /// Whether to report headings with identical text at the same level (default: false)
+    pub check_duplicates: bool,
+    /// How to handle documents not starting with exactly one H1 (default: ignore)
+    pub single_h1: RuleMode,
+    /// Whether a front matter `title` provides the H1: a missing H1 is fixed by
+    /// inserting it (default: false)
+    pub front_matter_title: bool,
+    /// Capitalization enforced on headings (default: off)
+    pub capitalization: HeadingCase,
+    /// Words kept exactly as written by the capitalization rule, e.g. product names
+    pub capitalization_exceptions: Vec<String>,
+    /// How to handle headings ending with punctuation (default: ignore)
+    pub trailing_punctuation: RuleMode,
+    /// Characters removed from the end of headings (default: ".:。：")
+    pub trailing_punctuation_chars: String,
+    /// How to handle paragraphs made of a single bold line, e.g. `**Title**` (default: ignore)
+    pub bold_headings: RuleMode,
+    /// Level of the headings bold lines are converted to, 1-6 (default: 2)
+    pub bold_heading_level: u8,
+}
+
+/// Heading capitalization style
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadingCase {
+    /// Capitalization is left as-is
+    Off,
+    /// Only the first word is capitalized
+    Sentence,
+    /// All words except minor words (a, the, of...) are capitalized
+    Title,
+}
+
+/// How an optional lint rule is applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleMode {
+    /// Rule is disabled
+    Ignore,
+    /// Violations are reported but not changed
+    Warn,
+    /// Violations are fixed
+    Fix,
+}
+
+/// Blank lines separating each kind of block from its neighbors, when
+/// `formatting.blank_lines` is enabled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlankLineOptions {
+    /// Blank line before fenced code blocks (default: true)
+    pub before_code: bool,
+    /// Blank line after fenced code blocks (default: true)
+    pub after_code: bool,
+    /// Blank line before tables (default: true)
+    pub before_tables: bool,
+    /// Blank line after tables (default: true)
+    pub after_tables: bool,
+    /// Blank line before blockquotes (default: true)
+    pub before_blockquotes: bool,
+    /// Blank line after blockquotes (default: true)
+    pub after_blockquotes: bool,
+    /// Blank line before lists (default: true)
+    pub before_lists: bool,
+    /// Blank line between a list and a following heading (default: true)
+    pub after_lists: bool,
+    /// Blank lines around thematic breaks (default: true)
+    pub around_thematic_breaks: bool,
 }
 
 /// Spacing processing options
@@ -63,22 +298,305 @@ pub struct HeadingOptions {
 pub struct SpacingOptions {
     /// Whether to add spaces between CJK and ASCII/digits (default: true)
     pub cjk_ascii: bool,
+    /// Whether to remove single spaces between CJK and ASCII/digits instead (default: false)
+    pub strip_cjk_ascii: bool,
     /// Whether to add spaces around code spans (default: true)
     pub around_code_spans: bool,
+    /// Scripts spaced from ASCII letters and digits (default: all)
+    pub cjk_scripts: Vec<CjkScript>,
+    /// Character inserted between CJK and ASCII: "space", "thin" or "nbsp" (default: space)
+    pub cjk_separator: CjkSeparator,
+    /// Unit symbols joined to the preceding number and spaced like it (default: %, ‰, ℃...)
+    pub units: Vec<String>,
+    /// Terms never spaced from CJK text, such as model numbers (default: none)
+    pub exceptions: Vec<String>,
+    /// Currency symbols joined to the following number (default: ¥, ￥, $, €, £)
+    pub currencies: Vec<String>,
+    /// Amounts (¥100, 50%, 25°C, 3:00PM) next to CJK text: "space" or "attach" (default: space)
+    pub amounts: AmountSpacing,
 }
 
-// Default value implementations
-impl Default for MdFormatConfig {
-    fn default() -> Self {
-        Self {
-            formatting: FormattingOptions::default(),
-            lists: ListOptions::default(),
-            headings: HeadingOptions::default(),
-            spacing: SpacingOptions::default(),
+/// How amounts with a currency, unit or time suffix are separated from CJK text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AmountSpacing {
+    /// Spaced like ASCII words (价格 ¥100 元)
+    Space,
+    /// Never spaced (价格¥100元)
+    Attach,
+}
+
+/// Character inserted between CJK characters and ASCII letters or digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CjkSeparator {
+    /// Regular space (U+0020)
+    Space,
+    /// Thin space (U+2009)
+    Thin,
+    /// No-break space (U+00A0)
+    Nbsp,
+}
+
+impl CjkSeparator {
+    /// The inserted character
+    fn as_char(self) -> char {
+        match self {
+            CjkSeparator::Space => ' ',
+            CjkSeparator::Thin => '\u{2009}',
+            CjkSeparator::Nbsp => '\u{00A0}',
+        }
+    }
+}
+
+/// Script of CJK characters spaced from ASCII letters and digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CjkScript {
+    /// Chinese characters and Japanese kanji
+    Han,
+    /// Japanese hiragana
+    Hiragana,
+    /// Japanese katakana, including half-width forms
+    Katakana,
+    /// Korean hangul
+    Hangul,
+}
+
+impl CjkScript {
+    /// Whether the character belongs to the script
+    fn contains(self, c: char) -> bool {
+        let script = c.script();
+        match self {
+            CjkScript::Han => script == Script::Han,
+            CjkScript::Hiragana => script == Script::Hiragana,
+            CjkScript::Katakana => script == Script::Katakana,
+            CjkScript::Hangul => script == Script::Hangul,
         }
     }
 }
 
+/// Punctuation options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PunctuationOptions {
+    /// Typographic punctuation in prose: "preserve", "smart" or "straight" (default: preserve)
+    pub mode: PunctuationMode,
+    /// Quotes around Chinese/Japanese text in smart mode: "curly" or "corner" (default: curly)
+    pub cjk_quotes: QuoteStyle,
+    /// En/em dashes: "preserve", "ascii" (--/---) or "unicode" (–/—) (default: preserve)
+    pub dashes: PunctuationStyle,
+    /// Ellipses, including spaced ". . .": "preserve", "ascii" (...) or "unicode" (…) (default: preserve)
+    pub ellipsis: PunctuationStyle,
+    /// ASCII punctuation of Chinese/Japanese sentences: "preserve", "chinese" or "japanese" (default: preserve)
+    pub cjk_width: CjkPunctuation,
+    /// Longest run of one repeated full-width punctuation mark, 0 for no limit (default: 0)
+    pub cjk_max_repeat: usize,
+}
+
+/// How punctuation in prose is rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PunctuationMode {
+    /// Punctuation is left as written
+    Preserve,
+    /// Straight quotes become curly quotes, `...` an ellipsis and `--`/`---` en/em dashes
+    Smart,
+    /// Curly quotes, ellipses and en/em dashes become their ASCII forms
+    Straight,
+}
+
+/// Which form a single punctuation mark is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PunctuationStyle {
+    /// Left as written
+    Preserve,
+    /// ASCII sequence such as `--` or `...`
+    Ascii,
+    /// Unicode character such as `–` or `…`
+    Unicode,
+}
+
+/// Full-width punctuation written in Chinese/Japanese sentences
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CjkPunctuation {
+    /// Punctuation is left as written
+    Preserve,
+    /// `,` `.` `?` `!` `:` `;` `(...)` become `，` `。` `？` `！` `：` `；` `（...）`
+    Chinese,
+    /// As in Chinese, except that `,` becomes the ideographic comma `、`
+    Japanese,
+}
+
+/// Quotation marks used around Chinese/Japanese text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    /// “...” and ‘...’
+    Curly,
+    /// 「...」 and 『...』
+    Corner,
+}
+
+/// Paragraph wrapping options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WrapOptions {
+    /// How prose paragraphs are wrapped: "preserve", "no", "width" or "sentence" (default: preserve)
+    pub mode: WrapMode,
+    /// Maximum line width in columns, CJK characters counting as two (default: 80)
+    pub width: usize,
+}
+
+/// How prose paragraphs are wrapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapMode {
+    /// Line breaks are left as written
+    Preserve,
+    /// Each paragraph is joined into a single line
+    #[serde(alias = "unwrap")]
+    No,
+    /// Paragraphs are reflowed to the maximum width
+    Width,
+    /// Each sentence is put on its own line
+    Sentence,
+}
+
+/// Protected line options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectOptions {
+    /// Regular expressions matching lines that are passed through verbatim
+    /// (default: pymdownx snippet and `{% ... %}` include markers)
+    pub patterns: Vec<String>,
+    /// Whether rows of badge images (shields.io...) are passed through verbatim (default: true)
+    pub badge_lines: bool,
+    /// Whether paragraphs that look like ASCII art or manually aligned columns
+    /// are passed through verbatim (default: true)
+    pub aligned_text: bool,
+    /// Length in bytes above which a line is passed through verbatim, 0 for no
+    /// limit (default: 10000)
+    pub max_line_length: usize,
+}
+
+/// Table of contents options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TocOptions {
+    /// Lowest heading level listed in the TOC 1-6 (default: 1)
+    pub min_level: u8,
+    /// Highest heading level listed in the TOC 1-6 (default: 6)
+    pub max_level: u8,
+    /// Marker excluding a heading from the TOC when placed on the heading line
+    /// (default: "<!-- toc-ignore -->")
+    pub ignore_marker: String,
+    /// Whether to generate an ordered list instead of an unordered one (default: false)
+    pub ordered: bool,
+}
+
+/// Heading anchor options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnchorOptions {
+    /// Slug algorithm used for heading anchors (default: github)
+    pub style: AnchorStyle,
+    /// How to handle `#anchor` links that match no heading (default: ignore)
+    pub check_links: RuleMode,
+    /// Whether to add a `{#id}` attribute to headings without one (default: false)
+    pub generate_ids: bool,
+}
+
+/// Heading anchor slug algorithm of the target platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnchorStyle {
+    Github,
+    Gitlab,
+    Azure,
+    Pandoc,
+}
+
+/// Table alignment options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableOptions {
+    /// How cell widths are measured: display columns or characters (default: display)
+    pub cell_width: tables::CellWidth,
+    /// Maximum width of a column, wider cells are left unpadded (default: 0, no limit)
+    pub max_column_width: usize,
+    /// Whether to wrap data cells wider than `max_column_width` with `<br>` (default: false)
+    pub wrap_cells: bool,
+    /// How to handle rows with fewer or more cells than the header (default: ignore)
+    pub ragged_rows: RuleMode,
+    /// Line break tag in cells: "preserve", "<br>", "<br/>" or "<br />" (default: preserve)
+    pub line_break: String,
+    /// Whether to convert simple HTML `<table>` blocks into pipe tables (default: false)
+    pub convert_html: bool,
+    /// Delimiter row dashes: fill the column width or minimal `---` (default: full)
+    pub delimiter_style: tables::DelimiterStyle,
+}
+
+/// Link style options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkOptions {
+    /// Bare `https://...` URLs in prose: "preserve" or "autolink" (default: preserve)
+    pub bare_urls: links::BareUrls,
+    /// Links to their own URL: "preserve", "angle" (`<url>`) or "inline" (`[text](url)`) (default: preserve)
+    pub autolinks: links::AutolinkStyle,
+    /// Link style: "preserve", "reference" or "inline" (default: preserve)
+    pub style: links::LinkStyle,
+    /// Labels of generated reference definitions: "numbered" or "slug" (default: numbered)
+    pub reference_labels: links::ReferenceLabels,
+    /// Whether to group link reference definitions at the bottom, sorted and deduplicated (default: false)
+    pub sort_definitions: bool,
+    /// Undefined references and unused definitions (default: ignore)
+    pub check_references: RuleMode,
+    /// Quotes around link titles: "preserve", "double", "single" or "parens" (default: preserve)
+    pub title_quotes: links::TitleQuotes,
+    /// Link destinations with literal spaces: "preserve", "encode" (`%20`) or "angle" (`<...>`) (default: preserve)
+    pub destination_spaces: links::DestinationSpaces,
+    /// Whether `--check` reports relative link and image targets missing on disk (default: false)
+    pub check_targets: bool,
+    /// Destination prefixes to rewrite, mapped to their replacement (default: none)
+    pub rewrite: BTreeMap<String, String>,
+    /// Base URL of the repository files, like `https://github.com/org/repo/blob/main` (default: "")
+    pub repository_url: String,
+    /// Links to repository files: "preserve", "relative" or "absolute" (default: preserve)
+    pub repository_links: links::RepositoryLinks,
+    /// Images without alt text (default: ignore)
+    pub image_alt: RuleMode,
+    /// Directory of the document in its repository, found from the input file
+    #[serde(skip)]
+    pub document_dir: PathBuf,
+}
+
+/// Emoji options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmojiOptions {
+    /// Whether to replace shortcode aliases with the preferred name, like `:thumbsup:` with `:+1:` (default: false)
+    pub normalize_aliases: bool,
+    /// Emoji form: "preserve", "shortcode" (`:tada:`) or "unicode" (🎉) (default: preserve)
+    pub style: emoji::EmojiStyle,
+}
+
+/// YAML front matter options
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontMatterOptions {
+    /// Whether to format the YAML front matter instead of keeping it as written (default: false)
+    pub format: bool,
+    /// Top-level keys put first, in this order (default: [])
+    pub key_order: Vec<String>,
+    /// Whether to sort the other top-level keys alphabetically (default: false)
+    pub sort_keys: bool,
+}
+
+// Default value implementations
 impl Default for FormattingOptions {
     fn default() -> Self {
         Self {
@@ -86,6 +604,17 @@ impl Default for FormattingOptions {
             format_lists: true,
             blank_lines: true,
             merge_blank_lines: true,
+            thematic_break: "---".to_string(),
+            strong_marker: "preserve".to_string(),
+            pandoc: false,
+            unicode_nfc: false,
+            halfwidth_alphanumerics: false,
+            invisible_chars: RuleMode::Ignore,
+            ideographic_spaces: IdeographicSpaces::Preserve,
+            end_of_line: LineEnding::Auto,
+            final_newline: FinalNewline::Single,
+            preserve_outer_whitespace: false,
+            dialect: dialects::Dialect::Markdown,
         }
     }
 }
@@ -96,6 +625,8 @@ impl Default for ListOptions {
             indent: 2,
             unordered_marker: "-".to_string(),
             renumber_ordered: true,
+            tab_width: 4,
+            indent_style: IndentStyle::Spaces,
         }
     }
 }
@@ -105,6 +636,35 @@ impl Default for HeadingOptions {
         Self {
             numbering_start_level: 0,
             blank_line_after: true,
+            blank_lines_after: 1,
+            blank_lines_before: Vec::new(),
+            skipped_levels: RuleMode::Ignore,
+            strip_numbering: false,
+            check_duplicates: false,
+            single_h1: RuleMode::Ignore,
+            front_matter_title: false,
+            capitalization: HeadingCase::Off,
+            capitalization_exceptions: Vec::new(),
+            trailing_punctuation: RuleMode::Ignore,
+            trailing_punctuation_chars: ".:。：".to_string(),
+            bold_headings: RuleMode::Ignore,
+            bold_heading_level: 2,
+        }
+    }
+}
+
+impl Default for BlankLineOptions {
+    fn default() -> Self {
+        Self {
+            before_code: true,
+            after_code: true,
+            before_tables: true,
+            after_tables: true,
+            before_blockquotes: true,
+            after_blockquotes: true,
+            before_lists: true,
+            after_lists: true,
+            around_thematic_breaks: true,
         }
     }
 }
@@ -113,7 +673,115 @@ impl Default for SpacingOptions {
     fn default() -> Self {
         Self {
             cjk_ascii: true,
+            strip_cjk_ascii: false,
+            cjk_scripts: vec![CjkScript::Han, CjkScript::Hiragana, CjkScript::Katakana, CjkScript::Hangul],
             around_code_spans: true,
+            cjk_separator: CjkSeparator::Space,
+            units: ["%", "％", "‰", "℃", "℉", "°C", "°F", "°"].map(String::from).to_vec(),
+            exceptions: Vec::new(),
+            currencies: ["¥", "￥", "$", "€", "£"].map(String::from).to_vec(),
+            amounts: AmountSpacing::Space,
+        }
+    }
+}
+
+impl Default for WrapOptions {
+    fn default() -> Self {
+        Self {
+            mode: WrapMode::Preserve,
+            width: 80,
+        }
+    }
+}
+
+impl Default for PunctuationOptions {
+    fn default() -> Self {
+        Self {
+            mode: PunctuationMode::Preserve,
+            cjk_quotes: QuoteStyle::Curly,
+            dashes: PunctuationStyle::Preserve,
+            ellipsis: PunctuationStyle::Preserve,
+            cjk_width: CjkPunctuation::Preserve,
+            cjk_max_repeat: 0,
+        }
+    }
+}
+
+impl Default for ProtectOptions {
+    fn default() -> Self {
+        Self {
+            patterns: vec![
+                r"^\s*-{2,}8<-{2,}".to_string(),
+                r"^\s*\{%.*%\}\s*$".to_string(),
+            ],
+            badge_lines: true,
+            aligned_text: true,
+            max_line_length: 10000,
+        }
+    }
+}
+
+impl Default for TocOptions {
+    fn default() -> Self {
+        Self {
+            min_level: 1,
+            max_level: 6,
+            ignore_marker: "<!-- toc-ignore -->".to_string(),
+            ordered: false,
+        }
+    }
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            cell_width: tables::CellWidth::Display,
+            max_column_width: 0,
+            wrap_cells: false,
+            ragged_rows: RuleMode::Ignore,
+            line_break: "preserve".to_string(),
+            convert_html: false,
+            delimiter_style: tables::DelimiterStyle::Full,
+        }
+    }
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        Self {
+            bare_urls: links::BareUrls::Preserve,
+            autolinks: links::AutolinkStyle::Preserve,
+            style: links::LinkStyle::Preserve,
+            reference_labels: links::ReferenceLabels::Numbered,
+            sort_definitions: false,
+            check_references: RuleMode::Ignore,
+            title_quotes: links::TitleQuotes::Preserve,
+            destination_spaces: links::DestinationSpaces::Preserve,
+            check_targets: false,
+            rewrite: BTreeMap::new(),
+            repository_url: String::new(),
+            repository_links: links::RepositoryLinks::Preserve,
+            image_alt: RuleMode::Ignore,
+            document_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Default for EmojiOptions {
+    fn default() -> Self {
+        Self {
+            normalize_aliases: false,
+            style: emoji::EmojiStyle::Preserve,
+        }
+    }
+}
+
+impl Default for AnchorOptions {
+    fn default() -> Self {
+        Self {
+            style: AnchorStyle::Github,
+            check_links: RuleMode::Ignore,
+            generate_ids: false,
         }
     }
 }
@@ -122,6 +790,11 @@ impl Default for SpacingOptions {
 const DEFAULT_CONFIG_TEMPLATE: &str = r#"# mdformat configuration file
 # Generated with 'mdformat --init-config'
 
+# Language presetting the [spacing] and [punctuation] options for CJK text, which
+# options set below still override: "none", "zh-CN" (spaces, ，。 and “”), "zh-TW"
+# (spaces, ，。 and 「」), "ja" (no spaces, 、。 and 「」) or "ko" (spaces, ASCII punctuation)
+locale = "none"
+
 [formatting]
 # Whether to format table alignment
 format_tables = true
@@ -131,6 +804,32 @@ format_lists = true
 blank_lines = true
 # Whether to merge consecutive blank lines
 merge_blank_lines = true
+# Canonical form of thematic breaks (horizontal rules): "---", "***", "___", ...
+thematic_break = "---"
+# Strong emphasis marker: "preserve" (as written), "**" or "__"
+strong_marker = "preserve"
+# Whether to recognize Pandoc extensions (^superscript^, ~subscript~, ::: fenced divs)
+pandoc = false
+# Whether to normalize prose to Unicode NFC (composes decomposed accents and Hangul; code is untouched)
+unicode_nfc = false
+# Whether to convert full-width letters and digits (ＡＢＣ１２３) in prose to ASCII (ABC123)
+halfwidth_alphanumerics = false
+# Zero-width spaces/non-joiners, stray byte order marks and bidi control characters in prose:
+# "ignore", "warn" (report their positions) or "fix" (remove them)
+invisible_chars = "ignore"
+# Ideographic spaces (U+3000), e.g. indenting Japanese paragraphs: "preserve" (kept
+# even at the start of the document and the end of lines) or "space" (converted to
+# regular spaces in prose)
+ideographic_spaces = "preserve"
+# Line endings of the output: "auto" (those of the input, CRLF when most of its
+# lines end with it), "lf" or "crlf"
+end_of_line = "auto"
+# Newlines at the end of the output: "single" (exactly one), "none" or "preserve"
+# (those ending the input, including trailing blank lines)
+final_newline = "single"
+# Whether to keep the blank lines and whitespace before and after the document as
+# is (e.g. files designed for concatenation), overriding final_newline
+preserve_outer_whitespace = false
 
 [lists]
 # Number of spaces for list indentation (per level)
@@ -139,18 +838,227 @@ indent = 2
 unordered_marker = "-"
 # Whether to renumber ordered lists
 renumber_ordered = true
+# Columns between tab stops when measuring tab-indented lists (lists mixing tabs
+# and spaces count a tab as their smallest space indentation, e.g. 2)
+tab_width = 4
+# List indentation on output: "spaces" (lists.indent spaces per level, tabs in list
+# content expanded) or "tabs" (one tab per level)
+indent_style = "spaces"
 
 [headings]
 # Heading numbering start level (0=no numbering, 1=from H1, 2=from H2...)
 numbering_start_level = 0
-# Whether to enforce blank line after headings
+# Whether to enforce blank line after headings (when disabled, tables, lists, code
+# blocks and badge rows right below a heading stay attached to it)
 blank_line_after = true
+# Number of blank lines after headings (when blank_line_after is enabled)
+blank_lines_after = 1
+# Number of blank lines before headings per level (H1, H2, ...), the last value
+# applies to deeper levels, e.g. [2, 2, 1]; empty keeps the blank lines of the input
+blank_lines_before = []
+# Skipped heading levels (e.g. H1 -> H3): "ignore", "warn" (report) or "fix" (close the gaps)
+skipped_levels = "ignore"
+# Whether to remove manual numbering prefixes from headings ("1.2.3", "第三章", "一、")
+strip_numbering = false
+# Whether to report headings with identical text at the same level (colliding anchors)
+check_duplicates = false
+# Documents must start with exactly one H1: "ignore", "warn" (report) or "fix"
+# (demote extra H1 sections, insert a missing H1 from the front matter title)
+single_h1 = "ignore"
+# Whether the front matter title provides the H1 when the document has none
+front_matter_title = false
+# Heading capitalization: "off", "sentence" or "title"
+capitalization = "off"
+# Words kept exactly as written by the capitalization rule (e.g. product names)
+capitalization_exceptions = []
+# Headings ending with punctuation: "ignore", "warn" (report) or "fix" (remove it)
+trailing_punctuation = "ignore"
+# Characters treated as trailing punctuation
+trailing_punctuation_chars = ".:。："
+# Paragraphs made of a single bold line used as headings: "ignore", "warn" (report)
+# or "fix" (convert them to headings)
+bold_headings = "ignore"
+# Level of the headings bold lines are converted to (1-6)
+bold_heading_level = 2
+
+[blank_lines]
+# Blank lines separating each kind of block from its neighbors (when
+# formatting.blank_lines is enabled), e.g. to match markdownlint's MD022/MD031/MD032/MD058
+before_code = true
+after_code = true
+before_tables = true
+after_tables = true
+before_blockquotes = true
+after_blockquotes = true
+before_lists = true
+# Blank line between a list and a following heading (other lines continue the last item)
+after_lists = true
+around_thematic_breaks = true
 
 [spacing]
 # Whether to add spaces between CJK and ASCII/digits
 cjk_ascii = true
+# Whether to remove single spaces between CJK and ASCII/digits instead, for house
+# styles forbidding them (requires cjk_ascii = false)
+strip_cjk_ascii = false
 # Whether to add spaces around inline code spans
 around_code_spans = true
+# Scripts spaced from ASCII letters and digits: "han", "hiragana", "katakana", "hangul"
+cjk_scripts = ["han", "hiragana", "katakana", "hangul"]
+# Character inserted between CJK and ASCII: "space", "thin" (U+2009 thin space)
+# or "nbsp" (U+00A0 no-break space)
+cjk_separator = "space"
+# Unit symbols joined to the preceding number ("50 %" to "50%") and spaced from
+# CJK text like it ("50%增长" to "50% 增长"); [] to disable
+units = ["%", "％", "‰", "℃", "℉", "°C", "°F", "°"]
+# Terms never spaced from CJK text nor split, such as model numbers ("华为P40")
+exceptions = []
+# Currency symbols joined to the following number ("¥ 100" to "¥100")
+currencies = ["¥", "￥", "$", "€", "£"]
+# Amounts with a currency, unit or time suffix (¥100, 50%, 25°C, 3:00PM) next to
+# CJK text: "space" (spaced like ASCII words, 价格 ¥100 元) or "attach" (价格¥100元)
+amounts = "space"
+
+[punctuation]
+# Punctuation in prose (never in code spans or URLs): "preserve", "smart"
+# (curly quotes, "..." to "…", "--" to "–" and "---" to "—") or "straight"
+# (the reverse, back to ASCII)
+mode = "preserve"
+# Quotes around Chinese/Japanese text in smart mode: "curly" (“”) or "corner" (「」)
+cjk_quotes = "curly"
+# En/em dashes, applied after mode: "preserve", "ascii" (--/---) or "unicode" (–/—)
+dashes = "preserve"
+# Ellipses, including spaced ". . .": "preserve", "ascii" (...) or "unicode" (…)
+ellipsis = "preserve"
+# ASCII punctuation ending or inside Chinese/Japanese sentences (never in code,
+# numbers or URLs): "preserve", "chinese" (, . ? ! : ; () to ，。？！：；（）)
+# or "japanese" (the same, with , to 、)
+cjk_width = "preserve"
+# Longest run of one repeated full-width mark (。。 or ！！！), longer runs are
+# shortened to it; 0 for no limit
+cjk_max_repeat = 0
+
+[wrap]
+# How prose paragraphs are wrapped: "preserve" (line breaks as written), "no" or
+# "unwrap" (one line per paragraph, keeping hard breaks), "sentence" (one line
+# per sentence) or "width" (reflowed to the maximum width; lines break between
+# Chinese/Japanese characters without a space and never start with closing
+# punctuation)
+mode = "preserve"
+# Maximum line width in columns, CJK characters counting as two
+width = 80
+
+[protect]
+# Regular expressions matching lines that are passed through verbatim
+# (never spaced or wrapped), e.g. snippet and include markers
+patterns = ['^\s*-{2,}8<-{2,}', '^\s*\{%.*%\}\s*$']
+# Whether lines made only of badge images and links (shields.io, badgen...) are
+# passed through verbatim
+badge_lines = true
+# Whether paragraphs that look like ASCII art or manually aligned columns (box-drawing
+# characters, +---+ corners, runs of spaces inside several lines) are passed through verbatim
+aligned_text = true
+# Length in bytes above which a line (base64 data URI images, minified HTML...) is
+# passed through verbatim, 0 for no limit
+max_line_length = 10000
+
+[toc]
+# Table of contents generated between <!-- toc --> and <!-- tocstop --> markers
+# Heading levels listed in the TOC
+min_level = 1
+max_level = 6
+# Headings ending with this marker are left out of the TOC
+ignore_marker = "<!-- toc-ignore -->"
+# Whether to generate an ordered list instead of an unordered one
+ordered = false
+
+[anchors]
+# Slug algorithm for heading anchors: "github", "gitlab", "azure" or "pandoc"
+style = "github"
+# Links to #anchors matching no heading: "ignore", "warn" (report) or "fix"
+# (point them to the heading they most likely meant)
+check_links = "ignore"
+# Whether to add a {#id} attribute with the generated anchor to headings without one
+generate_ids = false
+
+[tables]
+# How cell widths are measured: "display" (CJK characters and emoji take two
+# columns) or "chars" (one column per character)
+cell_width = "display"
+# Maximum width of a column (0 = no limit), wider cells are left unpadded
+max_column_width = 0
+# Whether to wrap data cells wider than max_column_width with <br> line breaks
+wrap_cells = false
+# Rows with fewer or more cells than the header: "ignore", "warn" (report) or
+# "fix" (pad short rows with empty cells, rows with extra cells are reported)
+ragged_rows = "ignore"
+# Line break tags in cells: "preserve" (as written), "<br>", "<br/>" or "<br />"
+line_break = "preserve"
+# Whether to convert simple HTML <table> blocks (header row of <th> cells, no
+# rowspan/colspan) into pipe tables
+convert_html = false
+# Delimiter row dashes: "full" (fill the column width) or "minimal" (always "---",
+# data rows are still padded)
+delimiter_style = "full"
+
+[links]
+# Bare https://... URLs in prose: "preserve" or "autolink" (wrap them in <...>
+# so strict CommonMark renderers link them)
+bare_urls = "preserve"
+# Links to their own URL: "preserve", "angle" (<https://example.com>) or "inline"
+# ([example.com](https://example.com)), so a document uses one style
+autolinks = "preserve"
+# Link style: "preserve", "reference" ([text][label], with the definitions
+# collected at the bottom of the document) or "inline" ([text](url), deleting
+# the definitions no longer used)
+style = "preserve"
+# Labels of generated reference definitions: "numbered" ([1]) or "slug" ([link-text])
+reference_labels = "numbered"
+# Whether to group link reference definitions at the bottom of the document,
+# sorted by label, dropping repeated labels and merging identical destinations
+sort_definitions = false
+# References to undefined labels and unused definitions: "ignore", "warn"
+# (report) or "fix" (also delete unused definitions)
+check_references = "ignore"
+# Quotes around link, image and definition titles: "preserve", "double" ("title"),
+# "single" ('title') or "parens" ((title))
+title_quotes = "preserve"
+# Link destinations containing literal spaces, which many renderers do not link:
+# "preserve", "encode" (spaces become %20) or "angle" (wrap them in <...>)
+destination_spaces = "preserve"
+# Whether --check reports relative link and image targets that do not exist on
+# disk (relative to the checked file, or the current directory for stdin)
+check_targets = false
+# Base URL of the repository files, used by repository_links
+repository_url = ""
+# Links to files of the repository: "preserve", "relative" (absolute links under
+# repository_url become relative to the document) or "absolute" (the reverse)
+repository_links = "preserve"
+# Images without alt text (![](...)): "ignore", "warn" (report) or "fix" (use
+# the file name stem of the image as a placeholder)
+image_alt = "ignore"
+
+[links.rewrite]
+# Link destination prefixes to rewrite, for example when documentation moves
+# (the longest matching prefix wins):
+# "https://github.com/org/repo/blob/main/" = "/"
+
+[emoji]
+# Whether to replace emoji shortcode aliases with their preferred GitHub name
+# (:thumbsup: becomes :+1:, :satisfied: becomes :laughing:)
+normalize_aliases = false
+# Emoji form, for renderers supporting only one: "preserve", "shortcode" (known
+# Unicode emoji become :tada:) or "unicode" (known shortcodes become 🎉)
+style = "preserve"
+
+[front_matter]
+# Whether to format the YAML front matter (keys quoted only when needed, 2-space
+# indentation); front matter with comments or anchors is always kept as written
+format = false
+# Top-level keys put first, in this order, e.g. ["title", "date", "tags"]
+key_order = []
+# Whether to sort the other top-level keys alphabetically
+sort_keys = false
 "#;
 
 /// Find project configuration file by searching upward from start directory
@@ -261,7 +1169,7 @@ fn load_config(working_dir: &Path, explicit_config: Option<&Path>) -> Result<MdF
     if let Some(config_path) = explicit_config {
         let content = std::fs::read_to_string(config_path)
             .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
-        let config: MdFormatConfig = toml::from_str(&content)
+        let config = parse_config(&content)
             .with_context(|| format!("Config file format error: {:?}", config_path))?;
         validate_config(&config)?;
         return Ok(config);
@@ -277,7 +1185,7 @@ fn load_config(working_dir: &Path, explicit_config: Option<&Path>) -> Result<MdF
             .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
 
         // Parse and override directly (unspecified TOML fields will use Default)
-        config = toml::from_str(&content)
+        config = parse_config(&content)
             .with_context(|| format!("Config file format error: {:?}", config_path))?;
     }
 
@@ -285,6 +1193,27 @@ fn load_config(working_dir: &Path, explicit_config: Option<&Path>) -> Result<MdF
     Ok(config)
 }
 
+/// Parse a config file, presetting the options its locale implies
+fn parse_config(content: &str) -> Result<MdFormatConfig> {
+    let mut table: toml::Table = toml::from_str(content)?;
+    let locale: Locale = match table.get("locale") {
+        Some(locale) => locale.clone().try_into()?,
+        None => Locale::None,
+    };
+    let profile: toml::Table = toml::from_str(locale.profile())?;
+    for (section, preset) in profile {
+        let options = table
+            .entry(section)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if let (toml::Value::Table(options), toml::Value::Table(preset)) = (options, preset) {
+            for (key, value) in preset {
+                options.entry(key).or_insert(value);
+            }
+        }
+    }
+    Ok(toml::Value::Table(table).try_into()?)
+}
+
 /// Validate configuration value validity
 fn validate_config(config: &MdFormatConfig) -> Result<()> {
     // Validate unordered list marker
@@ -295,6 +1224,25 @@ fn validate_config(config: &MdFormatConfig) -> Result<()> {
         );
     }
 
+    // Validate thematic break form
+    if !RE_THEMATIC_BREAK_FORM
+        .is_match(&config.formatting.thematic_break)
+        .unwrap_or(false)
+    {
+        anyhow::bail!(
+            "Invalid config value: formatting.thematic_break = '{}' (must be 3 or more '-', '*' or '_')",
+            config.formatting.thematic_break
+        );
+    }
+
+    // Validate strong emphasis marker
+    if !matches!(config.formatting.strong_marker.as_str(), "preserve" | "**" | "__") {
+        anyhow::bail!(
+            "Invalid config value: formatting.strong_marker = '{}' (must be 'preserve', '**' or '__')",
+            config.formatting.strong_marker
+        );
+    }
+
     // Validate heading numbering level
     if config.headings.numbering_start_level > 6 {
         anyhow::bail!(
@@ -303,10 +1251,65 @@ fn validate_config(config: &MdFormatConfig) -> Result<()> {
         );
     }
 
+    // Validate bold heading level
+    if !(1..=6).contains(&config.headings.bold_heading_level) {
+        anyhow::bail!(
+            "Invalid config value: headings.bold_heading_level = {} (must be between 1-6)",
+            config.headings.bold_heading_level
+        );
+    }
+
+    // Validate table line break style
+    if !matches!(config.tables.line_break.as_str(), "preserve" | "<br>" | "<br/>" | "<br />") {
+        anyhow::bail!(
+            "Invalid config value: tables.line_break = '{}' (must be 'preserve', '<br>', '<br/>' or '<br />')",
+            config.tables.line_break
+        );
+    }
+
+    // Validate CJK-ASCII spacing modes
+    if config.spacing.cjk_ascii && config.spacing.strip_cjk_ascii {
+        anyhow::bail!(
+            "Invalid config value: spacing.strip_cjk_ascii = true (requires spacing.cjk_ascii = false)"
+        );
+    }
+
+    // Validate wrap width
+    if config.wrap.width == 0 {
+        anyhow::bail!("Invalid config value: wrap.width = 0 (must be at least 1)");
+    }
+
+    // Validate TOC levels
+    let (min_level, max_level) = (config.toc.min_level, config.toc.max_level);
+    if !(1..=6).contains(&min_level) || !(1..=6).contains(&max_level) || min_level > max_level {
+        anyhow::bail!(
+            "Invalid config value: toc.min_level = {}, toc.max_level = {} (must be between 1-6, min_level <= max_level)",
+            min_level,
+            max_level
+        );
+    }
+
     // Validate indentation
     if config.lists.indent == 0 {
         anyhow::bail!("Invalid config value: lists.indent = 0 (must be greater than 0)");
     }
+    if config.lists.tab_width == 0 {
+        anyhow::bail!("Invalid config value: lists.tab_width = 0 (must be greater than 0)");
+    }
+
+    // Validate repository URL
+    if config.links.repository_links != links::RepositoryLinks::Preserve && config.links.repository_url.is_empty() {
+        anyhow::bail!(
+            "Invalid config value: links.repository_url = '' (must be set when links.repository_links is not 'preserve')"
+        );
+    }
+
+    // Validate protected line patterns
+    for pattern in &config.protect.patterns {
+        if let Err(e) = Regex::new(pattern) {
+            anyhow::bail!("Invalid config value: protect.patterns = '{}' ({})", pattern, e);
+        }
+    }
 
     Ok(())
 }
@@ -374,7 +1377,27 @@ fn build_final_config(args: &CliArgs) -> Result<MdFormatConfig> {
         config.formatting.blank_lines = false;
     }
 
-    // 3. Validate final configuration again
+    config.formatting.dialect = args.dialect.unwrap_or_else(|| dialects::Dialect::from_path(args.input.as_deref()));
+
+    if let Some(eol) = args.eol {
+        config.formatting.end_of_line = eol;
+    }
+
+    if let Some((mode, width)) = args.wrap {
+        config.wrap.mode = mode;
+        config.wrap.width = width.unwrap_or(config.wrap.width);
+    }
+
+    if args.unwrap {
+        config.wrap.mode = WrapMode::No;
+    }
+
+    // 3. Locate the document in its repository
+    if config.links.repository_links != links::RepositoryLinks::Preserve {
+        config.links.document_dir = links::document_dir(args.input.as_deref(), &working_dir);
+    }
+
+    // 4. Validate final configuration again
     validate_config(&config)?;
 
     Ok(config)
@@ -388,6 +1411,9 @@ fn build_final_config(args: &CliArgs) -> Result<MdFormatConfig> {
     about = "Formats Markdown code with consistent empty lines and spacing"
 )]
 struct CliArgs {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+
     /// Input file (default: stdin)
     input: Option<PathBuf>,
 
@@ -396,7 +1422,7 @@ struct CliArgs {
     output: Option<PathBuf>,
 
     /// Config file path (if specified, other auto-searched config files will be ignored)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     config: Option<PathBuf>,
 
     /// Generate example config file to specified path (default: ./.mdformat.toml)
@@ -406,15 +1432,15 @@ struct CliArgs {
     // The following arguments will override corresponding values in config file
 
     /// Number of spaces for list indentation (overrides config file)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     indent: Option<usize>,
 
     /// Unordered list marker: "-", "*", "+" (overrides config file)
-    #[arg(short = 'm', long)]
+    #[arg(short = 'm', long, global = true)]
     unordered_marker: Option<String>,
 
     /// Heading numbering start level 0-6 (overrides config file)
-    #[arg(short = 'n', long)]
+    #[arg(short = 'n', long, global = true)]
     heading_numbering: Option<u8>,
 
     /// Disable table formatting
@@ -436,6 +1462,119 @@ struct CliArgs {
     /// Disable blank lines between elements
     #[arg(long)]
     no_blank_lines: bool,
+
+    /// Markdown dialect of the input (default: from the file extension, .mdx, .Rmd or .qmd)
+    #[arg(long, value_name = "DIALECT", global = true)]
+    dialect: Option<dialects::Dialect>,
+
+    /// Format the Markdown in the line comments of source code starting with this prefix, such as "#" or "//"
+    #[arg(long, value_name = "PREFIX")]
+    comment_prefix: Option<String>,
+
+    /// Line endings of the output (overrides config file)
+    #[arg(long, value_name = "EOL", global = true)]
+    eol: Option<LineEnding>,
+
+    /// Prose wrapping: "no", "preserve", "sentence" or a width in columns (overrides config file)
+    #[arg(long, value_name = "POLICY", global = true, value_parser = wrap::parse_policy)]
+    wrap: Option<(WrapMode, Option<usize>)>,
+
+    /// Join the lines of each prose paragraph, keeping hard breaks (same as --wrap=no)
+    #[arg(long, global = true, conflicts_with = "wrap")]
+    unwrap: bool,
+
+    /// Check mode: report issues and exit with an error if the input is not formatted, without writing output
+    #[arg(long)]
+    check: bool,
+
+    /// Print the heading tree (level, text, slug, line) instead of the formatted document
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "json")]
+    outline: Option<outline::OutlineFormat>,
+
+    /// Write UTF-8 output instead of re-encoding it in the detected input encoding (GBK, Big5...)
+    #[arg(long, conflicts_with = "output_encoding")]
+    to_utf8: bool,
+
+    /// Encoding of the input, such as "gbk", "big5" or "shift_jis" (default: detected)
+    #[arg(long, value_name = "ENCODING", global = true, value_parser = encoding::parse_label)]
+    input_encoding: Option<&'static encoding_rs::Encoding>,
+
+    /// Encoding of the output (default: that of the input)
+    #[arg(long, value_name = "ENCODING", value_parser = encoding::parse_label)]
+    output_encoding: Option<&'static encoding_rs::Encoding>,
+}
+
+/// Subcommands
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Print the table of contents of a document
+    Toc {
+        /// Input file (default: stdin)
+        input: Option<PathBuf>,
+    },
+    /// Convert CSV/TSV data into an aligned Markdown table
+    Table {
+        /// Delimited data file, the first row being the header ("-" for stdin)
+        #[arg(long, value_name = "FILE")]
+        from_csv: PathBuf,
+        /// Field delimiter (default: tab for .tsv files, comma otherwise)
+        #[arg(short, long)]
+        delimiter: Option<char>,
+    },
+}
+
+/// Read the input file, or stdin if no file is given, returning its text and
+/// its encoding, detected unless given
+fn read_input(
+    path: Option<&Path>,
+    encoding: Option<&'static encoding_rs::Encoding>,
+) -> Result<(String, &'static encoding_rs::Encoding)> {
+    let mut bytes = Vec::new();
+    match path {
+        Some(path) => File::open(path)?.read_to_end(&mut bytes)?,
+        None => io::stdin().read_to_end(&mut bytes)?,
+    };
+    let (content, encoding) = match encoding {
+        Some(encoding) => (encoding::decode_as(&bytes, encoding)?, encoding),
+        None => encoding::decode(&bytes),
+    };
+    debug!("input encoding: {}", encoding.name());
+    Ok((content, encoding))
+}
+
+/// Handle the toc subcommand: print the TOC of the formatted document
+fn handle_toc(
+    input: Option<&Path>,
+    encoding: Option<&'static encoding_rs::Encoding>,
+    config: &MdFormatConfig,
+) -> Result<()> {
+    let (content, _) = read_input(input, encoding)?;
+    let formatted = format_markdown(&content, config);
+    let lines = formatted.lines().collect::<Vec<_>>();
+    let toc = toc::render_toc(&toc::collect_headings(&lines, config), config);
+
+    let mut output = toc.join("\n");
+    output.push('\n');
+    io::stdout().write_all(output.as_bytes())?;
+    Ok(())
+}
+
+fn handle_table(
+    from_csv: &Path,
+    delimiter: Option<char>,
+    encoding: Option<&'static encoding_rs::Encoding>,
+    config: &MdFormatConfig,
+) -> Result<()> {
+    let input = (from_csv != Path::new("-")).then_some(from_csv);
+    let (content, _) = read_input(input, encoding)?;
+    let tsv = from_csv.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"));
+    let delimiter = delimiter.unwrap_or(if tsv { '\t' } else { ',' });
+    let table = tables::from_delimited(&content, delimiter, config);
+
+    let mut output = table.join("\n");
+    output.push('\n');
+    io::stdout().write_all(output.as_bytes())?;
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -449,33 +1588,168 @@ fn main() -> Result<()> {
     // Load and build configuration
     let config = build_final_config(&args)?;
 
+    // Handle subcommands
+    match &args.command {
+        Some(CliCommand::Toc { input }) => return handle_toc(input.as_deref(), args.input_encoding, &config),
+        Some(CliCommand::Table { from_csv, delimiter }) => {
+            return handle_table(from_csv, *delimiter, args.input_encoding, &config);
+        }
+        None => {}
+    }
+
     // Read input content
-    let mut content = String::new();
-    match &args.input {
-        Some(path) => File::open(path)?.read_to_string(&mut content)?,
-        None => io::stdin().read_to_string(&mut content)?,
+    let (content, input_encoding) = read_input(args.input.as_deref(), args.input_encoding)?;
+
+    // Outline mode: print the heading tree of the input
+    if let Some(format) = args.outline {
+        let output = outline::render_outline(&outline::build_outline(&content, &config), format)?;
+        match &args.output {
+            Some(path) => File::create(path)?.write_all(output.as_bytes())?,
+            None => io::stdout().write_all(output.as_bytes())?,
+        };
+        return Ok(());
+    }
+
+    // Format code (with configuration), only the Markdown cells of notebooks
+    // or the comments of source code
+    let (formatted, mut diagnostics) = if let Some(prefix) = &args.comment_prefix {
+        (comments::format_comments(&content, prefix, &config), Vec::new())
+    } else if args.input.as_deref().is_some_and(notebook::is_notebook) {
+        (notebook::format_notebook(&content, &config)?, Vec::new())
+    } else {
+        format_document(&content, &config)
     };
+    let formatted = apply_line_ending(formatted, &content, config.formatting.end_of_line);
+    if args.check && config.links.check_targets {
+        let base = args.input.as_deref().and_then(Path::parent).unwrap_or(Path::new(""));
+        links::check_link_targets(&content, base, &mut diagnostics);
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    }
+
+    let source = args
+        .input
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "<stdin>".to_string());
+    // Fixed issues are only reported in check mode
+    for diagnostic in diagnostics.iter().filter(|d| args.check || !d.fixed) {
+        eprintln!("{}:{}", source, diagnostic);
+    }
 
-    // Format code (with configuration)
-    let formatted = format_markdown(&content, &config);
+    // Check mode: report instead of writing
+    if args.check {
+        let unchanged = formatted == content;
+        if !unchanged {
+            eprintln!("{}: would be reformatted", source);
+        }
+        if !unchanged || !diagnostics.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    // Write output
+    // Write output, in the input encoding unless UTF-8 is requested
+    let output_encoding = match args.output_encoding {
+        Some(encoding) => encoding,
+        None if args.to_utf8 => encoding_rs::UTF_8,
+        None => input_encoding,
+    };
+    let output = encoding::encode(&formatted, output_encoding)?;
     match &args.output {
-        Some(path) => File::create(path)?.write_all(formatted.as_bytes())?,
-        None => io::stdout().write_all(formatted.as_bytes())?,
+        Some(path) => File::create(path)?.write_all(&output)?,
+        None => io::stdout().write_all(&output)?,
     };
     Ok(())
 }
 
+/// Convert the line endings of the formatted text to CRLF when requested, or in
+/// auto mode when most lines of the input end with CRLF
+fn apply_line_ending(formatted: String, input: &str, eol: LineEnding) -> String {
+    let crlf = match eol {
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+        LineEnding::Auto => {
+            let crlf_count = input.matches("\r\n").count();
+            crlf_count > 0 && crlf_count * 2 >= input.matches('\n').count()
+        }
+    };
+    if crlf { formatted.replace('\n', "\r\n") } else { formatted }
+}
+
+/// An issue reported by a lint rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Line number in the input (1-based)
+    pub line: usize,
+    /// Name of the rule reporting the issue
+    pub rule: &'static str,
+    pub message: String,
+    /// Whether the issue was fixed in the formatted output
+    pub fixed: bool,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: [{}] {}", self.line, self.rule, self.message)?;
+        if self.fixed {
+            write!(f, " (fixed)")?;
+        }
+        Ok(())
+    }
+}
+
 fn format_markdown(text: &str, config: &MdFormatConfig) -> String {
+    format_document(text, config).0
+}
+
+/// Format a document, returning the formatted text and the issues reported by
+/// the lint rules
+fn format_document(text: &str, config: &MdFormatConfig) -> (String, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+
     // Convert string to a vector of lines
     // Remove empty lines at the beginning and end
     // And remove spaces at the end of each line
-    let lines = text
-        .trim()
+    // Ideographic spaces are kept unless converted (based on config)
+    let preserve = config.formatting.ideographic_spaces == IdeographicSpaces::Preserve;
+    let is_trimmed = |c: char| c.is_whitespace() && !(preserve && c == '\u{3000}');
+    // Outer whitespace is kept (based on config) up to the line break before the first line
+    let keep_outer = config.formatting.preserve_outer_whitespace;
+    let leading = &text[..text.len() - text.trim_start_matches(is_trimmed).len()];
+    let leading = if keep_outer { &leading[..leading.rfind('\n').map_or(0, |i| i + 1)] } else { leading };
+    let content_end = text.trim_end_matches(is_trimmed).len().max(leading.len());
+    let trailing = &text[content_end..];
+    let trimmed = &text[leading.len()..content_end];
+    let line_offset = leading.matches('\n').count();
+    let mut lines = trimmed
         .lines()
-        .map(|line| line.trim_end())
+        .map(|line| line.trim_end_matches(is_trimmed).to_string())
         .collect::<Vec<_>>();
+    // Lines ending with a trailing-space hard break, marked once the blocks are extracted
+    let mut hard_breaks = wrap::trailing_space_breaks(&trimmed.lines().collect::<Vec<_>>());
+
+    // Apply line rules on the original lines so reported line numbers match the input
+    if !preserve {
+        inline::convert_ideographic_spaces(&mut lines);
+    }
+    inline::strip_invisible_chars(&mut lines, config, line_offset, &mut diagnostics);
+    links::check_image_alt(&mut lines, config, line_offset, &mut diagnostics);
+    headings::apply_heading_rules(&mut lines, config, line_offset, &mut diagnostics);
+    if config.tables.convert_html {
+        let origins = tables::convert_html_tables(&mut lines);
+        hard_breaks = origins.iter().map(|&i| hard_breaks[i]).collect();
+    }
+    let lines = lines.iter().map(|line| line.as_str()).collect::<Vec<_>>();
+
+    // Replace blocks that need special handling with placeholder lines
+    let (mut lines, blocks, origins) = extract_blocks(&lines, config);
+
+    // Keep the hard breaks of prose lines as backslashes when rewrapping
+    if config.wrap.mode != WrapMode::Preserve {
+        let hard_breaks = origins.iter().map(|&i| hard_breaks[i]).collect::<Vec<_>>();
+        lines = wrap::mark_hard_breaks(&lines.iter().map(|line| line.as_str()).collect::<Vec<_>>(), &hard_breaks);
+    }
+    let lines = lines.iter().map(|line| line.as_str()).collect::<Vec<_>>();
 
     // Format all lines
     let new_lines = format_lines(lines, config);
@@ -489,19 +1763,354 @@ fn format_markdown(text: &str, config: &MdFormatConfig) -> String {
 
     let mut ret = new_lines.join("\n");
 
+    // Fix link destinations with spaces and rewrite their prefixes, then convert
+    // links between inline and reference style (if enabled)
+    ret = links::fix_destination_spaces(&ret, config.links.destination_spaces);
+    ret = links::rewrite_destinations(&ret, &config.links.rewrite);
+    ret = links::convert_repository_links(
+        &ret,
+        &config.links.repository_url,
+        &config.links.document_dir,
+        config.links.repository_links,
+    );
+    match config.links.style {
+        links::LinkStyle::Preserve => {}
+        links::LinkStyle::Reference => ret = links::to_reference_links(&ret, config.links.reference_labels),
+        links::LinkStyle::Inline => ret = links::to_inline_links(&ret),
+    }
+    links::check_references(text, config, &mut diagnostics);
+    if config.links.check_references == RuleMode::Fix {
+        ret = links::remove_unused_definitions(&ret);
+    }
+    if config.links.sort_definitions {
+        ret = links::sort_definitions(&ret);
+    }
+    ret = links::normalize_titles(&ret, config.links.title_quotes);
+
     // Format tables (if enabled)
+    tables::check_ragged_rows(text, config, &mut diagnostics);
     if config.formatting.format_tables {
-        ret = format_tables(&ret);
+        ret = tables::format_tables(&ret, config);
     }
 
-    // End with "\n"
-    if !ret.ends_with('\n') {
-        ret.push('\n');
+    // Add {#id} attributes to headings (if enabled)
+    if config.anchors.generate_ids {
+        ret = anchors::add_heading_ids(&ret, config.anchors.style);
     }
 
+    // Regenerate the table of contents from the formatted headings
+    ret = toc::update_toc(&ret, config);
+
+    // Check intra-document links against the heading anchors
+    ret = anchors::check_anchor_links(&ret, text, config, &mut diagnostics);
+
+    // Reflow prose paragraphs (based on config)
+    if config.wrap.mode != WrapMode::Preserve {
+        ret = wrap::wrap_paragraphs(&ret, config);
+    }
+
+    // Put the extracted blocks back
+    ret = restore_blocks(&ret, &blocks);
+
+    // End with the configured newlines, or put back the outer whitespace
+    ret.truncate(ret.trim_end_matches('\n').len());
+    if keep_outer {
+        ret.insert_str(0, &leading.replace('\r', ""));
+        ret.push_str(&trailing.replace('\r', ""));
+    } else {
+        let newlines = match config.formatting.final_newline {
+            FinalNewline::Single => 1,
+            FinalNewline::None => 0,
+            FinalNewline::Preserve => trailing.matches('\n').count(),
+        };
+        ret.push_str(&"\n".repeat(newlines));
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    (ret, diagnostics)
+}
+
+/// Prefix of the placeholder lines standing in for extracted blocks
+const BLOCK_PLACEHOLDER_PREFIX: &str = "\x00MDBLOCK";
+
+/// Prefix of the placeholder lines standing in for protected lines
+const LINE_PLACEHOLDER_PREFIX: &str = "\x00MDLINE";
+
+fn block_placeholder(index: usize) -> String {
+    format!("{}{}\x00", BLOCK_PLACEHOLDER_PREFIX, index)
+}
+
+fn line_placeholder(index: usize) -> String {
+    format!("{}{}\x00", LINE_PLACEHOLDER_PREFIX, index)
+}
+
+/// Extract blocks that must not go through the line-based passes
+///
+/// Each block is formatted on its own and replaced with a single placeholder
+/// line, so the list and table passes never see its content and the block
+/// keeps its required structure.
+///
+/// YAML/TOML front matter is kept as written, or formatted if enabled. Display
+/// math (`$$` ... `$$`) and bare LaTeX environments (`\begin{align}`) are kept
+/// verbatim. Pandoc fenced divs have their content formatted recursively.
+/// Foreign blocks of the dialect and tables with formatting turned off are
+/// kept verbatim. MkDocs-Material content tabs (`=== "Title"` followed by a
+/// 4-space indented body) have their body formatted recursively. Lines
+/// matching the configured protected patterns, badge lines, aligned text and
+/// overlong lines are kept verbatim without blank lines around them.
+///
+/// The index of the input line each output line starts at is returned along
+/// with the blocks.
+fn extract_blocks(lines: &[&str], config: &MdFormatConfig) -> (Vec<String>, Vec<String>, Vec<usize>) {
+    // Patterns are validated when the config is loaded
+    let protected = config
+        .protect
+        .patterns
+        .iter()
+        .filter_map(|pattern| Regex::new(pattern).ok())
+        .collect::<Vec<_>>();
+
+    let mut result = Vec::new();
+    let mut blocks = Vec::new();
+    let mut origins = Vec::new();
+    let mut in_code = false;
+    let mut i = 0;
+
+    // Front matter is kept untouched, unless YAML formatting is enabled
+    if let Some(end) = front_matter_end(lines) {
+        let formatted = match config.front_matter.format {
+            true => front_matter::format_front_matter(&lines[..=end], &config.front_matter),
+            false => None,
+        };
+        blocks.push(formatted.unwrap_or_else(|| lines[..=end].join("\n")));
+        result.push(block_placeholder(0));
+        origins.push(i);
+        i = end + 1;
+    }
+
+    let mut aligned = vec![false; i];
+    if config.protect.aligned_text {
+        aligned.extend(aligned_lines(&lines[i..]));
+    } else {
+        aligned.resize(lines.len(), false);
+    }
+
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("```") {
+            in_code = !in_code;
+        }
+        // Display math is opaque, with blank lines around it
+        if !in_code && let Some(count) = display_math_len(lines, i).or_else(|| latex_environment_len(lines, i)) {
+            blocks.push(lines[i..i + count].join("\n"));
+            result.push(block_placeholder(blocks.len() - 1));
+            origins.push(i);
+            i += count;
+            continue;
+        }
+        // Fenced divs (Pandoc, Quarto) have their content formatted recursively
+        let divs = config.formatting.pandoc || config.formatting.dialect == dialects::Dialect::Quarto;
+        if !in_code && divs && let Some(count) = divs::div_len(lines, i) {
+            blocks.push(divs::format_div(&lines[i..i + count], config));
+            result.push(block_placeholder(blocks.len() - 1));
+            origins.push(i);
+            i += count;
+            continue;
+        }
+        let badges = config.protect.badge_lines && links::is_badge_line(line);
+        let long = config.protect.max_line_length > 0 && line.len() > config.protect.max_line_length;
+        let foreign = dialects::is_verbatim_line(line, config.formatting.dialect);
+        if !in_code
+            && (long || badges || foreign || aligned[i] || protected.iter().any(|re| re.is_match(line).unwrap_or(false)))
+        {
+            blocks.push(line.to_string());
+            result.push(line_placeholder(blocks.len() - 1));
+            origins.push(i);
+            i += 1;
+            continue;
+        }
+        // Foreign blocks of the dialect (MDX imports, JSX components) are kept untouched
+        if !in_code && let Some(count) = dialects::block_len(lines, i, config.formatting.dialect) {
+            blocks.push(lines[i..i + count].join("\n"));
+            result.push(block_placeholder(blocks.len() - 1));
+            origins.push(i);
+            i += count;
+            continue;
+        }
+        // Tables with formatting turned off are kept untouched
+        if !in_code && let Some(count) = tables::disabled_table_len(lines, i) {
+            blocks.push(lines[i..i + count].join("\n"));
+            result.push(block_placeholder(blocks.len() - 1));
+            origins.push(i);
+            i += count;
+            continue;
+        }
+        if in_code || !RE_CONTENT_TAB.is_match(line).unwrap_or(false) {
+            result.push(line.to_string());
+            origins.push(i);
+            i += 1;
+            continue;
+        }
+
+        // Collect the indented tab body (blank lines are kept only when
+        // followed by more indented content)
+        let mut end = i + 1;
+        let mut body_end = end;
+        while end < lines.len() {
+            let body_line = lines[end];
+            if body_line.is_empty() {
+                end += 1;
+            } else if body_line.starts_with("    ") || body_line.starts_with('\t') {
+                end += 1;
+                body_end = end;
+            } else {
+                break;
+            }
+        }
+
+        blocks.push(format_content_tab(line, &lines[i + 1..body_end], config));
+        result.push(block_placeholder(blocks.len() - 1));
+        origins.push(i);
+        i = body_end;
+    }
+
+    (result, blocks, origins)
+}
+
+/// Mark the lines of the paragraphs that look like ASCII art or manually
+/// aligned columns
+fn aligned_lines(lines: &[&str]) -> Vec<bool> {
+    let mut mask = vec![false; lines.len()];
+    let mut in_code = false;
+    let mut start = 0;
+    for i in 0..=lines.len() {
+        let line = lines.get(i).copied().unwrap_or("");
+        let content = line.trim_start();
+        let fence = content.starts_with("```") || content.starts_with("~~~");
+        let breaks = content.is_empty()
+            || fence
+            || in_code
+            || content.starts_with(['#', '>'])
+            || RE_LIST_ITEM.is_match(line).unwrap_or(false);
+        if breaks {
+            if i > start && is_aligned_paragraph(&lines[start..i]) {
+                mask[start..i].fill(true);
+            }
+            start = i + 1;
+        }
+        if fence {
+            in_code = !in_code;
+        }
+    }
+    mask
+}
+
+/// Whether the paragraph contains box-drawing characters or `+---+` corners,
+/// or several lines with runs of spaces inside them, unless it is a table
+fn is_aligned_paragraph(paragraph: &[&str]) -> bool {
+    if paragraph.iter().any(|line| tables::is_delimiter_row(line.trim())) {
+        return false;
+    }
+    let art = paragraph.iter().any(|line| {
+        line.chars().any(|c| ('\u{2500}'..='\u{259F}').contains(&c)) || RE_ASCII_BOX.is_match(line).unwrap_or(false)
+    });
+    let columns = paragraph
+        .iter()
+        .filter(|line| RE_ALIGNED_COLUMNS.is_match(line.trim()).unwrap_or(false))
+        .count();
+    art || columns >= 2
+}
+
+/// Find the closing delimiter line of the front matter at the start of the
+/// document (`---` ... `---`/`...` for YAML, `+++` ... `+++` for TOML)
+fn front_matter_end<S: AsRef<str>>(lines: &[S]) -> Option<usize> {
+    let closing: &[&str] = match lines.first().map(|line| line.as_ref()) {
+        Some("---") => &["---", "..."],
+        Some("+++") => &["+++"],
+        _ => return None,
+    };
+    lines
+        .iter()
+        .skip(1)
+        .position(|line| closing.contains(&line.as_ref()))
+        .map(|pos| pos + 1)
+}
+
+/// Number of lines of the display math block (`$$` ... `$$`, possibly on a
+/// single line) starting at `lines[start]`
+fn display_math_len(lines: &[&str], start: usize) -> Option<usize> {
+    let rest = lines[start].trim().strip_prefix("$$")?;
+    if let Some(math) = rest.strip_suffix("$$") {
+        return (!math.trim().is_empty() && !math.contains("$$")).then_some(1);
+    }
+    if rest.contains("$$") {
+        return None;
+    }
+    let end = lines[start + 1..].iter().position(|line| line.trim_end().ends_with("$$"))?;
+    Some(end + 2)
+}
+
+/// Number of lines of the LaTeX environment (`\begin{align}` ... `\end{align}`)
+/// starting at `lines[start]`, written bare for KaTeX or MathJax
+fn latex_environment_len(lines: &[&str], start: usize) -> Option<usize> {
+    let caps = RE_LATEX_BEGIN.captures(lines[start]).ok().flatten()?;
+    let name = caps.get(1)?.as_str();
+    let (begin, end) = (format!("\\begin{{{}}}", name), format!("\\end{{{}}}", name));
+    // Environments of the same name may be nested
+    let mut depth = 0;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        depth += line.matches(&begin).count();
+        depth = depth.saturating_sub(line.matches(&end).count());
+        if depth == 0 {
+            return Some(i - start + 1);
+        }
+    }
+    None
+}
+
+/// Format a content tab: the marker line followed by its recursively
+/// formatted body, re-indented by 4 spaces
+fn format_content_tab(marker: &str, body: &[&str], config: &MdFormatConfig) -> String {
+    let body = body
+        .iter()
+        .map(|line| {
+            line.strip_prefix("    ")
+                .or_else(|| line.strip_prefix('\t'))
+                .unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut ret = marker.to_string();
+    if body.trim().is_empty() {
+        return ret;
+    }
+
+    let formatted = format_markdown(&body, config);
+    if config.formatting.blank_lines {
+        ret.push('\n');
+    }
+    for line in formatted.trim_end().lines() {
+        ret.push('\n');
+        if !line.is_empty() {
+            ret.push_str("    ");
+            ret.push_str(line);
+        }
+    }
     ret
 }
 
+/// Replace placeholder lines with the extracted blocks
+fn restore_blocks(text: &str, blocks: &[String]) -> String {
+    let mut text = text.to_string();
+    for (i, block) in blocks.iter().enumerate() {
+        text = text
+            .replace(&block_placeholder(i), block)
+            .replace(&line_placeholder(i), block);
+    }
+    text
+}
+
 #[derive(Debug, PartialEq, Clone)]
 enum LineState {
     Normal,
@@ -513,6 +2122,9 @@ enum LineState {
     Title,
     List,
     Blockquote,
+    Block,
+    Verbatim,
+    ThematicBreak,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -541,6 +2153,19 @@ fn get_line_state(line: &str, prev_state: LineState) -> LineState {
     if line.is_empty() {
         return LineState::Empty;
     }
+    if line.starts_with(BLOCK_PLACEHOLDER_PREFIX) {
+        return LineState::Block;
+    }
+    if line.starts_with(LINE_PLACEHOLDER_PREFIX) {
+        return LineState::Verbatim;
+    }
+    if RE_THEMATIC_BREAK.is_match(line).unwrap_or(false) {
+        // A line of dashes right after a paragraph is a setext heading underline
+        let setext = prev_state == LineState::Normal && line.trim().chars().all(|c| c == '-');
+        if !setext {
+            return LineState::ThematicBreak;
+        }
+    }
     if RE_LIST_ITEM.is_match(line).unwrap_or(false) {
         return LineState::List;
     }
@@ -597,17 +2222,25 @@ impl HeadingCounters {
     }
 }
 
+/// Strip the numbering of a previous run from a heading title
+///
+/// A single number of three digits or more is never section numbering but part
+/// of the title (a year, a quantity).
+fn strip_heading_numbering(title: &str) -> &str {
+    let Ok(Some(m)) = RE_HEADING_NUMBERING.find(title) else {
+        return title;
+    };
+    let number = m.as_str().trim_end().trim_end_matches('.');
+    if number.contains('.') || number.len() < 3 { &title[m.end()..] } else { title }
+}
+
 /// Add numbering to headings
-fn add_heading_numbering(
-    line: &str,
-    heading_config: &HeadingOptions,
-    counters: &mut HeadingCounters,
-    spacing_config: &SpacingOptions,
-) -> String {
-    // Extract heading level
+fn add_heading_numbering(line: &str, counters: &mut HeadingCounters, config: &MdFormatConfig) -> String {
+    let heading_config = &config.headings;
+    // Extract heading level (only ATX headings, "#tag" is not a heading)
     let level = line.chars().take_while(|&c| c == '#').count();
-    if level == 0 || level > 6 {
-        return format_line(line, spacing_config);
+    if level == 0 || level > 6 || !(line[level..].is_empty() || line[level..].starts_with([' ', '\t'])) {
+        return format_line(line, config);
     }
 
     // Update counter
@@ -618,15 +2251,14 @@ fn add_heading_numbering(
         let numbering = counters.get_numbering(level, heading_config.numbering_start_level);
         let title_text = line[level..].trim_start();
 
-        // Remove existing numbering (simple approach: remove leading digits, dots, and spaces)
-        let title_text = title_text
-            .trim_start_matches(|c: char| c.is_numeric() || c == '.' || c == ' ');
+        // Remove existing numbering ("1", "1.2", "1.2.3." followed by a space)
+        let title_text = strip_heading_numbering(title_text);
 
         // Format: "## 1.2 Title"
         let formatted = format!("{} {} {}", "#".repeat(level), numbering, title_text);
-        format_line(&formatted, spacing_config)
+        format_line(&formatted, config)
     } else {
-        format_line(line, spacing_config)
+        format_line(line, config)
     }
 }
 
@@ -646,24 +2278,40 @@ fn format_lines(lines: Vec<&str>, config: &MdFormatConfig) -> Vec<String> {
         // insert space between CJK and ASCII
         let mut cur_state = get_line_state(line, prev_line_state.clone());
         debug!("{:?}: {}", cur_state, line);
+        // Blocks stay right below a heading when no blank line follows headings
+        let below_heading = prev_line_state == LineState::Title;
 
         match cur_state {
+            LineState::Normal if prev_line_state == LineState::Table && tables::is_caption(line) => {
+                // Pandoc table caption stays attached to the table
+                ret.push(format_line(line, config));
+                cur_state = LineState::Table;
+            }
+            LineState::Normal if !prev_line.is_empty() && is_block_attribute(line) => {
+                // Block attribute list stays attached to the element above it,
+                // the blank lines following a heading moving below it
+                let inserted = ret.iter().rev().take_while(|l| l.is_empty()).count();
+                ret.truncate(ret.len() - inserted);
+                ret.push(line.trim_end().to_string());
+                ret.extend(std::iter::repeat_n(String::new(), inserted));
+                cur_state = prev_line_state.clone();
+            }
             LineState::Normal => {
-                // must be an empty line after a table, code block or blockquote (if enabled)
-                if config.formatting.blank_lines
-                    && (prev_line_state == LineState::Table
-                        || prev_line_state == LineState::CodeEnd
-                        || prev_line_state == LineState::Blockquote)
-                {
+                // must be an empty line after a table, code block, blockquote or block (if enabled)
+                if prev_line_state != LineState::List && blank_line_after(&prev_line_state, config) {
                     ret.push(String::new());
                 }
 
                 // Normal line needs to be formatted
-                ret.push(format_line(line, &config.spacing));
+                ret.push(format_line(line, config));
             }
             LineState::CodeStart => {
                 // Must be an empty line before a code block (if enabled)
-                if config.formatting.blank_lines && prev_line_state != LineState::Empty {
+                if config.formatting.blank_lines
+                    && config.blank_lines.before_code
+                    && prev_line_state != LineState::Empty
+                    && !below_heading
+                {
                     ret.push(String::new());
                 }
                 ret.push(line.to_string());
@@ -671,27 +2319,66 @@ fn format_lines(lines: Vec<&str>, config: &MdFormatConfig) -> Vec<String> {
             LineState::Blockquote => {
                 // Must be an empty line before a blockquote (if enabled)
                 if config.formatting.blank_lines
+                    && config.blank_lines.before_blockquotes
                     && prev_line_state != LineState::Empty
                     && prev_line_state != LineState::Blockquote
+                    && !below_heading
                 {
                     ret.push(String::new());
                 }
-                ret.push(format_line(line, &config.spacing));
+                if line.chars().all(|c| c == '>' || c.is_whitespace()) {
+                    // Bare separator lines keep only their markers, so they are
+                    // never taken for blank lines splitting the quote
+                    ret.push(line.split_whitespace().collect::<Vec<_>>().join(" "));
+                } else {
+                    ret.push(format_line(line, config));
+                }
             }
             LineState::Code | LineState::CodeEnd => {
                 ret.push(line.to_string());
             }
+            LineState::ThematicBreak => {
+                // Thematic breaks are normalized and surrounded by empty lines (if enabled)
+                // (dashes right after a paragraph would make it a setext heading)
+                let around = config.formatting.blank_lines && config.blank_lines.around_thematic_breaks;
+                let setext = prev_line_state == LineState::Normal && config.formatting.thematic_break.starts_with('-');
+                if (around || setext) && prev_line_state != LineState::Empty && !below_heading {
+                    ret.push(String::new());
+                }
+                ret.push(config.formatting.thematic_break.clone());
+                if around {
+                    ret.push(String::new());
+                    cur_state = LineState::Empty;
+                }
+            }
+            LineState::Verbatim => {
+                // Protected lines are passed through as-is
+                ret.push(line.to_string());
+            }
+            LineState::Block => {
+                // Extracted blocks are surrounded by empty lines (if enabled)
+                if config.formatting.blank_lines && prev_line_state != LineState::Empty && !below_heading {
+                    ret.push(String::new());
+                }
+                ret.push(line.to_string());
+            }
             LineState::Table => {
                 // Must be an empty line before a table (if enabled)
                 if config.formatting.blank_lines
+                    && config.blank_lines.before_tables
                     && prev_line_state != LineState::Table
                     && prev_line_state != LineState::Empty
+                    && !below_heading
                 {
                     ret.push(String::new());
                 }
 
-                // Table line needs to be formatted
-                ret.push(format_line(line, &config.spacing));
+                // Table line needs to be formatted, cell by cell if tables are aligned
+                if config.formatting.format_tables {
+                    ret.push(tables::format_row(line, config));
+                } else {
+                    ret.push(format_line(line, config));
+                }
             }
             LineState::Empty => {
                 // Merge consecutive empty lines (if enabled)
@@ -700,40 +2387,46 @@ fn format_lines(lines: Vec<&str>, config: &MdFormatConfig) -> Vec<String> {
                 }
             }
             LineState::Title => {
-                // Must be an empty line after a table, list or code block (if enabled)
-                if config.formatting.blank_lines
-                    && (prev_line_state == LineState::Table
-                        || prev_line_state == LineState::CodeEnd
-                        || prev_line_state == LineState::List
-                        || prev_line_state == LineState::Blockquote)
+                let level = line.chars().take_while(|&c| c == '#').count();
+                let blank_lines_before = &config.headings.blank_lines_before;
+                if let Some(&count) = blank_lines_before.get(level - 1).or(blank_lines_before.last())
+                    && !ret.is_empty()
                 {
+                    // Exact number of blank lines before the heading
+                    while ret.last().is_some_and(|l: &String| l.is_empty()) {
+                        ret.pop();
+                    }
+                    ret.extend(std::iter::repeat_n(String::new(), count));
+                } else if blank_line_after(&prev_line_state, config) {
                     ret.push(String::new());
                 }
 
                 // Header line needs to be formatted (may add numbering)
                 let formatted = if let Some(ref mut counters) = heading_counters {
-                    add_heading_numbering(line, &config.headings, counters, &config.spacing)
+                    add_heading_numbering(line, counters, config)
                 } else {
-                    format_line(line, &config.spacing)
+                    format_line(line, config)
                 };
                 ret.push(formatted);
-                // Must be an empty line after a header (if enabled)
-                if config.headings.blank_line_after {
-                    ret.push(String::new());
+                // Must be empty lines after a header (if enabled)
+                if config.headings.blank_line_after && config.headings.blank_lines_after > 0 {
+                    ret.extend(std::iter::repeat_n(String::new(), config.headings.blank_lines_after));
                     cur_state = LineState::Empty;
                 }
             }
             LineState::List => {
                 if config.formatting.blank_lines
+                    && config.blank_lines.before_lists
                     && prev_line_state != LineState::List
                     && prev_line_state != LineState::Empty
+                    && !below_heading
                 {
                     // Don't add blank line if previous line is indented content (part of list)
                     if !(prev_line_state == LineState::Normal && prev_line.starts_with(' ')) {
                         ret.push(String::new());
                     }
                 }
-                ret.push(format_line(line, &config.spacing));
+                ret.push(format_line(line, config));
             }
         }
 
@@ -743,49 +2436,180 @@ fn format_lines(lines: Vec<&str>, config: &MdFormatConfig) -> Vec<String> {
     ret
 }
 
-fn format_line(line: &str, config: &SpacingOptions) -> String {
+/// Whether the line is a kramdown block attribute list ({: .class #id}) or a
+/// lone attribute block, applying to the element above it
+fn is_block_attribute(line: &str) -> bool {
+    RE_BLOCK_ATTRIBUTE.is_match(line).unwrap_or(false)
+}
+
+/// Whether a blank line must follow a block ending with a line in this state
+/// (if enabled)
+fn blank_line_after(state: &LineState, config: &MdFormatConfig) -> bool {
+    let options = &config.blank_lines;
+    config.formatting.blank_lines
+        && match state {
+            LineState::Table => options.after_tables,
+            LineState::CodeEnd => options.after_code,
+            LineState::Blockquote => options.after_blockquotes,
+            LineState::List => options.after_lists,
+            LineState::Block => true,
+            _ => false,
+        }
+}
+
+fn format_line(line: &str, config: &MdFormatConfig) -> String {
     format_text(line, config)
 }
 
-fn format_text(text: &str, config: &SpacingOptions) -> String {
+/// Replace every match of `re` with a unique `\x00{tag}{i}\x00` placeholder
+///
+/// Returns the replaced spans, to be put back with `restore_spans`.
+fn protect_spans(text: &mut String, re: &Regex, tag: &str) -> Vec<String> {
+    let mut spans: Vec<(usize, String)> = Vec::new();
+    for m in re.find_iter(text) {
+        let m = m.unwrap();
+        spans.push((m.start(), m.as_str().to_string()));
+    }
+    // Replace from right to left so earlier positions stay valid
+    for (i, (pos, span)) in spans.iter().enumerate().rev() {
+        text.replace_range(*pos..*pos + span.len(), &format!("\x00{}{}\x00", tag, i));
+    }
+    spans.into_iter().map(|(_, span)| span).collect()
+}
+
+/// Put back the spans replaced by `protect_spans`
+fn restore_spans(text: &mut String, spans: &[String], tag: &str) {
+    for (i, span) in spans.iter().enumerate().rev() {
+        *text = text.replace(&format!("\x00{}{}\x00", tag, i), span);
+    }
+}
+
+fn format_text(text: &str, config: &MdFormatConfig) -> String {
     let mut text = text.to_string();
 
+    // Inline syntax of the dialect (MDX expressions, R Markdown inline code) is opaque
+    let foreign = match dialects::inline_pattern(config.formatting.dialect) {
+        Some(re) => protect_spans(&mut text, re, "MDFOREIGN"),
+        None => Vec::new(),
+    };
+
+    // CriticMarkup review annotations are kept exactly as written
+    let critic = protect_spans(&mut text, &inline::RE_CRITIC_MARKUP, "MDCRITIC");
+
     // Phase 1: Protect inline code spans so CJK-ASCII spacing skips their content.
     // We replace each code span with a unique placeholder, then restore them
     // before the code-span-surrounding spacing pass runs.
-    let re_code = Regex::new(r"`[^`]+`").unwrap();
-    let mut codes: Vec<(usize, String)> = Vec::new();
-    for m in re_code.find_iter(&text) {
-        let m = m.unwrap();
-        codes.push((m.start(), m.as_str().to_string()));
+    let codes = protect_spans(&mut text, &RE_CODE_SPAN_CONTENT, "MDCODE");
+    // Inline math is opaque to every inline rule
+    let math = protect_spans(&mut text, &inline::RE_MATH, "MDMATH");
+    // Attribute blocks ({#custom-id}, [span]{.class}) are kept untouched
+    let attributes = protect_spans(&mut text, &RE_ATTRIBUTE_BLOCK, "MDATTR");
+    // So are image embeds, sizes and attribute lists
+    let media = protect_spans(&mut text, &inline::RE_MEDIA_ATTRIBUTE, "MDMEDIA");
+    // Emoji shortcodes are never split by the spacing passes
+    if config.emoji.style == emoji::EmojiStyle::Shortcode {
+        text = emoji::emoji_to_shortcodes(&text);
     }
-    // Replace from right to left so earlier positions stay valid
-    codes.sort_by(|a, b| b.0.cmp(&a.0));
-    for (i, (pos, code)) in codes.iter().enumerate() {
-        let placeholder = format!("\x00MDCODE{}\x00", i);
-        text.replace_range(*pos..*pos + code.len(), &placeholder);
+    let mut shortcodes = protect_spans(&mut text, &emoji::RE_SHORTCODE, "MDEMOJI");
+    for shortcode in shortcodes.iter_mut() {
+        if config.emoji.style == emoji::EmojiStyle::Unicode {
+            *shortcode = emoji::shortcode_to_emoji(shortcode);
+        } else if config.emoji.normalize_aliases {
+            *shortcode = emoji::canonical_shortcode(shortcode);
+        }
+    }
+
+    // Pandoc superscripts and subscripts must stay free of spaces
+    let scripts = if config.formatting.pandoc {
+        protect_spans(&mut text, &inline::RE_SUB_SUPERSCRIPT, "MDSCRIPT")
+    } else {
+        Vec::new()
+    };
+
+    // Unicode NFC normalization (based on config)
+    if config.formatting.unicode_nfc {
+        text = text.nfc().collect();
+    }
+
+    // Full-width letters and digits (based on config)
+    if config.formatting.halfwidth_alphanumerics {
+        text = inline::halfwidth_alphanumerics(&text);
+    }
+
+    // Bare URLs (based on config)
+    if config.links.bare_urls == links::BareUrls::Autolink {
+        text = links::autolink_bare_urls(&text);
+    }
+    text = links::convert_autolinks(&text, config.links.autolinks);
+
+    // Strong emphasis markers (based on config)
+    if config.formatting.strong_marker != "preserve" {
+        text = inline::normalize_strong(&text, &config.formatting.strong_marker);
+    }
+
+    // Typographic punctuation (based on config)
+    match config.punctuation.mode {
+        PunctuationMode::Preserve => {}
+        PunctuationMode::Smart => text = inline::smart_punctuation(&text, config.punctuation.cjk_quotes),
+        PunctuationMode::Straight => text = inline::straight_punctuation(&text),
+    }
+    text = inline::normalize_dashes(&text, config.punctuation.dashes);
+    text = inline::normalize_ellipsis(&text, config.punctuation.ellipsis);
+    if config.punctuation.cjk_width != CjkPunctuation::Preserve {
+        text = inline::fullwidth_punctuation(&text, config.punctuation.cjk_width);
+    }
+    if config.punctuation.cjk_max_repeat > 0 {
+        text = inline::collapse_repeated_punctuation(&text, config.punctuation.cjk_max_repeat);
     }
 
     // CJK-ASCII spacing (based on config)
-    if config.cjk_ascii {
-        text = add_spaces_between_cjk_ascii(&text);
-        // sometimes we need to perform this twice to make it stable
-        text = add_spaces_between_cjk_ascii(&text);
+    if config.spacing.cjk_ascii {
+        let terms = inline::protect_terms(&mut text, &config.spacing.exceptions, "MDTERM");
+        // Link destinations, autolinks and HTML attributes are never spaced
+        text = inline::outside_urls(&text, |text| {
+            let mut text = inline::join_units(text, &config.spacing);
+            let amounts = match config.spacing.amounts {
+                AmountSpacing::Space => Vec::new(),
+                AmountSpacing::Attach => inline::protect_amounts(&mut text, &config.spacing, "MDAMOUNT"),
+            };
+            let mut text = add_spaces_between_cjk_ascii(&text, &config.spacing);
+            restore_spans(&mut text, &amounts, "MDAMOUNT");
+            // Inline spans are spaced by their content, outside the markers
+            let text = inline::space_around_strikethrough(&text, &config.spacing);
+            let text = inline::space_around_highlight(&text, &config.spacing);
+            let text = inline::space_around_emphasis(&text, &config.spacing);
+            let text = inline::space_around_links(&text, &config.spacing);
+            let text = inline::space_around_math(&text, &config.spacing);
+            inline::space_around_critic_markup(&text, &critic, &config.spacing)
+        });
+        text = inline::trim_around_fullwidth_punctuation(&text);
+        restore_spans(&mut text, &terms, "MDTERM");
+    } else if config.spacing.strip_cjk_ascii {
+        text = inline::outside_urls(&text, |text| inline::strip_cjk_ascii_spaces(text, &config.spacing));
+    }
+    restore_spans(&mut text, &scripts, "MDSCRIPT");
+    if config.formatting.pandoc && config.spacing.cjk_ascii {
+        text = inline::outside_urls(&text, |text| inline::space_around_sub_superscript(text, &config.spacing));
     }
 
     // Phase 2: Restore inline code spans so the code-span-surrounding pass
     // can see the backticks and add spaces around them.
-    for (i, (_, code)) in codes.iter().enumerate().rev() {
-        text = text.replace(&format!("\x00MDCODE{}\x00", i), code.as_str());
-    }
+    restore_spans(&mut text, &codes, "MDCODE");
 
     // Spacing around code spans (based on config)
-    if config.around_code_spans {
+    if config.spacing.around_code_spans {
         text = add_space_around_code_spans(&text);
         // sometimes we need to perform this twice to make it stable
         text = add_space_around_code_spans(&text);
     }
 
+    restore_spans(&mut text, &math, "MDMATH");
+    restore_spans(&mut text, &shortcodes, "MDEMOJI");
+    restore_spans(&mut text, &media, "MDMEDIA");
+    restore_spans(&mut text, &attributes, "MDATTR");
+    restore_spans(&mut text, &critic, "MDCRITIC");
+    restore_spans(&mut text, &foreign, "MDFOREIGN");
+
     text
 }
 
@@ -802,9 +2626,21 @@ fn format_lists(lines: &[String], config: &ListOptions) -> Vec<String> {
 
     let mut result = Vec::new();
     let mut list_stack: Vec<ListContext> = Vec::new();
+    let mut in_code = false;
+    let tab_width = list_tab_width(lines, config);
 
     for line in lines {
-        if let Some(caps) = RE_LIST_ITEM.captures(line).unwrap() {
+        // Fenced code content is never list content
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if in_code {
+            result.push(line.clone());
+            continue;
+        }
+
+        // Tabs count up to the next tab stop
+        let expanded = expand_leading_tabs(line, tab_width);
+        if let Some(caps) = RE_LIST_ITEM.captures(&expanded).unwrap() {
             let indent = caps.get(1).unwrap().as_str().len();
             let content = caps.get(4).unwrap().as_str();
 
@@ -862,11 +2698,10 @@ fn format_lists(lines: &[String], config: &ListOptions) -> Vec<String> {
 
             // Construct the new formatted line
             let current_context = list_stack.last().unwrap();
-            let prefix_indent = " ".repeat(if list_stack.len() > 1 {
-                config.indent * (list_stack.len() - 1)
-            } else {
-                0
-            });
+            let prefix_indent = match config.indent_style {
+                IndentStyle::Spaces => " ".repeat(config.indent * (list_stack.len() - 1)),
+                IndentStyle::Tabs => "\t".repeat(list_stack.len() - 1),
+            };
 
             let new_line = match current_context.list_type {
                 ListType::Unordered => format!("{}{} {}", prefix_indent, config.unordered_marker, content),
@@ -889,7 +2724,11 @@ fn format_lists(lines: &[String], config: &ListOptions) -> Vec<String> {
             } else if line.starts_with(' ') || line.starts_with('\t') {
                 // Indented content: part of the list item (code blocks, continued text, etc.)
                 // Keep list_stack intact
-                result.push(line.clone());
+                if config.indent_style == IndentStyle::Spaces && !list_stack.is_empty() {
+                    result.push(expanded);
+                } else {
+                    result.push(line.clone());
+                }
             } else {
                 // Real non-list content (text, heading, code, etc.): end the list
                 list_stack.clear();
@@ -901,6 +2740,40 @@ fn format_lists(lines: &[String], config: &ListOptions) -> Vec<String> {
     result
 }
 
+/// Width of the tabs in list indentation: when list items are indented with
+/// both tabs and spaces, a tab counts as one level of the space indentation
+/// (the smallest one), so both give the same depth
+fn list_tab_width(lines: &[String], config: &ListOptions) -> usize {
+    let mut tabs = false;
+    let mut space_indent = config.tab_width;
+    let mut in_code = false;
+    for line in lines {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        if in_code || !RE_LIST_ITEM.is_match(line).unwrap_or(false) {
+            continue;
+        }
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        if indent.contains('\t') {
+            tabs = true;
+        } else if !indent.is_empty() {
+            space_indent = space_indent.min(indent.len());
+        }
+    }
+    if tabs { space_indent } else { config.tab_width }
+}
+
+/// Replace the tabs of the leading whitespace with spaces up to the next tab stop
+fn expand_leading_tabs(line: &str, tab_width: usize) -> String {
+    let content = line.trim_start_matches([' ', '\t']);
+    let mut width = 0;
+    for c in line[..line.len() - content.len()].chars() {
+        width = if c == '\t' { (width / tab_width + 1) * tab_width } else { width + 1 };
+    }
+    format!("{}{}", " ".repeat(width), content)
+}
+
 lazy_static! {
     // Regular expression to capture list lines:
     // 1: Indentation (leading spaces)
@@ -909,20 +2782,44 @@ lazy_static! {
     // 4: List item content
     static ref RE_LIST_ITEM: Regex =
         Regex::new(r"^(\s*)(?:([*+-])|(\d+)\.)\s+(.*)").unwrap();
-    static ref RE_CJK: Regex =
-        Regex::new(r"(\p{sc=Han})([a-zA-Z0-9])|([a-zA-Z0-9])(\p{sc=Han})").unwrap();
+    // Thematic break: 3 or more '-', '*' or '_', optionally separated by spaces
+    static ref RE_THEMATIC_BREAK: Regex = Regex::new(r"^ {0,3}([-*_])[ \t]*(?:\1[ \t]*){2,}$").unwrap();
+    static ref RE_THEMATIC_BREAK_FORM: Regex = Regex::new(r"^([-*_])\1{2,}$").unwrap();
+    // Existing heading numbering prefix: "1 ", "1.2 ", "1.2.3. "
+    static ref RE_HEADING_NUMBERING: Regex = Regex::new(r"^\d+(?:\.\d+)*\.?(?:\s+|$)").unwrap();
+    // LaTeX environment opening a line: 1: name (align, equation*...)
+    static ref RE_LATEX_BEGIN: Regex = Regex::new(r"^\s*\\begin\{([A-Za-z]+\*?)\}").unwrap();
+    // Corner of an ASCII-art box: +---+
+    static ref RE_ASCII_BOX: Regex = Regex::new(r"[+|]-{3,}[+|]").unwrap();
+    // Run of spaces inside a line, not after sentence punctuation
+    static ref RE_ALIGNED_COLUMNS: Regex = Regex::new(r"[^\s.!?:;,] {2,}\S").unwrap();
+    // MkDocs-Material content tab marker: === "Title" (also ===! and ===+)
+    static ref RE_CONTENT_TAB: Regex = Regex::new(r#"^===[!+]? +".*"$"#).unwrap();
     static ref RE_CODE_SPAN: Regex = Regex::new(r"([^`\s]?)(`[^`]*`)([^`\s]?)").unwrap();
+    static ref RE_CODE_SPAN_CONTENT: Regex = Regex::new(r"`[^`]+`").unwrap();
+    // Inline HTML tag ending or starting the text
+    static ref RE_HTML_TAG_END: Regex = Regex::new(r"</?[a-zA-Z][^<>]*>$").unwrap();
+    static ref RE_HTML_TAG_START: Regex = Regex::new(r"^</?[a-zA-Z][^<>]*>").unwrap();
+    // Attribute block: {#custom-id} or {.class} (may also hold key=value pairs), or any
+    // attributes right after a span, link or image: [text]{key=value}, *em*{.red}
+    static ref RE_ATTRIBUTE_BLOCK: Regex =
+        Regex::new(r"\{[#.][^{}\n]*\}|(?<=[\])*_`\x00])\{[^{}\n]*\}").unwrap();
+    // Block attribute list on its own line: {: .class #id}, {.class}
+    static ref RE_BLOCK_ATTRIBUTE: Regex = Regex::new(r"^\{[:.#][^{}]*\}\s*$").unwrap();
 }
-fn add_spaces_between_cjk_ascii(text: &str) -> String {
-    RE_CJK
-        .replace_all(text, |caps: &Captures| {
-            if let Some(cjk) = caps.get(1) {
-                format!("{} {}", cjk.as_str(), &caps[2])
-            } else {
-                format!("{} {}", caps.get(3).unwrap().as_str(), &caps[4])
-            }
-        })
-        .to_string()
+fn add_spaces_between_cjk_ascii(text: &str, spacing: &SpacingOptions) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut prev = None;
+    for c in text.chars() {
+        if let Some(prev) = prev
+            && inline::needs_space(prev, c, &spacing.cjk_scripts)
+        {
+            result.push(spacing.cjk_separator.as_char());
+        }
+        result.push(c);
+        prev = Some(c);
+    }
+    result
 }
 
 fn add_space_around_code_spans(text: &str) -> String {
@@ -932,15 +2829,27 @@ fn add_space_around_code_spans(text: &str) -> String {
             let code = caps.get(2).unwrap().as_str();
             let after = caps.get(3).unwrap().as_str();
             debug!("before: [{}], code: [{}], after: [{}]", before, code, after);
-            if before.is_empty() && after.is_empty() {
-                return format!("{}", code);
-            } else if before.is_empty() {
-                return format!("{} {}", code, after);
-            } else if after.is_empty() {
-                return format!("{} {}", before, code);
+            // Full-width punctuation already separates the code span
+            let space = |text: &str| match text.chars().next() {
+                Some(c) if !inline::is_fullwidth_punctuation(c) => " ",
+                _ => "",
+            };
+            // So do inline HTML tags around it (<kbd>`Ctrl`</kbd>), and its
+            // attribute block (`code`{.python}) stays attached
+            let (start, end) = (caps.get(2).unwrap().start(), caps.get(2).unwrap().end());
+            let before_space = if RE_HTML_TAG_END.is_match(&text[..start]).unwrap_or(false) {
+                ""
             } else {
-                return format!("{} {} {}", before, code, after);
-            }
+                space(before)
+            };
+            let after_space = if RE_HTML_TAG_START.is_match(&text[end..]).unwrap_or(false)
+                || text[end..].starts_with("\x00MDATTR")
+            {
+                ""
+            } else {
+                space(after)
+            };
+            format!("{}{}{}{}{}", before, before_space, code, after_space, after)
         })
         .to_string()
 }
@@ -987,25 +2896,25 @@ mod tests {
         let fmt_md = format_markdown("# 123你好2谢谢hello`你好call function()`$text谢谢$谢谢", &MdFormatConfig::default());
         assert_eq!(
             fmt_md,
-            "# 123 你好 2 谢谢 hello `你好call function()` $text 谢谢$谢谢\n"
+            "# 123 你好 2 谢谢 hello `你好call function()` $text谢谢$ 谢谢\n"
         );
 
         let fmt_md = format_markdown("123你好2谢谢hello`你好call function()`$text谢谢$谢谢", &MdFormatConfig::default());
         assert_eq!(
             fmt_md,
-            "123 你好 2 谢谢 hello `你好call function()` $text 谢谢$谢谢\n"
+            "123 你好 2 谢谢 hello `你好call function()` $text谢谢$ 谢谢\n"
         );
 
         let fmt_md = format_markdown("- 123你好2谢谢hello`你好call function()`$text谢谢$谢谢", &MdFormatConfig::default());
         assert_eq!(
             fmt_md,
-            "- 123 你好 2 谢谢 hello `你好call function()` $text 谢谢$谢谢\n"
+            "- 123 你好 2 谢谢 hello `你好call function()` $text谢谢$ 谢谢\n"
         );
 
         let fmt_md = format_markdown("1. 123你好2谢谢hello`你好call function()`$text谢谢$谢谢", &MdFormatConfig::default());
         assert_eq!(
             fmt_md,
-            "1. 123 你好 2 谢谢 hello `你好call function()` $text 谢谢$谢谢\n"
+            "1. 123 你好 2 谢谢 hello `你好call function()` $text谢谢$ 谢谢\n"
         );
     }
 
@@ -1353,91 +3262,2126 @@ Some paragraph text.
         assert_eq!(format_markdown(input5, &MdFormatConfig::default()), expected5);
     }
 
-    // ===== Added: FormattingOptions tests (6 tests) =====
     #[test]
-    fn test_disable_format_tables() {
-        // Test disabling table formatting
-        let mut config = MdFormatConfig::default();
-        config.formatting.format_tables = false;
+    fn test_content_tabs() {
+        // Tab bodies keep their 4-space indentation and fenced code is left intact
+        let input = "intro\n=== \"Python\"\n    ```py\n    * not a list\n    ```\n=== \"中文Tab\"\n    说明text\n    * item\nafter";
+        let expected = "intro\n\n=== \"Python\"\n\n    ```py\n    * not a list\n    ```\n\n=== \"中文Tab\"\n\n    说明 text\n\n    - item\n\nafter\n";
+        assert_eq!(format_markdown(input, &MdFormatConfig::default()), expected);
 
-        let input = "|a|b|\n|---|---|\n| column 1 | column 2    |";
-        let output = format_markdown(input, &config);
+        // Blank lines inside the tab body are preserved
+        let input2 = "=== \"A\"\n\n    para 1\n\n    para 2\n\n=== \"B\"\n\n    para 3";
+        let expected2 = "=== \"A\"\n\n    para 1\n\n    para 2\n\n=== \"B\"\n\n    para 3\n";
+        assert_eq!(format_markdown(input2, &MdFormatConfig::default()), expected2);
 
-        // Table should not be formatted
-        assert_eq!(output, "|a|b|\n|---|---|\n| column 1 | column 2    |\n");
+        // Tab markers inside code blocks are not touched
+        let input3 = "```\n=== \"A\"\n    code\n```";
+        assert_eq!(format_markdown(input3, &MdFormatConfig::default()), "```\n=== \"A\"\n    code\n```\n");
     }
 
     #[test]
-    fn test_disable_format_lists() {
-        // Test disabling list formatting
+    fn test_protected_markers() {
+        // Snippet and include markers are never spaced
+        let input = "中文text\n--8<-- \"docs/中文abc.md\"\n{% include \"中文abc.html\" %}\n中文text";
+        let expected = "中文 text\n--8<-- \"docs/中文abc.md\"\n{% include \"中文abc.html\" %}\n中文 text\n";
+        assert_eq!(format_markdown(input, &MdFormatConfig::default()), expected);
+
+        // Custom patterns
         let mut config = MdFormatConfig::default();
-        config.formatting.format_lists = false;
+        config.protect.patterns = vec![r"^@@".to_string()];
+        let output = format_markdown("@@中文abc\n--8<-- \"中文abc.md\"", &config);
+        assert_eq!(output, "@@中文abc\n--8<-- \"中文 abc.md\"\n");
+    }
 
-        let input = "1. first\n5. second\n+ item";
-        let output = format_markdown(input, &config);
+    #[test]
+    fn test_validate_invalid_protect_pattern() {
+        let mut config = MdFormatConfig::default();
+        config.protect.patterns = vec!["(unclosed".to_string()];
 
-        // List numbers and markers should not be modified (not adding blank lines between list items is correct behavior)
-        assert_eq!(output, "1. first\n5. second\n+ item\n");
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("protect.patterns"));
     }
 
     #[test]
-    fn test_disable_cjk_spacing() {
-        // Test disabling CJK-ASCII spacing
-        let mut config = MdFormatConfig::default();
-        config.spacing.cjk_ascii = false;
+    fn test_thematic_break() {
+        // Different forms are normalized and surrounded by blank lines
+        let input = "para\n***\n- - -\ntext\n_____\n* * *";
+        let expected = "para\n\n---\n\n---\n\ntext\n\n---\n\n---\n";
+        assert_eq!(format_markdown(input, &MdFormatConfig::default()), expected);
 
-        let input = "123你好world";
-        let output = format_markdown(input, &config);
+        // A dash line right after a paragraph is a setext heading underline
+        let input2 = "Title\n---\ntext";
+        assert_eq!(format_markdown(input2, &MdFormatConfig::default()), "Title\n---\ntext\n");
 
-        // Should not add spaces
-        assert_eq!(output, "123你好world\n");
+        // Custom canonical form
+        let mut config = MdFormatConfig::default();
+        config.formatting.thematic_break = "***".to_string();
+        assert_eq!(format_markdown("a\n\n---\n\nb", &config), "a\n\n***\n\nb\n");
     }
 
     #[test]
-    fn test_disable_code_span_spacing() {
-        // Test disabling spacing around code spans
-        let mut config = MdFormatConfig::default();
-        config.spacing.around_code_spans = false;
+    fn test_front_matter_untouched() {
+        let input = "---\ntitle: 中文title\ntags: [a, b]\n---\n# 标题Title";
+        let expected = "---\ntitle: 中文title\ntags: [a, b]\n---\n\n# 标题 Title\n";
+        assert_eq!(format_markdown(input, &MdFormatConfig::default()), expected);
+    }
 
-        let input = "text`code`text";
-        let output = format_markdown(input, &config);
+    #[test]
+    fn test_validate_invalid_thematic_break() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.thematic_break = "-*-".to_string();
 
-        // Should not add spaces
-        assert_eq!(output, "text`code`text\n");
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("thematic_break"));
     }
 
     #[test]
-    fn test_disable_blank_lines() {
-        // Test disabling blank lines between elements
+    fn test_validate_invalid_bold_heading_level() {
         let mut config = MdFormatConfig::default();
-        config.formatting.blank_lines = false;
-
-        let input = "# Title\n## Subtitle\ntext\n```\ncode\n```\nmore text";
-        let output = format_markdown(input, &config);
+        config.headings.bold_heading_level = 0;
 
-        // Should not add blank lines (blank line after heading is still controlled by heading.blank_line_after)
-        assert!(output.contains("```\ncode\n```\nmore text"));
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("bold_heading_level"));
     }
 
     #[test]
-    fn test_disable_merge_blank_lines() {
-        // Test disabling merging consecutive blank lines
+    fn test_validate_invalid_table_line_break() {
         let mut config = MdFormatConfig::default();
-        config.formatting.merge_blank_lines = false;
-
-        let input = "line1\n\n\n\nline2";
-        let output = format_markdown(input, &config);
+        config.tables.line_break = "<br >".to_string();
 
-        // Consecutive blank lines should be preserved (input has 3 blank lines, output should also have 3)
-        assert_eq!(output, "line1\n\n\n\nline2\n");
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("tables.line_break"));
     }
 
-    // ===== Added: ListOptions tests (4 tests) =====
     #[test]
-    fn test_custom_list_indent_4() {
-        // Test custom indentation: 4 spaces
+    fn test_validate_invalid_strong_marker() {
         let mut config = MdFormatConfig::default();
-        config.lists.indent = 4;
+        config.formatting.strong_marker = "*".to_string();
+
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("strong_marker"));
+    }
+
+    #[test]
+    fn test_skipped_heading_levels() {
+        let input = "# H1\n### H3\n#### H4\n## H2\n```\n### code\n```";
+
+        // Ignored by default
+        let (output, diagnostics) = format_document(input, &MdFormatConfig::default());
+        assert!(output.starts_with("# H1\n\n### H3\n"));
+        assert!(diagnostics.is_empty());
+
+        // Reported with line numbers
+        let mut config = MdFormatConfig::default();
+        config.headings.skipped_levels = RuleMode::Warn;
+        let (output, diagnostics) = format_document(&format!("\n\n{}", input), &config);
+        assert!(output.starts_with("# H1\n\n### H3\n"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 4);
+        assert_eq!(diagnostics[0].rule, "heading-increment");
+
+        // Fixed by closing the gaps
+        config.headings.skipped_levels = RuleMode::Fix;
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, "# H1\n\n## H3\n\n### H4\n\n## H2\n\n```\n### code\n```\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].fixed);
+    }
+
+    #[test]
+    fn test_toc_generation() {
+        // The content between the markers is regenerated
+        let input = "# Guide\n<!-- toc -->\n- [Old](#old)\n<!-- tocstop -->\n## Getting Started\n### Install it!\n```\n## not a heading\n```\n## 中文标题API\n#### Deep";
+        let expected = "# Guide\n\n<!-- toc -->\n\n- [Guide](#guide)\n  - [Getting Started](#getting-started)\n    - [Install it!](#install-it)\n  - [中文标题 API](#中文标题-api)\n    - [Deep](#deep)\n\n<!-- tocstop -->\n\n## Getting Started\n\n### Install it!\n\n```\n## not a heading\n```\n\n## 中文标题 API\n\n#### Deep\n";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(output, expected);
+
+        // Formatting is stable
+        assert_eq!(format_markdown(&output, &MdFormatConfig::default()), expected);
+
+        // A missing stop marker is added
+        let output = format_markdown("<!-- toc -->\n# A\n## B", &MdFormatConfig::default());
+        assert_eq!(output, "<!-- toc -->\n\n- [A](#a)\n  - [B](#b)\n\n<!-- tocstop -->\n\n# A\n\n## B\n");
+    }
+
+    #[test]
+    fn test_toc_options() {
+        let mut config = MdFormatConfig::default();
+        config.toc.min_level = 2;
+        config.toc.max_level = 3;
+        config.toc.ordered = true;
+
+        let input = "# Title\n<!-- toc -->\n<!-- tocstop -->\n## A\n### A.1\n#### A.1.1\n### A.2\n## Changelog <!-- toc-ignore -->\n## B";
+        let output = format_markdown(input, &config);
+        assert!(output.contains("<!-- toc -->\n\n1. [A](#a)\n   1. [A.1](#a1)\n   2. [A.2](#a2)\n2. [B](#b)\n\n<!-- tocstop -->\n"));
+        assert!(output.contains("## Changelog <!-- toc-ignore -->\n"));
+    }
+
+    #[test]
+    fn test_toc_nested_ordered() {
+        // Nested ordered entries are indented past their parent's "1. " marker
+        let mut config = MdFormatConfig::default();
+        config.toc.ordered = true;
+
+        let input = "<!-- toc -->\n<!-- tocstop -->\n# A\n## B\n### C\n## D\n# E";
+        let expected = concat!(
+            "<!-- toc -->\n\n",
+            "1. [A](#a)\n",
+            "   1. [B](#b)\n",
+            "      1. [C](#c)\n",
+            "   2. [D](#d)\n",
+            "2. [E](#e)\n",
+            "\n<!-- tocstop -->\n\n",
+            "# A\n\n## B\n\n### C\n\n## D\n\n# E\n",
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(output, expected);
+        assert_eq!(format_markdown(&output, &config), expected);
+    }
+
+    #[test]
+    fn test_validate_invalid_toc_levels() {
+        let mut config = MdFormatConfig::default();
+        config.toc.min_level = 4;
+        config.toc.max_level = 2;
+
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("toc.min_level"));
+    }
+
+    #[test]
+    fn test_toc_duplicate_anchors() {
+        let input = "<!-- toc -->\n<!-- tocstop -->\n## Usage\n### Example\n## API\n### Example\n## `foo_bar()` *API*";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert!(output.contains("- [Usage](#usage)\n  - [Example](#example)\n- [API](#api)\n  - [Example](#example-1)\n- [`foo_bar()` *API*](#foo_bar-api)\n"));
+    }
+
+    #[test]
+    fn test_check_anchor_links() {
+        let input = "# Getting Started\n## 中文API\nSee [start](#getting-started), [api](#中文-api), [old](#GettingStarted) and [gone](#removed).\n\n[ref]: #getting_started";
+
+        let mut config = MdFormatConfig::default();
+        config.anchors.check_links = RuleMode::Warn;
+        let (_, diagnostics) = format_document(input, &config);
+        let lines = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (3, "link to missing anchor '#GettingStarted'"),
+                (3, "link to missing anchor '#removed'"),
+                (5, "link to missing anchor '#getting_started'"),
+            ]
+        );
+
+        // Stale anchors are pointed to the matching heading, unknown ones are reported
+        config.anchors.check_links = RuleMode::Fix;
+        let (output, diagnostics) = format_document(input, &config);
+        assert!(output.contains("[old](#getting-started) and [gone](#removed)"));
+        assert!(output.contains("[ref]: #getting-started"));
+        let unfixed = diagnostics.iter().filter(|d| !d.fixed).collect::<Vec<_>>();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(unfixed.len(), 1);
+        assert_eq!(unfixed[0].message, "link to missing anchor '#removed'");
+    }
+
+    #[test]
+    fn test_toc_anchor_style() {
+        let mut config = MdFormatConfig::default();
+        config.anchors.style = AnchorStyle::Pandoc;
+
+        let output = format_markdown("<!-- toc -->\n# 1 Intro\n# Intro", &config);
+        assert!(output.contains("- [1 Intro](#intro)\n- [Intro](#intro-1)\n"));
+    }
+
+    #[test]
+    fn test_custom_heading_ids() {
+        // Attribute braces are not spaced, custom IDs are used as anchors
+        let input = "<!-- toc -->\n## 中文Title {#中文id}\n## Usage {#usage-guide .big}\n## Usage";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert!(output.contains("- [中文 Title](#中文id)\n- [Usage](#usage-guide)\n- [Usage](#usage)\n"));
+        assert!(output.contains("## 中文 Title {#中文id}\n"));
+        assert!(output.contains("## Usage {#usage-guide .big}\n"));
+
+        // IDs are generated for headings without one
+        let mut config = MdFormatConfig::default();
+        config.anchors.generate_ids = true;
+        let output = format_markdown("# Intro {#start}\n## Getting Started\n## Getting Started", &config);
+        assert_eq!(output, "# Intro {#start}\n\n## Getting Started {#getting-started}\n\n## Getting Started {#getting-started-1}\n");
+        assert_eq!(format_markdown(&output, &config), output);
+    }
+
+    #[test]
+    fn test_align_table_display_width() {
+        // CJK characters and emoji take two columns, the blockquote prefix is kept
+        let input = "|名称|说明|\n|:-|-:|\n|a|中文|\n|✅|x|\n\n> | a | b |\n> |---|:-:|\n> |中|y|";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(
+            output,
+            concat!(
+                "| 名称 | 说明 |\n",
+                "| :--- | ---: |\n",
+                "| a    | 中文 |\n",
+                "| ✅   |    x |\n",
+                "\n",
+                "> | a   |   b   |\n",
+                "> | --- | :---: |\n",
+                "> | 中  |   y   |\n"
+            )
+        );
+
+        let mut config = MdFormatConfig::default();
+        config.tables.cell_width = tables::CellWidth::Chars;
+        let output = format_markdown("|名称|b|\n|---|---|\n|a|b|", &config);
+        assert_eq!(output, "| 名称  | b   |\n| --- | --- |\n| a   | b   |\n");
+    }
+
+    #[test]
+    fn test_table_alignment_colons() {
+        // Alignment colons are kept and control the padding of header and data cells
+        let input = "|none|left|center|right|\n|-|:-|:-:|-:|\n|a|b|c|d|\n|long cell|x|wide text|1.5|";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(
+            output,
+            concat!(
+                "| none      | left |  center   | right |\n",
+                "| --------- | :--- | :-------: | ----: |\n",
+                "| a         | b    |     c     |     d |\n",
+                "| long cell | x    | wide text |   1.5 |\n"
+            )
+        );
+        // Formatting is stable
+        assert_eq!(format_markdown(&output, &MdFormatConfig::default()), output);
+    }
+
+    #[test]
+    fn test_table_max_column_width() {
+        let mut config = MdFormatConfig::default();
+        config.tables.max_column_width = 12;
+
+        // Wider cells are left unpadded
+        let input = "|key|description|\n|---|---|\n|a|short|\n|b|a much longer description|";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "| key | description |\n",
+                "| --- | ----------- |\n",
+                "| a   | short       |\n",
+                "| b   | a much longer description |\n"
+            )
+        );
+
+        // Or wrapped with <br>, keeping code spans and existing line breaks
+        config.tables.wrap_cells = true;
+        let input = "|key|description|\n|---|---|\n|a|short|\n|b|a much longer description|\n|c|use `cargo build --release`<br/>then run|\n|d|这是一段很长的中文说明文字|";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "| key | description  |\n",
+                "| --- | ------------ |\n",
+                "| a   | short        |\n",
+                "| b   | a much<br>longer<br>description  |\n",
+                "| c   | use<br>`cargo build --release`<br/>then run |\n",
+                "| d   | 这是一段很长<br>的中文说明文<br>字 |\n"
+            )
+        );
+        // Formatting is stable
+        assert_eq!(format_markdown(&output, &config), output);
+    }
+
+    #[test]
+    fn test_table_wrapped_cells_padded() {
+        // Wrapped cells are padded to the column width by their widest line
+        let mut config = MdFormatConfig::default();
+        config.tables.max_column_width = 8;
+        config.tables.wrap_cells = true;
+
+        let input = "|k|desc|\n|---|---|\n|x|abcdefg|\n|y|tiny words|";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "| k   | desc    |\n",
+                "| --- | ------- |\n",
+                "| x   | abcdefg |\n",
+                "| y   | tiny<br>words   |\n"
+            )
+        );
+        // The last line of the wrapped cell ends where the delimiter row does
+        let lines = output.lines().collect::<Vec<_>>();
+        let delimiter = lines[1].rsplit("| ").next().unwrap();
+        assert_eq!(lines[3].rsplit("<br>").next().unwrap().len(), delimiter.len());
+        assert_eq!(format_markdown(&output, &config), output);
+    }
+
+    #[test]
+    fn test_table_escaped_pipes() {
+        // Escaped pipes and pipes inside code spans don't split cells
+        let input = "|op|meaning|\n|---|---|\n|`a|b`|or|\n|\\||pipe \\| bar|\n|`\\|`|x \\|";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(
+            output,
+            concat!(
+                "| op    | meaning     |\n",
+                "| ----- | ----------- |\n",
+                "| `a|b` | or          |\n",
+                "| \\|    | pipe \\| bar |\n",
+                "| `\\|`  | x \\|        |\n"
+            )
+        );
+        assert_eq!(format_markdown(&output, &MdFormatConfig::default()), output);
+    }
+
+    #[test]
+    fn test_table_cell_inline_formatting() {
+        // Spacing rules run on each cell before the columns are measured
+        let input = "|a|b|\n|---|---|\n|中文abc|`x`|\n|`code`中|数字123|";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(
+            output,
+            concat!(
+                "| a         | b        |\n",
+                "| --------- | -------- |\n",
+                "| 中文 abc  | `x`      |\n",
+                "| `code` 中 | 数字 123 |\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_table_sort_directive() {
+        let config = MdFormatConfig::default();
+        let table = "|name|size|\n|---|---|\n|beta|1,200|\n|Alpha|30|\n|gamma|4|";
+
+        // Lexical sort, case-insensitive
+        let output = format_markdown(&format!("<!-- mdformat: sort-by=1 -->\n{}", table), &config);
+        assert_eq!(
+            output,
+            concat!(
+                "<!-- mdformat: sort-by=1 -->\n\n",
+                "| name  | size  |\n",
+                "| ----- | ----- |\n",
+                "| Alpha | 30    |\n",
+                "| beta  | 1,200 |\n",
+                "| gamma | 4     |\n"
+            )
+        );
+
+        // Numeric columns are detected, descending order is optional
+        let output = format_markdown(&format!("<!-- mdformat: sort-by=2:desc -->\n\n{}", table), &config);
+        let names = output.lines().skip(4).map(|line| &line[2..7]).collect::<Vec<_>>();
+        assert_eq!(names, vec!["beta ", "Alpha", "gamma"]);
+
+        // Forced lexical comparison of a numeric column
+        let output = format_markdown(&format!("<!-- mdformat: sort-by=2:text:desc -->\n{}", table), &config);
+        let names = output.lines().skip(4).map(|line| &line[2..7]).collect::<Vec<_>>();
+        assert_eq!(names, vec!["gamma", "Alpha", "beta "]);
+
+        // Tables without the directive keep their order
+        let output = format_markdown(&format!("text\n\n{}", table), &config);
+        assert!(output.contains("| beta  | 1,200 |\n| Alpha | 30    |\n"));
+    }
+
+    #[test]
+    fn test_table_ragged_rows() {
+        let mut config = MdFormatConfig::default();
+        config.tables.ragged_rows = RuleMode::Warn;
+
+        let input = "|a|b|c|\n|---|---|---|\n|1|2|\n|1|2|3|\n|1|2|3|4|";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, format_markdown(input, &MdFormatConfig::default()));
+        let lines = diagnostics.iter().map(|d| (d.line, d.fixed)).collect::<Vec<_>>();
+        assert_eq!(lines, vec![(3, false), (5, false)]);
+        assert!(diagnostics.iter().all(|d| d.rule == "table-ragged-row"));
+
+        // Short rows are padded, extra cells are only reported
+        config.tables.ragged_rows = RuleMode::Fix;
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "| a   | b   | c   |\n",
+                "| --- | --- | --- |\n",
+                "| 1   | 2   |     |\n",
+                "| 1   | 2   | 3   |\n",
+                "| 1   | 2   | 3   | 4 |\n"
+            )
+        );
+        let lines = diagnostics.iter().map(|d| (d.line, d.fixed)).collect::<Vec<_>>();
+        assert_eq!(lines, vec![(3, true), (5, false)]);
+    }
+
+    #[test]
+    fn test_table_cell_line_breaks() {
+        // Each line of a cell is formatted on its own, the tags are kept as written
+        let input = "|a|b|\n|---|---|\n|中<br>abc|中<br/>`x`|\n|a <BR /> b|c|";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(
+            output,
+            concat!(
+                "| a         | b          |\n",
+                "| --------- | ---------- |\n",
+                "| 中<br>abc | 中<br/>`x` |\n",
+                "| a<BR />b  | c          |\n"
+            )
+        );
+
+        // Or normalized to one style
+        let mut config = MdFormatConfig::default();
+        config.tables.line_break = "<br />".to_string();
+        let output = format_markdown(input, &config);
+        assert!(output.contains("| 中<br />abc | 中<br />`x` |\n| a<br />b    | c           |\n"));
+        // Line breaks outside tables are not touched
+        assert_eq!(format_markdown("a<br>b", &config), "a<br>b\n");
+    }
+
+    #[test]
+    fn test_convert_html_tables() {
+        let mut config = MdFormatConfig::default();
+        config.tables.convert_html = true;
+
+        let input = concat!(
+            "<table>\n",
+            "  <thead><tr><th>Name</th><th align=\"right\">Size</th></tr></thead>\n",
+            "  <tbody>\n",
+            "    <tr><td><code>a|b</code></td><td>1</td></tr>\n",
+            "    <tr><td><b>粗体</b> and <a href=\"https://x.org\">link</a></td>\n",
+            "        <td><p>one</p><p>two</p></td></tr>\n",
+            "  </tbody>\n",
+            "</table>\n",
+            "\n",
+            "<table><tr><th>a</th></tr><tr><td colspan=\"2\">b</td></tr></table>"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "| Name                               |       Size |\n",
+                "| ---------------------------------- | ---------: |\n",
+                "| `a\\|b`                             |          1 |\n",
+                "| **粗体** and [link](https://x.org) | one<br>two |\n",
+                "\n",
+                "<table><tr><th>a</th></tr><tr><td colspan=\"2\">b</td></tr></table>\n"
+            )
+        );
+
+        // Disabled by default
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert!(output.starts_with("<table>\n"));
+    }
+
+    #[test]
+    fn test_table_caption() {
+        // Captions stay attached to the table and are inline-formatted
+        let input = "|a|b|\n|---|---|\n|1|2|\nTable: 测试results | summary\ntext\n\n|a|\n|---|\n: short caption";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(
+            output,
+            concat!(
+                "| a   | b   |\n",
+                "| --- | --- |\n",
+                "| 1   | 2   |\n",
+                "Table: 测试 results | summary\n",
+                "\n",
+                "text\n",
+                "\n",
+                "| a   |\n",
+                "| --- |\n",
+                ": short caption\n"
+            )
+        );
+        assert_eq!(format_markdown(&output, &MdFormatConfig::default()), output);
+    }
+
+    #[test]
+    fn test_table_off_directive() {
+        let mut config = MdFormatConfig::default();
+        config.tables.ragged_rows = RuleMode::Fix;
+
+        let table = "|a|中文|\n|-|:-:|\n|1|2|3|\n|  x  |y|";
+        let input = format!("<!-- mdformat: table=off -->\n{}\n\n{}", table, table);
+        let (output, diagnostics) = format_document(&input, &config);
+        assert_eq!(
+            output,
+            format!(
+                "<!-- mdformat: table=off -->\n\n{}\n\n| a   | 中文  |\n| --- | :---: |\n| 1   |   2   | 3 |\n| x   |   y   |\n",
+                table
+            )
+        );
+        // Only the formatted table is checked
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 9);
+    }
+
+    #[test]
+    fn test_table_delimiter_style() {
+        let mut config = MdFormatConfig::default();
+        config.tables.delimiter_style = tables::DelimiterStyle::Minimal;
+
+        let input = "|name|description|center|\n|-|--:|:-:|\n|a|long description|x|";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "| name |      description | center |\n",
+                "| --- | ---: | :---: |\n",
+                "| a    | long description |   x    |\n"
+            )
+        );
+        assert_eq!(format_markdown(&output, &config), output);
+    }
+
+    #[test]
+    fn test_table_in_code_block_untouched() {
+        let input = "```\n|a|b|\n|---|---|\n```";
+        assert_eq!(format_markdown(input, &MdFormatConfig::default()), "```\n|a|b|\n|---|---|\n```\n");
+    }
+
+    #[test]
+    fn test_strong_marker() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.strong_marker = "**".to_string();
+
+        let input = "__bold__ and __two words__, **kept**, snake__case__name, `__init__` and __*nested*__, [x](a/__b__.md)";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "**bold** and **two words**, **kept**, snake__case__name, `__init__` and __*nested*__, [x](a/__b__.md)\n"
+        );
+
+        // Intraword strong emphasis can't use underscores
+        config.formatting.strong_marker = "__".to_string();
+        let output = format_markdown("**bold**, foo**bar**baz, **_x_**", &config);
+        assert_eq!(output, "__bold__, foo**bar**baz, **_x_**\n");
+    }
+
+    #[test]
+    fn test_strikethrough_cjk_spacing() {
+        let input = "中文~~删除~~中文 abc~~del~~中 ~~中文~~abc 中~~abc~~文 中~~ x ~~文";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(output, "中文~~删除~~中文 abc~~del~~ 中 ~~中文~~ abc 中 ~~abc~~ 文 中~~ x ~~文\n");
+        assert_eq!(format_markdown(&output, &MdFormatConfig::default()), output);
+    }
+
+    #[test]
+    fn test_highlight_cjk_spacing() {
+        let input = "中==高亮==文 a==中文==b 中==abc==文 x == y, a===b, if x==1 and y==2";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(output, "中==高亮==文 a ==中文== b 中 ==abc== 文 x == y, a===b, if x==1 and y==2\n");
+        assert_eq!(format_markdown(&output, &MdFormatConfig::default()), output);
+    }
+
+    #[test]
+    fn test_pandoc_sub_superscript() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.pandoc = true;
+
+        // No spaces inside the spans, CJK spacing outside them
+        let input = "x^2^y 中^上标a^文 H~2~O 中~a下~文 2^10^中 ~~删除~~ a~b c~d";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "x^2^y 中^上标a^ 文 H~2~O 中 ~a下~文 2^10^ 中 ~~删除~~ a~b c~d\n");
+        assert_eq!(format_markdown(&output, &config), output);
+
+        // Without the toggle the spans are ordinary text
+        let output = format_markdown("中^上标a^文", &MdFormatConfig::default());
+        assert_eq!(output, "中^上标 a^文\n");
+    }
+
+    #[test]
+    fn test_smart_punctuation() {
+        let mut config = MdFormatConfig::default();
+        config.punctuation.mode = PunctuationMode::Smart;
+
+        let input = concat!(
+            "He said \"it's 'fine'\"... pages 10--20 -- really---no. Use `--check \"x\"` or --check.\n",
+            "\n",
+            "See [a](https://x.org/a--b \"t\") and <a href=\"x\">y</a>, the '90s and users' docs.\n",
+            "\n",
+            "他说\"你好\"和'再见'\n",
+            "\n",
+            "Title\n",
+            "---"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "He said “it’s ‘fine’”… pages 10–20 – really—no. Use `--check \"x\"` or --check.\n",
+                "\n",
+                "See [a](https://x.org/a--b \"t\") and <a href=\"x\">y</a>, the ’90s and users’ docs.\n",
+                "\n",
+                "他说“你好”和‘再见’\n",
+                "\n",
+                "Title\n",
+                "---\n"
+            )
+        );
+
+        config.punctuation.cjk_quotes = QuoteStyle::Corner;
+        let output = format_markdown("他说\"你好\"和'再见', \"hi\"", &config);
+        assert_eq!(output, "他说「你好」和『再见』, “hi”\n");
+    }
+
+    #[test]
+    fn test_straight_punctuation() {
+        let mut config = MdFormatConfig::default();
+        config.punctuation.mode = PunctuationMode::Straight;
+
+        let input = "“It’s” ‘ok’… 10–20 — done. Keep `“code” —` and [a](https://x.org/“q”).";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "\"It's\" 'ok'... 10--20 --- done. Keep `“code” —` and [a](https://x.org/“q”).\n");
+    }
+
+    #[test]
+    fn test_dash_and_ellipsis_normalization() {
+        let mut config = MdFormatConfig::default();
+        config.punctuation.dashes = PunctuationStyle::Unicode;
+        config.punctuation.ellipsis = PunctuationStyle::Unicode;
+
+        let input = "Pages 1--5 – see --help — or not --- well. . . wait... `a -- b . . .`";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "Pages 1–5 – see --help — or not — well… wait… `a -- b . . .`\n");
+
+        config.punctuation.dashes = PunctuationStyle::Ascii;
+        config.punctuation.ellipsis = PunctuationStyle::Ascii;
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "Pages 1--5 -- see --help --- or not --- well... wait... `a -- b . . .`\n");
+    }
+
+    #[test]
+    fn test_unicode_nfc() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.unicode_nfc = true;
+        config.anchors.generate_ids = true;
+
+        // "Cafe\u{301}" and decomposed "한" (U+1112 U+1161 U+11AB)
+        let input = "# Cafe\u{301} \u{1112}\u{1161}\u{11AB}\n\nSee [it](#café-한) and `e\u{301}` too.";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "# Café 한 {#café-한}\n\nSee [it](#café-한) and `e\u{301}` too.\n");
+    }
+
+    #[test]
+    fn test_halfwidth_alphanumerics() {
+        let mut config = MdFormatConfig::default();
+        let input = "版本ＶＥＲ１２，见 `ＡＢＣ`\n";
+        assert_eq!(format_markdown(input, &config), input);
+
+        config.formatting.halfwidth_alphanumerics = true;
+        assert_eq!(format_markdown(input, &config), "版本 VER12，见 `ＡＢＣ`\n");
+    }
+
+    #[test]
+    fn test_invisible_chars() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.invisible_chars = RuleMode::Warn;
+
+        let input = "# Title\n\nZero\u{200B}width and \u{202E}bidi.\n\n```\na\u{200B}b\n```\n\nKeep `x\u{200B}y` here.\n";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, input);
+        let messages = diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "3: [invisible-char] invisible character U+200B (zero width space) at column 5",
+                "3: [invisible-char] invisible character U+202E (bidi embedding or override) at column 16",
+            ]
+        );
+
+        config.formatting.invisible_chars = RuleMode::Fix;
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(
+            output,
+            "# Title\n\nZerowidth and bidi.\n\n```\na\u{200B}b\n```\n\nKeep `x\u{200B}y` here.\n"
+        );
+        assert!(diagnostics.iter().all(|d| d.fixed));
+    }
+
+    #[test]
+    fn test_autolink_bare_urls() {
+        let mut config = MdFormatConfig::default();
+        config.links.bare_urls = links::BareUrls::Autolink;
+
+        let input = concat!(
+            "See https://example.com/a_(b) and (https://example.com/c). Or https://x.org/?q=1!\n",
+            "\n",
+            "Keep [https://a.org](https://a.org), <https://b.org>, `https://c.org` and <a href=\"https://d.org\">d</a>.\n",
+            "\n",
+            "[ref]: https://e.org\n",
+            "\n",
+            "```\n",
+            "https://f.org\n",
+            "```"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "See <https://example.com/a_(b)> and (<https://example.com/c>). Or <https://x.org/?q=1>!\n",
+                "\n",
+                "Keep [https://a.org](https://a.org), <https://b.org>, `https://c.org` and <a href=\"https://d.org\">d</a>.\n",
+                "\n",
+                "[ref]: https://e.org\n",
+                "\n",
+                "```\n",
+                "https://f.org\n",
+                "```\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_autolink_style() {
+        let mut config = MdFormatConfig::default();
+        config.links.autolinks = links::AutolinkStyle::Inline;
+
+        let input = "Visit <https://example.com/docs> or [example.org](https://example.org) and [docs](https://x.org) ![https://i.org](https://i.org)";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "Visit [example.com/docs](https://example.com/docs) or [example.org](https://example.org) and [docs](https://x.org) ![https://i.org](https://i.org)\n"
+        );
+
+        config.links.autolinks = links::AutolinkStyle::Angle;
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "Visit <https://example.com/docs> or <https://example.org> and [docs](https://x.org) ![https://i.org](https://i.org)\n"
+        );
+    }
+
+    #[test]
+    fn test_reference_links() {
+        let mut config = MdFormatConfig::default();
+        config.links.style = links::LinkStyle::Reference;
+
+        let input = concat!(
+            "See [the docs](https://x.org/docs \"Docs\") and [home](https://x.org).\n",
+            "Again [docs](https://x.org/docs \"Docs\"), `[a](b)` and ![img](i.png).\n",
+            "\n",
+            "| Link |\n",
+            "| --- |\n",
+            "| [x](https://x.org) |\n",
+            "\n",
+            "[1]: https://old.org"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "See [the docs][2] and [home][3].\n",
+                "Again [docs][2], `[a](b)` and ![img](i.png).\n",
+                "\n",
+                "| Link   |\n",
+                "| ------ |\n",
+                "| [x][3] |\n",
+                "\n",
+                "[1]: https://old.org\n",
+                "[2]: https://x.org/docs \"Docs\"\n",
+                "[3]: https://x.org\n"
+            )
+        );
+
+        config.links.reference_labels = links::ReferenceLabels::Slug;
+        let output = format_markdown("[Getting Started](a.md) and [getting started](b.md)", &config);
+        assert_eq!(
+            output,
+            "[Getting Started][getting-started] and [getting started][getting-started-1]\n\n[getting-started]: a.md\n[getting-started-1]: b.md\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_links() {
+        let mut config = MdFormatConfig::default();
+        config.links.style = links::LinkStyle::Inline;
+
+        let input = concat!(
+            "See [the docs][Docs], [docs][] and ![logo][l]. Keep [missing][x], [ ] and `[docs]` as is.\n",
+            "\n",
+            "[docs]: https://x.org/docs \"Docs\"\n",
+            "[l]: <logo one.png>\n",
+            "\n",
+            "[unused]: https://u.org\n",
+            "\n",
+            "## Next"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "See [the docs](https://x.org/docs \"Docs\"), [docs](https://x.org/docs \"Docs\") and ![logo](<logo one.png>). ",
+                "Keep [missing][x], [ ] and `[docs]` as is.\n",
+                "\n",
+                "[unused]: https://u.org\n",
+                "\n",
+                "## Next\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_sort_definitions() {
+        let mut config = MdFormatConfig::default();
+        config.links.sort_definitions = true;
+
+        let input = concat!(
+            "Read [b][beta], [a][Alpha], [c][10], [d][2] and [e][gamma].\n",
+            "\n",
+            "[beta]: https://b.org\n",
+            "[10]: https://ten.org\n",
+            "\n",
+            "## More\n",
+            "\n",
+            "[alpha]: https://a.org\n",
+            "[Alpha]: https://other.org\n",
+            "[2]: https://two.org\n",
+            "[gamma]: https://b.org\n"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "Read [b][beta], [a][Alpha], [c][10], [d][2] and [e][beta].\n",
+                "\n",
+                "## More\n",
+                "\n",
+                "[2]: https://two.org\n",
+                "[10]: https://ten.org\n",
+                "[alpha]: https://a.org\n",
+                "[beta]: https://b.org\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_references() {
+        let mut config = MdFormatConfig::default();
+        config.links.check_references = RuleMode::Warn;
+
+        let input = "See [a][one], [b][missing], [c] and [x].\n\n[one]: https://one.org\n[two]: https://two.org\n[x]: https://x.org\n";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, input);
+        let messages = diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "1: [link-reference] undefined link reference [missing]",
+                "4: [link-reference] unused link reference definition [two]",
+            ]
+        );
+
+        config.links.check_references = RuleMode::Fix;
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(
+            output,
+            "See [a][one], [b][missing], [c] and [x].\n\n[one]: https://one.org\n[x]: https://x.org\n"
+        );
+        assert_eq!(diagnostics.iter().filter(|d| !d.fixed).count(), 1);
+    }
+
+    #[test]
+    fn test_link_title_quotes() {
+        let mut config = MdFormatConfig::default();
+        config.links.title_quotes = links::TitleQuotes::Double;
+
+        let input = concat!(
+            "See [a](a.md  'A'), ![b](b.png (B \"quoted\")) and [c](c.md \"C\"). Keep `[d](d.md 'D')` as is.\n",
+            "\n",
+            "[e]: https://e.org    (E)"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "See [a](a.md \"A\"), ![b](b.png \"B \\\"quoted\\\"\") and [c](c.md \"C\"). Keep `[d](d.md 'D')` as is.\n",
+                "\n",
+                "[e]: https://e.org \"E\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_destination_spaces() {
+        let mut config = MdFormatConfig::default();
+        config.links.destination_spaces = links::DestinationSpaces::Encode;
+
+        let input = "See [a](my notes/a b.md \"Title here\"), ![b](img%201 x.png), [c](c.md \"C d\") and [d](<d e.md>).";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "See [a](my%20notes/a%20b.md \"Title here\"), ![b](img%201%20x.png), [c](c.md \"C d\") and [d](<d e.md>).\n"
+        );
+
+        config.links.destination_spaces = links::DestinationSpaces::Angle;
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "See [a](<my notes/a b.md> \"Title here\"), ![b](<img%201 x.png>), [c](c.md \"C d\") and [d](<d e.md>).\n"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_link_destinations() {
+        let mut config = MdFormatConfig::default();
+        config.links.rewrite = BTreeMap::from([
+            ("https://github.com/org/repo/blob/main/".to_string(), "/".to_string()),
+            ("https://github.com/org/repo/blob/main/docs/".to_string(), "/guide/".to_string()),
+            ("https://old.org".to_string(), "https://new.org".to_string()),
+        ]);
+
+        let input = concat!(
+            "See [a](https://github.com/org/repo/blob/main/src/a.rs), ",
+            "![b](<https://github.com/org/repo/blob/main/docs/b c.png>) and `[c](https://old.org/c)` too.\n",
+            "\n",
+            "[d]: https://old.org/d \"D\""
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "See [a](/src/a.rs), ![b](</guide/b c.png>) and `[c](https://old.org/c)` too.\n",
+                "\n",
+                "[d]: https://new.org/d \"D\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_repository_links() {
+        let mut config = MdFormatConfig::default();
+        config.links.repository_url = "https://github.com/org/repo/blob/main/".to_string();
+        config.links.repository_links = links::RepositoryLinks::Relative;
+        config.links.document_dir = PathBuf::from("docs/guide");
+
+        let input = concat!(
+            "See [a](https://github.com/org/repo/blob/main/docs/guide/a.md#usage), ",
+            "[b](https://github.com/org/repo/blob/main/src/b.rs) and [c](https://example.com/c.md).\n",
+            "\n",
+            "[d]: https://github.com/org/repo/blob/main/docs/d.md"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "See [a](a.md#usage), [b](../../src/b.rs) and [c](https://example.com/c.md).\n\n[d]: ../d.md\n"
+        );
+
+        config.links.repository_links = links::RepositoryLinks::Absolute;
+        let output = format_markdown(&output, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "See [a](https://github.com/org/repo/blob/main/docs/guide/a.md#usage), ",
+                "[b](https://github.com/org/repo/blob/main/src/b.rs) and [c](https://example.com/c.md).\n",
+                "\n",
+                "[d]: https://github.com/org/repo/blob/main/docs/d.md\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_repository_url() {
+        let mut config = MdFormatConfig::default();
+        config.links.repository_links = links::RepositoryLinks::Relative;
+
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("links.repository_url"));
+    }
+
+    #[test]
+    fn test_image_alt() {
+        let mut config = MdFormatConfig::default();
+        config.links.image_alt = RuleMode::Warn;
+
+        let input = "# Images\n\n![](img/logo-dark.png \"Logo\") and ![ok](a.png)\n\nKeep `![](code.png)` and ![ ](<my%20photo.jpg?v=2>).\n";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, input);
+        let messages = diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "3: [image-alt] image without alt text: img/logo-dark.png",
+                "5: [image-alt] image without alt text: <my%20photo.jpg?v=2>",
+            ]
+        );
+
+        config.links.image_alt = RuleMode::Fix;
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(
+            output,
+            "# Images\n\n![logo-dark](img/logo-dark.png \"Logo\") and ![ok](a.png)\n\nKeep `![](code.png)` and ![my photo](<my%20photo.jpg?v=2>).\n"
+        );
+        assert!(diagnostics.iter().all(|d| d.fixed));
+    }
+
+    #[test]
+    fn test_image_size_and_attribute_syntax() {
+        let mut config = MdFormatConfig::default();
+        config.punctuation.mode = PunctuationMode::Smart;
+
+        let input = concat!(
+            "图标![图标|300](img.png)和![[图片abc.png|200]]以及![alt|100x200](b.png)\n",
+            "\n",
+            "![logo](a.png){: width=\"300\" title=\"中文abc\"}\n",
+            "\n",
+            "段落文字abc\n",
+            "{: .note title=\"x--y\"}"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "图标![图标|300](img.png)和![[图片abc.png|200]]以及![alt|100x200](b.png)\n",
+                "\n",
+                "![logo](a.png){: width=\"300\" title=\"中文abc\"}\n",
+                "\n",
+                "段落文字 abc\n",
+                "{: .note title=\"x--y\"}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_emoji_shortcodes() {
+        let mut config = MdFormatConfig::default();
+        config.emoji.normalize_aliases = true;
+
+        let input = "好的:thumbsup:谢谢:smile_cat:和:satisfied: at 10:30:45, `:thumbsup:` and :unknown_name:";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "好的:+1:谢谢:smile_cat:和:laughing: at 10:30:45, `:thumbsup:` and :unknown_name:\n");
+    }
+
+    #[test]
+    fn test_emoji_style() {
+        let mut config = MdFormatConfig::default();
+        config.emoji.style = emoji::EmojiStyle::Shortcode;
+
+        let input = "Done 🎉 and ❤️ / ❤ with 👍 `🚀` 🫠 👨\u{200D}💻 👍🏽";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "Done :tada: and :heart: / :heart: with :+1: `🚀` 🫠 👨\u{200D}💻 👍🏽\n");
+
+        config.emoji.style = emoji::EmojiStyle::Unicode;
+        let output = format_markdown(":tada: :thumbsup: :heart: `:rocket:` :no_such_emoji:", &config);
+        assert_eq!(output, "🎉 👍 ❤️ `:rocket:` :no_such_emoji:\n");
+    }
+
+    #[test]
+    fn test_badge_lines() {
+        let mut config = MdFormatConfig::default();
+        config.links.destination_spaces = links::DestinationSpaces::Encode;
+
+        let input = concat!(
+            "# 项目\n",
+            "\n",
+            "[![构建build](https://img.shields.io/badge/构建-passing-green)](https://ci.org/x) ",
+            "![license许可](https://img.shields.io/badge/license-MIT-blue.svg)\n",
+            "\n",
+            "![截图abc](my shot.png)"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "# 项目\n",
+                "\n",
+                "[![构建build](https://img.shields.io/badge/构建-passing-green)](https://ci.org/x) ",
+                "![license许可](https://img.shields.io/badge/license-MIT-blue.svg)\n",
+                "\n",
+                "![截图 abc](my%20shot.png)\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_cjk_spacing_disabled_everywhere() {
+        // With CJK-ASCII spacing off no pass inserts a space next to CJK text
+        let mut config = MdFormatConfig::default();
+        config.spacing.cjk_ascii = false;
+        config.formatting.pandoc = true;
+
+        let input = concat!(
+            "# 标题Title\n",
+            "\n",
+            "- 列表item ~~删除abc~~文字 ==高亮abc==文字 H~2~O水\n",
+            "\n",
+            "| 列A | 列B |\n",
+            "| --- | --- |\n",
+            "| 中文abc | 123中文 |"
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "# 标题Title\n",
+                "\n",
+                "- 列表item ~~删除abc~~文字 ==高亮abc==文字 H~2~O水\n",
+                "\n",
+                "| 列A     | 列B     |\n",
+                "| ------- | ------- |\n",
+                "| 中文abc | 123中文 |\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_cjk_scripts() {
+        let input = "ひらがなABCとカタカナDEF、ｶﾀｶﾅ1と한국어GHI";
+        let output = format_markdown(input, &MdFormatConfig::default());
+        assert_eq!(output, "ひらがな ABC とカタカナ DEF、ｶﾀｶﾅ 1 と한국어 GHI\n");
+
+        let mut config = MdFormatConfig::default();
+        config.spacing.cjk_scripts = vec![CjkScript::Han, CjkScript::Hiragana, CjkScript::Katakana];
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "ひらがな ABC とカタカナ DEF、ｶﾀｶﾅ 1 と한국어GHI\n");
+    }
+
+    #[test]
+    fn test_no_spaces_around_fullwidth_punctuation() {
+        let config = MdFormatConfig::default();
+        let input = "中文，`code`。说明 `x`：abc ，def 。「 ABC 」\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "中文，`code`。说明 `x`：abc，def。「ABC」\n"
+        );
+    }
+
+    #[test]
+    fn test_cjk_fullwidth_punctuation() {
+        let mut config = MdFormatConfig::default();
+        config.punctuation.cjk_width = CjkPunctuation::Chinese;
+        let input = "你好, world. 这是测试.版本3.14, 见说明.md\n\n真的?! 文件 (注释) 和 (note), 见 `a,b` 与 [链接](./中文.md)\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "你好，world. 这是测试。版本 3.14, 见说明.md\n\n真的？！文件（注释）和 (note), 见 `a,b` 与 [链接](./中文.md)\n"
+        );
+
+        config.punctuation.cjk_width = CjkPunctuation::Japanese;
+        assert_eq!(format_markdown("これは, テスト.\n", &config), "これは、テスト。\n");
+    }
+
+    #[test]
+    fn test_collapse_repeated_cjk_punctuation() {
+        let mut config = MdFormatConfig::default();
+        let input = "真的吗？？？太好了！！！。。「「引用」」\n";
+        assert_eq!(format_markdown(input, &config), input);
+
+        config.punctuation.cjk_max_repeat = 1;
+        assert_eq!(format_markdown(input, &config), "真的吗？太好了！。「「引用」」\n");
+        config.punctuation.cjk_max_repeat = 2;
+        assert_eq!(format_markdown(input, &config), "真的吗？？太好了！！。。「「引用」」\n");
+    }
+
+    #[test]
+    fn test_cjk_separator() {
+        let mut config = MdFormatConfig::default();
+        config.spacing.cjk_separator = CjkSeparator::Thin;
+        let input = "中文ABC和~~删除~~text\n";
+        let output = format_markdown(input, &config);
+        assert_eq!(output, "中文\u{2009}ABC\u{2009}和~~删除~~\u{2009}text\n");
+        assert_eq!(format_markdown(&output, &config), output);
+
+        config.spacing.cjk_separator = CjkSeparator::Nbsp;
+        assert_eq!(format_markdown("中文ABC\n", &config), "中文\u{00A0}ABC\n");
+    }
+
+    #[test]
+    fn test_no_cjk_spaces_in_link_destinations() {
+        let config = MdFormatConfig::default();
+        let input = concat!(
+            "见[文档a](./中文doc.md)和![图](img/图片a.png)，<https://例子.com/中文a>与<a href=\"x中文y\">链接b</a>\n",
+            "\n",
+            "见[文档][a]\n",
+            "\n",
+            "[a]: ./中文doc.md\n",
+        );
+        let expected = input.replace("文档a", "文档 a").replace("doc.md)和", "doc.md) 和").replace("链接b", "链接 b");
+        assert_eq!(format_markdown(input, &config), expected);
+    }
+
+    #[test]
+    fn test_inline_math_is_opaque() {
+        let config = MdFormatConfig::default();
+        let input = "中文$x中文y$中文，公式\\(a中b\\)说明a$b$c，$$E=mc^2$$中，价格$5和$10\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "中文 $x中文y$ 中文，公式 \\(a中b\\) 说明 a$b$c，$$E=mc^2$$ 中，价格 $5 和 $10\n"
+        );
+    }
+
+    #[test]
+    fn test_cjk_spacing_around_emphasis() {
+        let config = MdFormatConfig::default();
+        let input = "**粗体**text和中文*em*与__强调__abc，abc**粗体**中文，my_var_name\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "**粗体** text 和中文 *em* 与__强调__ abc，abc **粗体**中文，my_var_name\n"
+        );
+    }
+
+    #[test]
+    fn test_cjk_spacing_around_links() {
+        let config = MdFormatConfig::default();
+        let input = "[链接](url)英文和中文[link](url)，[中文](./中文a.md)b与![图](p)c，a[中文][r]\n\n[r]: u\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "[链接](url)英文和中文 [link](url)，[中文](./中文a.md) b 与![图](p) c，a [中文][r]\n\n[r]: u\n"
+        );
+    }
+
+    #[test]
+    fn test_no_spaces_around_inline_html() {
+        let config = MdFormatConfig::default();
+        let input = "按<kbd>Ctrl</kbd>中文和<sup>注</sup>a，<kbd>`Esc`</kbd>键与<span title=\"a中\">b</span>\n";
+        assert_eq!(format_markdown(input, &config), input);
+    }
+
+    #[test]
+    fn test_cjk_number_units() {
+        let mut config = MdFormatConfig::default();
+        let input = "占比50%和25 ℃，版本2.1.3发布，华为P40和小米13上市\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "占比 50% 和 25℃，版本 2.1.3 发布，华为 P40 和小米 13 上市\n"
+        );
+
+        config.spacing.units = Vec::new();
+        config.spacing.exceptions = vec!["华为P40".to_string()];
+        assert_eq!(
+            format_markdown(input, &config),
+            "占比 50%和 25 ℃，版本 2.1.3 发布，华为P40和小米 13 上市\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap_cjk_paragraphs() {
+        let mut config = MdFormatConfig::default();
+        config.wrap.mode = WrapMode::Width;
+        config.wrap.width = 20;
+        let input = concat!(
+            "一二三四五六七八九，十一二三四五六七八九十。English words\n",
+            "wrap at spaces.\n",
+            "\n",
+            "Title\n",
+            "=====\n",
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "一二三四五六七八九，\n",
+                "十一二三四五六七八九\n",
+                "十。English words\n",
+                "wrap at spaces.\n",
+                "\n",
+                "Title\n",
+                "=====\n",
+            )
+        );
+        assert_eq!(format_markdown(&output, &config), output);
+    }
+
+    #[test]
+    fn test_wrap_list_items_and_blockquotes() {
+        let mut config = MdFormatConfig::default();
+        config.wrap.mode = WrapMode::Width;
+        config.wrap.width = 24;
+        let input = concat!(
+            "- a list item long enough to wrap\n",
+            "\n",
+            "  its second paragraph is wrapped too\n",
+            "\n",
+            "  ```\n",
+            "  code is never wrapped, whatever its width\n",
+            "  ```\n",
+            "\n",
+            "> > a nested quote long enough to wrap\n",
+            "\n",
+            "[ref]: https://example.com/a/very/long/link/destination\n",
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "- a list item long\n",
+                "  enough to wrap\n",
+                "\n",
+                "  its second paragraph\n",
+                "  is wrapped too\n",
+                "\n",
+                "  ```\n",
+                "  code is never wrapped, whatever its width\n",
+                "  ```\n",
+                "\n",
+                "> > a nested quote long\n",
+                "> > enough to wrap\n",
+                "\n",
+                "[ref]: https://example.com/a/very/long/link/destination\n",
+            )
+        );
+        assert_eq!(format_markdown(&output, &config), output);
+    }
+
+    #[test]
+    fn test_wrap_sentences_and_unwrap() {
+        let mut config = MdFormatConfig::default();
+        config.wrap.mode = WrapMode::Sentence;
+        let input = concat!(
+            "First sentence, e.g. with `code. Here` inside. Second\n",
+            "one? \"Third!\" 第一句。第二句！第三\n",
+            "句\n",
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            concat!(
+                "First sentence, e.g. with `code. Here` inside.\n",
+                "Second one?\n",
+                "\"Third!\"\n",
+                "第一句。\n",
+                "第二句！\n",
+                "第三句\n",
+            )
+        );
+        assert_eq!(format_markdown(&output, &config), output);
+
+        config.wrap.mode = WrapMode::No;
+        assert_eq!(
+            format_markdown(input, &config),
+            "First sentence, e.g. with `code. Here` inside. Second one? \"Third!\" 第一句。第二句！第三句\n"
+        );
+
+        // Hard breaks end the joined lines
+        let input = "line one  \nline two\\\nline three\n中文\n继续\n\n```\ncode  \nline\n```\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "line one\\\nline two\\\nline three 中文继续\n\n```\ncode\nline\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap_hard_breaks_only_in_prose() {
+        // Trailing spaces outside prose paragraphs are dropped, never turned into backslashes
+        let mut config = MdFormatConfig::default();
+        config.wrap.mode = WrapMode::No;
+        let input = "---\ntitle: a  \ntags: b\n---\n\ntext  \nmore\n";
+        assert_eq!(format_markdown(input, &config), "---\ntitle: a\ntags: b\n---\n\ntext\\\nmore\n");
+        assert_eq!(format_markdown("$$\nx = 1  \ny\n$$\n", &config), "$$\nx = 1\ny\n$$\n");
+        let input = "para\n\n    code  \n    more\n\n<div>  \n<p>x</p>\n</div>\n";
+        assert_eq!(format_markdown(input, &config), "para\n\n    code\n    more\n\n<div>\n<p>x</p>\n</div>\n");
+    }
+
+    #[test]
+    fn test_wrap_skips_html_blocks() {
+        // Lines of an HTML block, up to the next blank line, are never reflowed
+        let mut config = MdFormatConfig::default();
+        config.wrap.mode = WrapMode::No;
+        let input = concat!(
+            "<div align=\"center\">\n",
+            "some text\n",
+            "more text\n",
+            "</div>\n",
+            "\n",
+            "<details>\n",
+            "<summary>Sum</summary>\n",
+            "body line one\n",
+            "body line two\n",
+            "</details>\n",
+            "\n",
+            "para\n",
+            "line\n",
+        );
+        let expected = input.replace("para\nline", "para line");
+        assert_eq!(format_markdown(input, &config), expected);
+
+        config.wrap.mode = WrapMode::Width;
+        config.wrap.width = 5;
+        assert_eq!(format_markdown("<p>\nlong words here\n</p>\n", &config), "<p>\nlong words here\n</p>\n");
+    }
+
+    #[test]
+    fn test_blank_line_policy_per_construct() {
+        let mut config = MdFormatConfig::default();
+        config.blank_lines.before_code = false;
+        config.blank_lines.after_tables = false;
+        config.blank_lines.before_lists = false;
+        config.blank_lines.around_thematic_breaks = false;
+        let input = concat!(
+            "Text\n",
+            "```\n",
+            "code\n",
+            "```\n",
+            "after code\n",
+            "| a |\n",
+            "| - |\n",
+            "after table\n",
+            "- item\n",
+            "\n",
+            "before break\n",
+            "***\n",
+            "after break\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "Text\n",
+                "```\n",
+                "code\n",
+                "```\n",
+                "\n",
+                "after code\n",
+                "\n",
+                "| a   |\n",
+                "| --- |\n",
+                "after table\n",
+                "- item\n",
+                "\n",
+                "before break\n",
+                "\n",
+                "---\n",
+                "after break\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_tab_indented_lists() {
+        let mut config = MdFormatConfig::default();
+        let input = "- a\n\t- b\n    - c\n\n\t  text\n";
+        assert_eq!(format_markdown(input, &config), "- a\n  - b\n  - c\n\n      text\n");
+
+        config.lists.tab_width = 2;
+        assert_eq!(format_markdown(input, &config), "- a\n  - b\n    - c\n\n    text\n");
+
+        // A tab counts as a level of the space indentation it is mixed with
+        let mixed = "- a\n  - b\n\t- c\n\t\t- d\n  - e\n";
+        assert_eq!(format_markdown(mixed, &config), "- a\n  - b\n  - c\n    - d\n  - e\n");
+
+        config.lists.tab_width = 4;
+        config.lists.indent_style = IndentStyle::Tabs;
+        assert_eq!(format_markdown(input, &config), "- a\n\t- b\n\t- c\n\n\t  text\n");
+    }
+
+    #[test]
+    fn test_apply_line_ending() {
+        let formatted = "# Title\n\ntext\n".to_string();
+        let crlf = "# Title\r\n\r\ntext\r\n";
+        assert_eq!(apply_line_ending(formatted.clone(), crlf, LineEnding::Auto), crlf);
+        assert_eq!(apply_line_ending(formatted.clone(), "# Title\r\ntext\n\n\n", LineEnding::Auto), formatted);
+        assert_eq!(apply_line_ending(formatted.clone(), crlf, LineEnding::Lf), formatted);
+        assert_eq!(apply_line_ending(formatted.clone(), "text\n", LineEnding::Crlf), crlf);
+    }
+
+    #[test]
+    fn test_final_newline() {
+        let mut config = MdFormatConfig::default();
+        let input = "# Title\n\ntext\n\n\n";
+        assert_eq!(format_markdown(input, &config), "# Title\n\ntext\n");
+
+        config.formatting.final_newline = FinalNewline::None;
+        assert_eq!(format_markdown(input, &config), "# Title\n\ntext");
+
+        config.formatting.final_newline = FinalNewline::Preserve;
+        assert_eq!(format_markdown(input, &config), input);
+        assert_eq!(format_markdown("text", &config), "text");
+    }
+
+    #[test]
+    fn test_preserve_outer_whitespace() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.preserve_outer_whitespace = true;
+        let input = "\n\n  indented text\n\n \n";
+        assert_eq!(format_markdown(input, &config), input);
+        assert_eq!(format_markdown("\n\n", &config), "\n\n");
+        assert_eq!(format_markdown("text", &config), "text");
+    }
+
+    #[test]
+    fn test_blockquote_separator_lines() {
+        let config = MdFormatConfig::default();
+        let input = "> a\n>  > \n> > b\n> \u{3000}\n>\n> c\n";
+        assert_eq!(format_markdown(input, &config), "> a\n> >\n> > b\n>\n>\n> c\n");
+    }
+
+    #[test]
+    fn test_aligned_text_paragraphs() {
+        let mut config = MdFormatConfig::default();
+        let input = concat!(
+            "名称    value   说明\n",
+            "alpha   1       第一\n",
+            "\n",
+            "+------+    ┌──┐\n",
+            "| box  |    │中│\n",
+            "+------+    └──┘\n",
+            "\n",
+            "Two spaces after a sentence.  Are fine\n",
+            "in prose.  So this is spaced中文\n",
+        );
+        let output = format_markdown(input, &config);
+        assert_eq!(output, input.replace("spaced中文", "spaced 中文"));
+
+        config.protect.aligned_text = false;
+        let output = format_markdown(input, &config);
+        assert!(output.contains("\n\n| box | │中│ |\n\n"));
+    }
+
+    #[test]
+    fn test_long_lines_are_verbatim() {
+        let mut config = MdFormatConfig::default();
+        config.protect.max_line_length = 40;
+        let long = format!("长行{}长行", "a".repeat(40));
+        let input = format!("{}\n\n短行abc\n", long);
+        assert_eq!(format_markdown(&input, &config), format!("{}\n\n短行 abc\n", long));
+
+        config.protect.max_line_length = 0;
+        assert_eq!(
+            format_markdown(&input, &config),
+            format!("长行 {} 长行\n\n短行 abc\n", "a".repeat(40))
+        );
+    }
+
+    #[test]
+    fn test_mdx_blocks_and_expressions() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.dialect = dialects::Dialect::Mdx;
+        let input = concat!(
+            "import {Chart} from './chart'\n",
+            "export const meta = {title: '标题abc'}\n",
+            "# 标题abc\n",
+            "<Chart\n",
+            "  data={[1, 2]}\n",
+            "  label=\"a>b 中文abc\"\n",
+            "/>\n",
+            "<Note type=\"tip\">\n",
+            "\n",
+            "*  提示abc {props.name}中文\n",
+            "\n",
+            "</Note>\n",
+            "正文abc {props.x}中文\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "import {Chart} from './chart'\n",
+                "export const meta = {title: '标题abc'}\n",
+                "\n",
+                "# 标题 abc\n",
+                "\n",
+                "<Chart\n",
+                "  data={[1, 2]}\n",
+                "  label=\"a>b 中文abc\"\n",
+                "/>\n",
+                "\n",
+                "<Note type=\"tip\">\n",
+                "\n",
+                "*  提示abc {props.name}中文\n",
+                "\n",
+                "</Note>\n",
+                "\n",
+                "正文 abc {props.x}中文\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_rmarkdown_chunks_and_inline_code() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.dialect = dialects::Dialect::Rmd;
+        let input = concat!(
+            "```{r setup, include=FALSE}\n",
+            "x<-c(1,2)中文\n",
+            "```\n",
+            "\n",
+            "平均值为`r mean(x)`元，mean is `r m`. 代码`x`中\n",
+            "\n",
+            "- item\n",
+            "\n",
+            "    ```{r}\n",
+            "    y<-1中文\n",
+            "    ```\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "```{r setup, include=FALSE}\n",
+                "x<-c(1,2)中文\n",
+                "```\n",
+                "\n",
+                "平均值为`r mean(x)`元，mean is `r m`. 代码 `x` 中\n",
+                "\n",
+                "- item\n",
+                "\n",
+                "    ```{r}\n",
+                "    y<-1中文\n",
+                "    ```\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_quarto_protected_syntax() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.dialect = dialects::Dialect::Quarto;
+        let input = concat!(
+            "::: {.callout-note title=\"注意abc\"}\n",
+            "内容abc\n",
+            ":::\n",
+            "\n",
+            "{{< meta 标题abc >}}\n",
+            "\n",
+            "[文本]{.mark title=\"说明abc\"}中文，值`{python} x`。正文abc\n",
+            "\n",
+            "- item\n",
+            "\n",
+            "    ```{python}\n",
+            "    y=1中文\n",
+            "    ```\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "::: {.callout-note title=\"注意abc\"}\n",
+                "内容 abc\n",
+                ":::\n",
+                "\n",
+                "{{< meta 标题abc >}}\n",
+                "\n",
+                "[文本]{.mark title=\"说明abc\"}中文，值`{python} x`。正文 abc\n",
+                "\n",
+                "- item\n",
+                "\n",
+                "    ```{python}\n",
+                "    y=1中文\n",
+                "    ```\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let config = MdFormatConfig::default();
+        let input = concat!(
+            "#!/usr/bin/env python\n",
+            "# 说明abc\n",
+            "#\n",
+            "# *  列表abc\n",
+            "def f():\n",
+            "    # 缩进abc\n",
+            "    return 1  # 行尾abc\n",
+        );
+        assert_eq!(
+            comments::format_comments(input, "#", &config),
+            concat!(
+                "#!/usr/bin/env python\n",
+                "# 说明 abc\n",
+                "#\n",
+                "# - 列表 abc\n",
+                "def f():\n",
+                "    # 缩进 abc\n",
+                "    return 1  # 行尾abc\n",
+            )
+        );
+
+        // Longer prefixes and other comment styles are not comments
+        let input = "/// 文档abc
+// 注释abc
+#include <x>
+";
+        assert_eq!(
+            comments::format_comments(input, "//", &config),
+            "/// 文档abc
+// 注释 abc
+#include <x>
+"
+        );
+    }
+
+    #[test]
+    fn test_attribute_blocks() {
+        let config = MdFormatConfig::default();
+        let input = concat!(
+            "## 小节abc\n",
+            "{: #sec}\n",
+            "正文abc\n",
+            "\n",
+            "| a | b |\n",
+            "|---|---|\n",
+            "{: .table}\n",
+            "后文abc\n",
+            "\n",
+            "```\n",
+            "code\n",
+            "```\n",
+            "{: .code}\n",
+            "\n",
+            "> 引用abc\n",
+            "{: .quote}\n",
+            "\n",
+            "[链接](x){.btn title=\"按钮abc\"} 文字*强调*{.red} 代码`x`{.py}\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "## 小节 abc\n",
+                "{: #sec}\n",
+                "\n",
+                "正文 abc\n",
+                "\n",
+                "| a   | b   |\n",
+                "| --- | --- |\n",
+                "{: .table}\n",
+                "\n",
+                "后文 abc\n",
+                "\n",
+                "```\n",
+                "code\n",
+                "```\n",
+                "{: .code}\n",
+                "\n",
+                "> 引用 abc\n",
+                "{: .quote}\n",
+                "\n",
+                "[链接](x){.btn title=\"按钮abc\"} 文字*强调*{.red} 代码 `x`{.py}\n",
+            )
+        );
+
+        // Attribute lines are never joined into the paragraph
+        let mut config = MdFormatConfig::default();
+        config.wrap.mode = WrapMode::No;
+        assert_eq!(format_markdown("段落abc\n{: .lead}\n", &config), "段落 abc\n{: .lead}\n");
+    }
+
+    #[test]
+    fn test_pandoc_fenced_divs() {
+        let mut config = MdFormatConfig::default();
+        config.formatting.pandoc = true;
+        let input = concat!(
+            "段落abc\n",
+            ":::{.warning}\n",
+            "\n",
+            "警告abc\n",
+            "*  列表abc\n",
+            "\n",
+            "::::::\n",
+            "::: {#outer .note} :::\n",
+            "外层abc\n",
+            "\n",
+            ":::: inner\n",
+            "```\n",
+            ":::\n",
+            "```\n",
+            "::::\n",
+            ":::\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "段落 abc\n",
+                "\n",
+                "::: {.warning}\n",
+                "警告 abc\n",
+                "\n",
+                "- 列表 abc\n",
+                ":::\n",
+                "\n",
+                ":::: {#outer .note}\n",
+                "外层 abc\n",
+                "\n",
+                "::: inner\n",
+                "```\n",
+                ":::\n",
+                "```\n",
+                ":::\n",
+                "::::\n",
+            )
+        );
+
+        // Without the Pandoc extensions, fences are ordinary lines
+        let config = MdFormatConfig::default();
+        assert_eq!(format_markdown("::: warning\n警告abc\n:::\n", &config), "::: warning\n警告 abc\n:::\n");
+    }
+
+    #[test]
+    fn test_critic_markup() {
+        let config = MdFormatConfig::default();
+        assert_eq!(
+            format_markdown("中文{++abc 中文++}中文{--删除--}abc，{~~旧abc~>新...~~}和{>>评论:  x<<}。\n", &config),
+            "中文 {++abc 中文++}中文{--删除--} abc，{~~旧abc~>新...~~}和{>>评论:  x<<}。\n"
+        );
+    }
+
+    #[test]
+    fn test_display_math_blocks() {
+        let config = MdFormatConfig::default();
+        let input = concat!(
+            "段落abc\n",
+            "$$\n",
+            "x = 中文abc\n",
+            "\n",
+            "y = 1\n",
+            "$$\n",
+            "后文abc\n",
+            "$$ a+b 中文 $$\n",
+            "公式$$E=mc^2$$中\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "段落 abc\n",
+                "\n",
+                "$$\n",
+                "x = 中文abc\n",
+                "\n",
+                "y = 1\n",
+                "$$\n",
+                "\n",
+                "后文 abc\n",
+                "\n",
+                "$$ a+b 中文 $$\n",
+                "\n",
+                "公式 $$E=mc^2$$ 中\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_latex_environments() {
+        let config = MdFormatConfig::default();
+        let input = concat!(
+            "段落abc\n",
+            "\\begin{align}\n",
+            "a &= b + 中文c \\\\\n",
+            "\\begin{align}\n",
+            "x  &= y\n",
+            "\\end{align}\n",
+            "\\end{align}\n",
+            "后文abc\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "段落 abc\n",
+                "\n",
+                "\\begin{align}\n",
+                "a &= b + 中文c \\\\\n",
+                "\\begin{align}\n",
+                "x  &= y\n",
+                "\\end{align}\n",
+                "\\end{align}\n",
+                "\n",
+                "后文 abc\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_front_matter() {
+        let mut config = MdFormatConfig::default();
+        config.front_matter.format = true;
+        config.front_matter.key_order = vec!["title".to_string(), "date".to_string()];
+        config.front_matter.sort_keys = true;
+        let input = concat!(
+            "---\n",
+            "tags:\n",
+            "- a\n",
+            "-   \"b\"\n",
+            "\"title\":    'Hello: world'\n",
+            "zeta: 1\n",
+            "date: 2024-01-05\n",
+            "author:\n",
+            "    name: 张三\n",
+            "    links: [x, y]\n",
+            "desc: |\n",
+            "  line one\n",
+            "  line two\n",
+            "---\n",
+            "正文abc\n",
+        );
+        assert_eq!(
+            format_markdown(input, &config),
+            concat!(
+                "---\n",
+                "title: 'Hello: world'\n",
+                "date: 2024-01-05\n",
+                "author:\n",
+                "  name: 张三\n",
+                "  links:\n",
+                "    - x\n",
+                "    - y\n",
+                "desc: |\n",
+                "  line one\n",
+                "  line two\n",
+                "tags:\n",
+                "  - a\n",
+                "  - b\n",
+                "zeta: 1\n",
+                "---\n",
+                "\n",
+                "正文 abc\n",
+            )
+        );
+
+        // Comments and anchors would be lost, so is invalid YAML
+        for front_matter in ["---\ntitle:  x # note\n---", "---\na: &x 1\nb:  *x\n---", "---\ntitle: [x\n---"] {
+            let input = format!("{}\n正文\n", front_matter);
+            assert_eq!(format_markdown(&input, &config), format!("{}\n\n正文\n", front_matter));
+        }
+    }
+
+    #[test]
+    fn test_list_mixed_tab_and_space_indent() {
+        // With the default tab width of 4, a tab is one level of the 2-space
+        // indentation it is mixed with, not two
+        let config = MdFormatConfig::default();
+        let mixed = "- a\n  - b\n\t- c\n\t\t- d\n  - e\n";
+        assert_eq!(format_markdown(mixed, &config), "- a\n  - b\n  - c\n    - d\n  - e\n");
+    }
+
+    #[test]
+    fn test_cjk_amount_spacing() {
+        let mut config = MdFormatConfig::default();
+        let input = "价格¥ 100元，增长50%以上，气温25°C左右，下午3:00PM开会，共3个\n";
+        assert_eq!(
+            format_markdown(input, &config),
+            "价格 ¥100 元，增长 50% 以上，气温 25°C 左右，下午 3:00PM 开会，共 3 个\n"
+        );
+
+        config.spacing.amounts = AmountSpacing::Attach;
+        assert_eq!(
+            format_markdown(input, &config),
+            "价格¥100元，增长50%以上，气温25°C左右，下午3:00PM开会，共 3 个\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_cjk_ascii_spaces() {
+        let mut config = MdFormatConfig::default();
+        config.spacing.cjk_ascii = false;
+        config.spacing.strip_cjk_ascii = true;
+        let input = "中文 ABC 和 123，a  中 and 見 [link](./中 文.md)\n";
+        assert_eq!(format_markdown(input, &config), "中文ABC和123，a  中and見 [link](./中 文.md)\n");
+    }
+
+    #[test]
+    fn test_validate_invalid_strip_cjk_ascii() {
+        let mut config = MdFormatConfig::default();
+        config.spacing.strip_cjk_ascii = true;
+
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("spacing.strip_cjk_ascii"));
+    }
+
+    #[test]
+    fn test_validate_invalid_wrap_width() {
+        let mut config = MdFormatConfig::default();
+        config.wrap.width = 0;
+
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("wrap.width"));
+    }
+
+    #[test]
+    fn test_ideographic_spaces() {
+        let mut config = MdFormatConfig::default();
+        let input = "\u{3000}\u{3000}これは段落です。\n\n年齢\u{3000}\u{3000}：20\u{3000}\n";
+        assert_eq!(format_markdown(input, &config), input);
+
+        config.formatting.ideographic_spaces = IdeographicSpaces::Space;
+        assert_eq!(format_markdown(input, &config), "これは段落です。\n\n年齢：20\n");
+    }
+
+    // ===== Added: FormattingOptions tests (6 tests) =====
+    #[test]
+    fn test_disable_format_tables() {
+        // Test disabling table formatting
+        let mut config = MdFormatConfig::default();
+        config.formatting.format_tables = false;
+
+        let input = "|a|b|\n|---|---|\n| column 1 | column 2    |";
+        let output = format_markdown(input, &config);
+
+        // Table should not be formatted
+        assert_eq!(output, "|a|b|\n|---|---|\n| column 1 | column 2    |\n");
+    }
+
+    #[test]
+    fn test_disable_format_lists() {
+        // Test disabling list formatting
+        let mut config = MdFormatConfig::default();
+        config.formatting.format_lists = false;
+
+        let input = "1. first\n5. second\n+ item";
+        let output = format_markdown(input, &config);
+
+        // List numbers and markers should not be modified (not adding blank lines between list items is correct behavior)
+        assert_eq!(output, "1. first\n5. second\n+ item\n");
+    }
+
+    #[test]
+    fn test_disable_cjk_spacing() {
+        // Test disabling CJK-ASCII spacing
+        let mut config = MdFormatConfig::default();
+        config.spacing.cjk_ascii = false;
+
+        let input = "123你好world";
+        let output = format_markdown(input, &config);
+
+        // Should not add spaces
+        assert_eq!(output, "123你好world\n");
+    }
+
+    #[test]
+    fn test_disable_code_span_spacing() {
+        // Test disabling spacing around code spans
+        let mut config = MdFormatConfig::default();
+        config.spacing.around_code_spans = false;
+
+        let input = "text`code`text";
+        let output = format_markdown(input, &config);
+
+        // Should not add spaces
+        assert_eq!(output, "text`code`text\n");
+    }
+
+    #[test]
+    fn test_disable_blank_lines() {
+        // Test disabling blank lines between elements
+        let mut config = MdFormatConfig::default();
+        config.formatting.blank_lines = false;
+
+        let input = "# Title\n## Subtitle\ntext\n```\ncode\n```\nmore text";
+        let output = format_markdown(input, &config);
+
+        // Should not add blank lines (blank line after heading is still controlled by heading.blank_line_after)
+        assert!(output.contains("```\ncode\n```\nmore text"));
+    }
+
+    #[test]
+    fn test_disable_merge_blank_lines() {
+        // Test disabling merging consecutive blank lines
+        let mut config = MdFormatConfig::default();
+        config.formatting.merge_blank_lines = false;
+
+        let input = "line1\n\n\n\nline2";
+        let output = format_markdown(input, &config);
+
+        // Consecutive blank lines should be preserved (input has 3 blank lines, output should also have 3)
+        assert_eq!(output, "line1\n\n\n\nline2\n");
+    }
+
+    // ===== Added: ListOptions tests (4 tests) =====
+    #[test]
+    fn test_custom_list_indent_4() {
+        // Test custom indentation: 4 spaces
+        let mut config = MdFormatConfig::default();
+        config.lists.indent = 4;
 
         let input = "- level 1\n  - level 2\n    - level 3";
         let output = format_markdown(input, &config);
@@ -1538,6 +5482,31 @@ Some paragraph text.
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn test_no_blank_line_below_heading() {
+        // Blocks right below a heading stay there when no blank line follows headings
+        let mut config = MdFormatConfig::default();
+        config.headings.blank_line_after = false;
+
+        let input = concat!(
+            "# Table\n| a |\n| - |\n\n",
+            "# List\n- item\n\n",
+            "# Code\n```\ncode\n```\n\n",
+            "# Quote\n> quote\n",
+        );
+        let expected = concat!(
+            "# Table\n| a   |\n| --- |\n\n",
+            "# List\n- item\n\n",
+            "# Code\n```\ncode\n```\n\n",
+            "# Quote\n> quote\n",
+        );
+        assert_eq!(format_markdown(input, &config), expected);
+
+        // A paragraph in between still gets its blank lines
+        let input = "# Title\ntext\n```\ncode\n```\n";
+        assert_eq!(format_markdown(input, &config), "# Title\ntext\n\n```\ncode\n```\n");
+    }
+
     #[test]
     fn test_heading_numbering_skip_levels() {
         // Test numbering with skipped heading levels
@@ -1565,6 +5534,23 @@ Some paragraph text.
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn test_heading_numbering_keeps_numbers_in_titles() {
+        // A year or a quantity starting a title is not old numbering
+        let mut config = MdFormatConfig::default();
+        config.headings.numbering_start_level = 2;
+
+        let input = "# Plan\n## Goals\n## 2024 Roadmap\n## 100 Tips\n### 360 Reviews\n## Risks\n";
+        let expected = concat!(
+            "# Plan\n\n## 1 Goals\n\n## 2 2024 Roadmap\n\n## 3 100 Tips\n\n",
+            "### 3.1 360 Reviews\n\n## 4 Risks\n"
+        );
+        assert_eq!(format_markdown(input, &config), expected);
+
+        // Formatting again only replaces the numbering
+        assert_eq!(format_markdown(expected, &config), expected);
+    }
+
     #[test]
     fn test_heading_add_numbering_to_clean_titles() {
         // Test adding numbering to headings without numbering
@@ -1606,6 +5592,215 @@ Some paragraph text.
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn test_heading_numbering_keeps_leading_digits_of_title() {
+        // Digits that are part of the title are not taken for numbering
+        let mut config = MdFormatConfig::default();
+        config.headings.numbering_start_level = 2;
+
+        let input = "## 3D Graphics\n## 2.1. Old Number\n### 3.14Pi\n#tag";
+        let output = format_markdown(input, &config);
+
+        let expected = "## 1 3D Graphics\n\n## 2 Old Number\n\n### 2.1 3.14Pi\n\n#tag\n";
+        assert_eq!(output, expected);
+
+        // Moving a section renumbers everything
+        let moved = "## 2 Old Number\n\n### 2.1 3.14Pi\n\n## 1 3D Graphics";
+        let expected = "## 1 Old Number\n\n### 1.1 3.14Pi\n\n## 2 3D Graphics\n";
+        assert_eq!(format_markdown(moved, &config), expected);
+    }
+
+    #[test]
+    fn test_strip_heading_numbering() {
+        let mut config = MdFormatConfig::default();
+        config.headings.strip_numbering = true;
+
+        let input = "# 第三章 总结\n## 1.2.3 Details\n## 一、背景\n### (2) 方法\n## 3D Graphics\n## 2024";
+        let expected = "# 总结\n\n## Details\n\n## 背景\n\n### 方法\n\n## 3D Graphics\n\n## 2024\n";
+        assert_eq!(format_markdown(input, &config), expected);
+
+        // Combined with numbering, manual numbers are replaced
+        config.headings.numbering_start_level = 1;
+        assert_eq!(format_markdown("# 第1章 Intro", &config), "# 1 Intro\n");
+    }
+
+    #[test]
+    fn test_duplicate_headings() {
+        let input = "# Guide\n## Setup\n### Notes\n## Usage\n###  Notes\n## Setup ##\n### Setup";
+
+        let (_, diagnostics) = format_document(input, &MdFormatConfig::default());
+        assert!(diagnostics.is_empty());
+
+        let mut config = MdFormatConfig::default();
+        config.headings.check_duplicates = true;
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, format_markdown(input, &MdFormatConfig::default()));
+        let found = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (5, "duplicate H3 heading 'Notes' (first at line 3)"),
+                (6, "duplicate H2 heading 'Setup' (first at line 2)"),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.rule == "duplicate-heading"));
+    }
+
+    #[test]
+    fn test_single_h1() {
+        let input = "Intro text\n# First\n## Sub\n# Second\n## Sub 2";
+
+        let mut config = MdFormatConfig::default();
+        config.headings.single_h1 = RuleMode::Warn;
+        let (_, diagnostics) = format_document(input, &config);
+        let found = diagnostics.iter().map(|d| (d.line, d.rule)).collect::<Vec<_>>();
+        assert_eq!(found, vec![(1, "first-line-h1"), (4, "single-h1")]);
+
+        // Extra H1 sections are demoted
+        config.headings.single_h1 = RuleMode::Fix;
+        let (output, diagnostics) = format_document("# First\n## Sub\n# Second\n## Sub 2", &config);
+        assert_eq!(output, "# First\n\n## Sub\n\n## Second\n\n### Sub 2\n");
+        assert!(diagnostics.iter().all(|d| d.fixed));
+    }
+
+    #[test]
+    fn test_single_h1_from_front_matter_title() {
+        let mut config = MdFormatConfig::default();
+        config.headings.single_h1 = RuleMode::Fix;
+        config.headings.front_matter_title = true;
+
+        let input = "---\ntitle: \"User Guide\"\n---\n## Install";
+        let expected = "---\ntitle: \"User Guide\"\n---\n\n# User Guide\n\n## Install\n";
+        assert_eq!(format_markdown(input, &config), expected);
+        assert_eq!(format_markdown(expected, &config), expected);
+
+        // Without a title the missing H1 is only reported
+        let (output, diagnostics) = format_document("## Install", &config);
+        assert_eq!(output, "## Install\n");
+        assert_eq!(diagnostics[0].rule, "first-line-h1");
+    }
+
+    #[test]
+    fn test_heading_capitalization() {
+        let mut config = MdFormatConfig::default();
+        config.headings.capitalization = HeadingCase::Sentence;
+        config.headings.capitalization_exceptions = vec!["GitHub".to_string(), "Linux".to_string()];
+
+        let input = "# Getting Started With github\n## 1.2 using The API On linux\n## Run `Cargo Build` Now {#Run-It}\n## 中文 Title Here";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(
+            output,
+            "# Getting started with GitHub\n\n## 1.2 Using the API on Linux\n\n## Run `Cargo Build` now {#Run-It}\n\n## 中文 title here\n"
+        );
+        assert_eq!(diagnostics.len(), 4);
+        assert!(diagnostics.iter().all(|d| d.rule == "heading-case" && d.fixed));
+
+        config.headings.capitalization = HeadingCase::Title;
+        let output = format_markdown("# the lord of the rings\n## a guide to github and the iOS app", &config);
+        assert_eq!(output, "# The Lord of the Rings\n\n## A Guide to GitHub and the iOS App\n");
+    }
+
+    #[test]
+    fn test_heading_trailing_punctuation() {
+        let mut config = MdFormatConfig::default();
+        config.headings.trailing_punctuation = RuleMode::Warn;
+
+        let input = "# Overview:\n## 安装。 ##\n## Usage {#usage}\n## Setup. {#setup}\n## ...\n```\n# code.\n```";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, format_markdown(input, &MdFormatConfig::default()));
+        let lines = diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 2, 4]);
+        assert!(diagnostics.iter().all(|d| d.rule == "heading-punctuation"));
+
+        config.headings.trailing_punctuation = RuleMode::Fix;
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "# Overview\n\n## 安装 ##\n\n## Usage {#usage}\n\n## Setup {#setup}\n\n## ...\n\n```\n# code.\n```\n"
+        );
+
+        config.headings.trailing_punctuation_chars = "?".to_string();
+        let output = format_markdown("# Why?\n## Overview:", &config);
+        assert_eq!(output, "# Why\n\n## Overview:\n");
+    }
+
+    #[test]
+    fn test_bold_headings() {
+        let mut config = MdFormatConfig::default();
+        config.headings.bold_headings = RuleMode::Warn;
+
+        let input = "**Introduction**\n\ntext\n\n__安装__\n\n**not** a heading\n\n**inline** and **more**\n\ntext\n**in paragraph**\n\n```\n**code**\n```";
+        let (output, diagnostics) = format_document(input, &config);
+        assert_eq!(output, format_markdown(input, &MdFormatConfig::default()));
+        let lines = diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 5]);
+        assert!(diagnostics.iter().all(|d| d.rule == "bold-heading"));
+
+        config.headings.bold_headings = RuleMode::Fix;
+        config.headings.bold_heading_level = 3;
+        let output = format_markdown(input, &config);
+        assert!(output.starts_with("### Introduction\n\ntext\n\n### 安装\n\n**not** a heading\n"));
+        assert!(output.contains("text\n**in paragraph**\n"));
+        assert!(output.contains("```\n**code**\n```"));
+    }
+
+    #[test]
+    fn test_heading_blank_line_counts() {
+        let mut config = MdFormatConfig::default();
+        config.headings.blank_lines_before = vec![2, 2, 1];
+
+        let input = "# Title\ntext\n## Section\n\n\n\n### Sub\ntext\n#### Deep\n- item\n## Next";
+        let output = format_markdown(input, &config);
+        assert_eq!(
+            output,
+            "# Title\n\ntext\n\n\n## Section\n\n### Sub\n\ntext\n\n#### Deep\n\n- item\n\n\n## Next\n"
+        );
+
+        config.headings.blank_lines_after = 2;
+        let output = format_markdown("# Title\ntext", &config);
+        assert_eq!(output, "# Title\n\n\ntext\n");
+    }
+
+    #[test]
+    fn test_outline() {
+        let input = "---\n# not a heading\n---\n# Title\n\n### Deep\n## Usage {#use}\n```\n## code\n```\n## API ##\n# Appendix";
+        let outline = outline::build_outline(input, &MdFormatConfig::default());
+
+        let summary = |nodes: &[outline::OutlineNode]| {
+            nodes
+                .iter()
+                .map(|n| (n.level, n.text.clone(), n.slug.clone(), n.line, n.children.len()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&outline),
+            vec![(1, "Title".to_string(), "title".to_string(), 4, 3), (1, "Appendix".to_string(), "appendix".to_string(), 12, 0)]
+        );
+        assert_eq!(
+            summary(&outline[0].children),
+            vec![
+                (3, "Deep".to_string(), "deep".to_string(), 6, 0),
+                (2, "Usage".to_string(), "use".to_string(), 7, 0),
+                (2, "API".to_string(), "api".to_string(), 11, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outline_slugs_match_toc() {
+        // Texts and slugs are those of the formatted headings, line numbers those of the input
+        let input = "<!-- toc -->\n<!-- tocstop -->\n# 中文Title\n\n\n## API接口\n## API接口";
+        let outline = outline::build_outline(input, &MdFormatConfig::default());
+        let formatted = format_markdown(input, &MdFormatConfig::default());
+
+        let nodes = [&outline[0], &outline[0].children[0], &outline[0].children[1]];
+        assert_eq!(nodes.map(|n| n.text.as_str()), ["中文 Title", "API 接口", "API 接口"]);
+        assert_eq!(nodes.map(|n| n.line), [3, 6, 7]);
+        for node in nodes {
+            assert!(formatted.contains(&format!("](#{})", node.slug)), "{}", node.slug);
+        }
+    }
+
     // ===== Added: Configuration validation tests (3 tests) =====
     #[test]
     fn test_validate_invalid_unordered_marker() {
@@ -1640,6 +5835,16 @@ Some paragraph text.
         assert!(result.unwrap_err().to_string().contains("indent"));
     }
 
+    #[test]
+    fn test_validate_zero_tab_width() {
+        let mut config = MdFormatConfig::default();
+        config.lists.tab_width = 0;
+
+        let result = validate_config(&config);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("tab_width"));
+    }
+
     // ===== Added: Configuration combination tests (5 tests) =====
     #[test]
     fn test_complex_config_combination() {
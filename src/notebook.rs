@@ -0,0 +1,52 @@
+//! Jupyter notebooks: formatting of the Markdown cells, everything else being
+//! written back untouched
+
+use crate::{MdFormatConfig, format_markdown};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Whether the file is a Jupyter notebook, from its extension
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Format the source of the Markdown cells of the notebook, keeping the key
+/// order and the one-space indentation Jupyter writes
+pub fn format_notebook(content: &str, config: &MdFormatConfig) -> Result<String> {
+    let mut notebook: Value = serde_json::from_str(content).context("The input is not a valid notebook")?;
+    let cells = notebook
+        .get_mut("cells")
+        .and_then(Value::as_array_mut)
+        .context("The notebook has no cells")?;
+    for cell in cells {
+        if cell.get("cell_type").and_then(Value::as_str) != Some("markdown") {
+            continue;
+        }
+        let Some(source) = cell.get_mut("source") else {
+            continue;
+        };
+        // The source is a string or an array of lines keeping their "\n"
+        let text = match source {
+            Value::String(text) => text.clone(),
+            Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+            _ => continue,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let formatted = format_markdown(&text, config);
+        let formatted = formatted.trim_end_matches('\n');
+        *source = match source {
+            Value::String(_) => Value::String(formatted.to_string()),
+            _ => Value::Array(formatted.split_inclusive('\n').map(|line| Value::String(line.to_string())).collect()),
+        };
+    }
+
+    let mut output = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    notebook.serialize(&mut serde_json::Serializer::with_formatter(&mut output, formatter))?;
+    output.push(b'\n');
+    Ok(String::from_utf8(output)?)
+}
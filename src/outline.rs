@@ -0,0 +1,81 @@
+//! Document outline export: the heading tree as JSON or YAML
+
+use crate::{MdFormatConfig, anchors, format_markdown, front_matter_end};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Output format of the outline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutlineFormat {
+    Json,
+    Yaml,
+}
+
+/// A heading of the outline with its subsections
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutlineNode {
+    pub level: usize,
+    pub text: String,
+    pub slug: String,
+    /// Line number in the input (1-based)
+    pub line: usize,
+    pub children: Vec<OutlineNode>,
+}
+
+/// Build the heading tree of a document, skipping front matter and fenced code blocks
+///
+/// A heading is nested under the closest preceding heading of a lower level,
+/// so skipped levels don't create empty intermediate nodes. Texts and slugs are
+/// those of the formatted document, matching its TOC links, and line numbers
+/// those of the input.
+pub fn build_outline(text: &str, config: &MdFormatConfig) -> Vec<OutlineNode> {
+    let headings_of = |text: &str| {
+        let lines = text.lines().collect::<Vec<_>>();
+        let start = front_matter_end(&lines).map_or(0, |end| end + 1);
+        let headings = anchors::collect_heading_anchors(&lines[start..], config.anchors.style);
+        headings.into_iter().map(move |heading| (start + heading.index + 1, heading)).collect::<Vec<_>>()
+    };
+    let input = headings_of(text);
+    let formatted = headings_of(&format_markdown(text, config));
+    // Headings added or removed by the heading rules leave only the formatted lines
+    let same_headings = input.len() == formatted.len();
+
+    let mut roots: Vec<OutlineNode> = Vec::new();
+    // Open headings, each one a child of the previous
+    let mut stack: Vec<OutlineNode> = Vec::new();
+    for (i, (line, heading)) in formatted.into_iter().enumerate() {
+        while stack.last().is_some_and(|node| node.level >= heading.level) {
+            close_node(&mut stack, &mut roots);
+        }
+        stack.push(OutlineNode {
+            level: heading.level,
+            text: heading.text,
+            slug: heading.slug,
+            line: if same_headings { input[i].0 } else { line },
+            children: Vec::new(),
+        });
+    }
+    while !stack.is_empty() {
+        close_node(&mut stack, &mut roots);
+    }
+
+    roots
+}
+
+/// Pop the innermost open heading and attach it to its parent
+fn close_node(stack: &mut Vec<OutlineNode>, roots: &mut Vec<OutlineNode>) {
+    let node = stack.pop().unwrap();
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+/// Render the outline in the given format
+pub fn render_outline(outline: &[OutlineNode], format: OutlineFormat) -> Result<String> {
+    Ok(match format {
+        OutlineFormat::Json => serde_json::to_string_pretty(outline)? + "\n",
+        OutlineFormat::Yaml => serde_yaml::to_string(outline)?,
+    })
+}
@@ -0,0 +1,734 @@
+//! Alignment of GFM pipe tables, measuring cells in display columns
+
+use crate::{Diagnostic, MdFormatConfig, RuleMode, TableOptions, format_text};
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthStr;
+
+lazy_static! {
+    // Delimiter row cell: optional colons around at least one dash
+    static ref RE_DELIMITER_CELL: Regex = Regex::new(r"^:?-+:?$").unwrap();
+    // Table directive comment: 1: space-separated key=value settings
+    static ref RE_TABLE_DIRECTIVE: Regex = Regex::new(r"^\s*<!--\s*mdformat:\s*(.*?)\s*-->\s*$").unwrap();
+    // Sort directive value: 1: column (1-based), 2: options like ":num:desc"
+    static ref RE_SORT_BY: Regex = Regex::new(r"^(\d+)((?::(?:num|text|desc))*)$").unwrap();
+    // HTML table row: 1: row content
+    static ref RE_HTML_ROW: Regex = Regex::new(r"(?is)<tr\b[^>]*>(.*?)</tr>").unwrap();
+    // HTML table cell: 1: tag (th/td), 2: attributes, 3: content
+    static ref RE_HTML_CELL: Regex = Regex::new(r"(?is)<(th|td)\b([^>]*)>(.*?)</\1>").unwrap();
+    // Column alignment of an HTML cell: align="center" or style="text-align: center"
+    static ref RE_HTML_ALIGN: Regex = Regex::new(r"(?i)(?:align\s*=\s*|text-align\s*:\s*)[\x22']?(left|center|right)").unwrap();
+    // Cell spans, which pipe tables can't express
+    static ref RE_HTML_SPAN: Regex = Regex::new(r"(?i)\b(?:rowspan|colspan)\s*=").unwrap();
+    // Inline HTML with a Markdown equivalent inside cells
+    static ref RE_HTML_STRONG: Regex = Regex::new(r"(?is)<(strong|b)>(.*?)</\1>").unwrap();
+    static ref RE_HTML_EMPHASIS: Regex = Regex::new(r"(?is)<(em|i)>(.*?)</\1>").unwrap();
+    static ref RE_HTML_CODE: Regex = Regex::new(r"(?is)<code>(.*?)</code>").unwrap();
+    static ref RE_HTML_LINK: Regex = Regex::new(r#"(?is)<a\s+href\s*=\s*"([^"]*)"[^>]*>(.*?)</a>"#).unwrap();
+    static ref RE_HTML_PARAGRAPH_BREAK: Regex = Regex::new(r"(?i)</p>\s*<p\b[^>]*>").unwrap();
+    static ref RE_HTML_PARAGRAPH: Regex = Regex::new(r"(?i)</?p\b[^>]*>").unwrap();
+    // Pandoc table caption: "Table: ...", "table: ..." or ": ..."
+    static ref RE_TABLE_CAPTION: Regex = Regex::new(r"^\s*(?:[Tt]able)?:\s+\S").unwrap();
+    // Line break inside a cell
+    static ref RE_CELL_BREAK: Regex = Regex::new(r"(?i)<br\s*/?>").unwrap();
+}
+
+/// How the width of table cells is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellWidth {
+    /// Display columns: East Asian wide characters and emoji take two columns
+    Display,
+    /// Number of characters
+    Chars,
+}
+
+/// How the dashes of the delimiter row are emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DelimiterStyle {
+    /// Dashes fill the column width
+    Full,
+    /// Three dashes plus the alignment colons, whatever the column width
+    Minimal,
+}
+
+/// Column alignment set by the colons of the delimiter row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    /// Parse a delimiter row cell like `:---:`
+    fn parse(cell: &str) -> Option<Self> {
+        if !RE_DELIMITER_CELL.is_match(cell).unwrap_or(false) {
+            return None;
+        }
+        Some(match (cell.starts_with(':'), cell.ends_with(':') && cell.len() > 1) {
+            (true, true) => Alignment::Center,
+            (true, false) => Alignment::Left,
+            (false, true) => Alignment::Right,
+            (false, false) => Alignment::None,
+        })
+    }
+
+    /// Minimum width of the delimiter cell: at least three dashes and the colons
+    fn min_width(self) -> usize {
+        match self {
+            Alignment::None => 3,
+            Alignment::Left | Alignment::Right => 4,
+            Alignment::Center => 5,
+        }
+    }
+}
+
+/// A pipe table of the document
+struct Table {
+    /// Indentation and blockquote markers before the table rows
+    prefix: String,
+    alignments: Vec<Alignment>,
+    /// Header row followed by the data rows, without the delimiter row
+    rows: Vec<Vec<String>>,
+}
+
+/// How the data rows of a table are sorted, from a `sort-by=COL[:num|:text][:desc]` directive
+struct SortBy {
+    /// Column index (0-based)
+    column: usize,
+    /// Numeric comparison: forced on or off, detected from the values if unset
+    numeric: Option<bool>,
+    descending: bool,
+}
+
+impl SortBy {
+    fn parse(value: &str) -> Option<Self> {
+        let caps = RE_SORT_BY.captures(value).ok()??;
+        let column = caps[1].parse::<usize>().ok()?.checked_sub(1)?;
+        let options = caps[2].split(':').collect::<Vec<_>>();
+        let numeric = if options.contains(&"num") {
+            Some(true)
+        } else if options.contains(&"text") {
+            Some(false)
+        } else {
+            None
+        };
+        Some(SortBy {
+            column,
+            numeric,
+            descending: options.contains(&"desc"),
+        })
+    }
+
+    /// Stable sort of the data rows, rows missing the column sort first
+    fn sort(&self, rows: &mut [Vec<String>]) {
+        let number = |row: &Vec<String>| {
+            row.get(self.column)
+                .and_then(|cell| cell.replace(',', "").parse::<f64>().ok())
+        };
+        let numeric = self.numeric.unwrap_or_else(|| {
+            rows.iter()
+                .filter(|row| row.get(self.column).is_some_and(|cell| !cell.is_empty()))
+                .all(|row| number(row).is_some())
+        });
+        rows.sort_by(|a, b| {
+            let ordering = if numeric {
+                number(a)
+                    .unwrap_or(f64::NEG_INFINITY)
+                    .total_cmp(&number(b).unwrap_or(f64::NEG_INFINITY))
+            } else {
+                let text = |row: &Vec<String>| row.get(self.column).map(|cell| cell.to_lowercase());
+                text(a).cmp(&text(b))
+            };
+            if self.descending { ordering.reverse() } else { ordering }
+        });
+    }
+}
+
+/// Settings of the `<!-- mdformat: key=value -->` directive before the table
+/// starting at `lines[start]`, at most one blank line above it
+fn table_directive<'a>(lines: &[&'a str], start: usize) -> Vec<(&'a str, &'a str)> {
+    let above = lines[..start]
+        .iter()
+        .rev()
+        .take(2)
+        .find(|line| !line.trim().is_empty());
+    let Some(caps) = above.and_then(|line| RE_TABLE_DIRECTIVE.captures(line).ok().flatten()) else {
+        return Vec::new();
+    };
+    caps.get(1)
+        .unwrap()
+        .as_str()
+        .split_whitespace()
+        .filter_map(|setting| setting.split_once('='))
+        .collect()
+}
+
+/// Number of lines of the table starting at `lines[start]` if formatting is
+/// turned off for it by a `<!-- mdformat: table=off -->` directive
+pub fn disabled_table_len(lines: &[&str], start: usize) -> Option<usize> {
+    let (_, count) = parse_table(lines, start)?;
+    table_directive(lines, start)
+        .contains(&("table", "off"))
+        .then_some(count)
+}
+
+/// Width of a cell text in the configured unit
+fn cell_width(text: &str, options: &TableOptions) -> usize {
+    match options.cell_width {
+        CellWidth::Display => text.width(),
+        CellWidth::Chars => text.chars().count(),
+    }
+}
+
+/// Width a cell is padded to: that of its widest line when it was wrapped at
+/// `<br>` tags for exceeding the maximum column width
+fn rendered_width(text: &str, options: &TableOptions) -> usize {
+    let width = cell_width(text, options);
+    if !options.wrap_cells || options.max_column_width == 0 || width <= options.max_column_width {
+        return width;
+    }
+    let mut widest = 0;
+    let mut last = 0;
+    for m in RE_CELL_BREAK.find_iter(text).flatten() {
+        widest = widest.max(cell_width(&text[last..m.start()], options));
+        last = m.end();
+    }
+    widest.max(cell_width(&text[last..], options))
+}
+
+/// Line break tag of a cell in the configured style, or as written if preserved
+fn line_break_tag<'a>(tag: &'a str, options: &'a TableOptions) -> &'a str {
+    if options.line_break == "preserve" { tag } else { &options.line_break }
+}
+
+/// Split a cell text into the units it may be wrapped between: words, code
+/// spans and single wide (CJK) characters, with whether a space precedes them
+fn wrap_units(text: &str, options: &TableOptions) -> Vec<(bool, String)> {
+    let mut units: Vec<(bool, String)> = Vec::new();
+    let mut unit = String::new();
+    let mut space = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            if !unit.is_empty() {
+                units.push((space, std::mem::take(&mut unit)));
+            }
+            space = true;
+        } else if c == '`' {
+            // Code spans are never broken
+            unit.push(c);
+            for c in chars.by_ref() {
+                unit.push(c);
+                if c == '`' {
+                    break;
+                }
+            }
+        } else if cell_width(&c.to_string(), options) > 1 {
+            if !unit.is_empty() {
+                units.push((space, std::mem::take(&mut unit)));
+                space = false;
+            }
+            units.push((space, c.to_string()));
+            space = false;
+        } else {
+            unit.push(c);
+        }
+    }
+    if !unit.is_empty() {
+        units.push((space, unit));
+    }
+    units
+}
+
+/// Wrap a cell text to `max_width` columns with `<br>` line breaks,
+/// keeping the existing line breaks
+fn wrap_cell(text: &str, max_width: usize, options: &TableOptions) -> String {
+    let mut wrapped = String::new();
+    let mut last = 0;
+    let segments = RE_CELL_BREAK
+        .find_iter(text)
+        .flatten()
+        .map(|m| {
+            let segment = (&text[last..m.start()], m.as_str());
+            last = m.end();
+            segment
+        })
+        .collect::<Vec<_>>();
+
+    for (segment, line_break) in segments.into_iter().chain([(&text[last..], "")]) {
+        let mut width = 0;
+        for (i, (space, unit)) in wrap_units(segment.trim(), options).into_iter().enumerate() {
+            let unit_width = cell_width(&unit, options);
+            if i > 0 && width + usize::from(space) + unit_width > max_width {
+                wrapped.push_str(line_break_tag("<br>", options));
+                width = 0;
+            } else if space {
+                wrapped.push(' ');
+                width += 1;
+            }
+            wrapped.push_str(&unit);
+            width += unit_width;
+        }
+        wrapped.push_str(line_break);
+    }
+    wrapped
+}
+
+/// Length of the backtick run at the start of `chars`
+fn backtick_run(chars: &[char]) -> usize {
+    chars.iter().take_while(|&&c| c == '`').count()
+}
+
+/// Split a table row into its trimmed cells
+///
+/// Leading and trailing pipes are optional. Escaped pipes `\|` and pipes
+/// inside code spans do not split cells.
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    if line.is_empty() || line == "|" {
+        return Vec::new();
+    }
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let chars = line.chars().collect::<Vec<_>>();
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut escaped = false;
+    let mut closed = false;
+    let mut i = 0;
+    while i < chars.len() {
+        closed = false;
+        let c = chars[i];
+        if c == '`' && !escaped {
+            // Copy a code span through to its closing backtick run of the same length
+            let run = backtick_run(&chars[i..]);
+            let mut end = i + run;
+            let close = loop {
+                match chars[end..].iter().position(|&c| c == '`') {
+                    Some(offset) => {
+                        let start = end + offset;
+                        let len = backtick_run(&chars[start..]);
+                        if len == run {
+                            break Some(start + len);
+                        }
+                        end = start + len;
+                    }
+                    None => break None,
+                }
+            };
+            let end = close.unwrap_or(i + run);
+            cell.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+        if c == '|' && !escaped {
+            cells.push(cell.trim().to_string());
+            cell.clear();
+            closed = true;
+            i += 1;
+            continue;
+        }
+        // Pairs of backslashes cancel each other out
+        escaped = c == '\\' && !escaped;
+        cell.push(c);
+        i += 1;
+    }
+    // The last cell is closed by a trailing pipe
+    if !closed {
+        cells.push(cell.trim().to_string());
+    }
+    cells
+}
+
+/// Apply inline formatting to a cell, each line between `<br>` tags on its own
+fn format_cell(cell: &str, config: &MdFormatConfig) -> String {
+    let options = &config.tables;
+    let mut formatted = String::new();
+    let mut last = 0;
+    for m in RE_CELL_BREAK.find_iter(cell).flatten() {
+        formatted.push_str(format_text(cell[last..m.start()].trim(), config).as_str());
+        formatted.push_str(line_break_tag(m.as_str(), options));
+        last = m.end();
+    }
+    formatted.push_str(format_text(cell[last..].trim(), config).as_str());
+    formatted
+}
+
+/// Apply inline formatting to each cell of a table row, so spacing rules
+/// never see the pipes between cells
+pub fn format_row(line: &str, config: &MdFormatConfig) -> String {
+    let prefix = line_prefix(line);
+    let row = &line[prefix.len()..];
+    if parse_delimiter_row(row).is_some() {
+        return line.to_string();
+    }
+    let cells = split_row(row)
+        .iter()
+        .map(|cell| format_cell(cell, config))
+        .collect::<Vec<_>>();
+    format!("{}| {} |", prefix, cells.join(" | "))
+}
+
+/// Whether the line is a Pandoc table caption, kept attached to the table above it
+pub fn is_caption(line: &str) -> bool {
+    RE_TABLE_CAPTION.is_match(line).unwrap_or(false)
+}
+
+/// Parse the delimiter row of a table, returning the column alignments
+fn parse_delimiter_row(line: &str) -> Option<Vec<Alignment>> {
+    if !line.contains('-') {
+        return None;
+    }
+    split_row(line).iter().map(|cell| Alignment::parse(cell)).collect()
+}
+
+/// Whether the line is the delimiter row of a table (`---|:---:`)
+pub fn is_delimiter_row(line: &str) -> bool {
+    parse_delimiter_row(line).is_some()
+}
+
+/// Indentation and blockquote markers at the start of a line
+fn line_prefix(line: &str) -> &str {
+    let end = line.find(|c| c != ' ' && c != '>').unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Parse the table starting at `lines[start]`, returning it with the number of lines it spans
+fn parse_table(lines: &[&str], start: usize) -> Option<(Table, usize)> {
+    let header = lines[start];
+    let prefix = line_prefix(header);
+    if !header.contains('|') {
+        return None;
+    }
+    let delimiter = lines.get(start + 1)?.strip_prefix(prefix)?;
+    let alignments = parse_delimiter_row(delimiter)?;
+    let header_cells = split_row(&header[prefix.len()..]);
+    if header_cells.len() != alignments.len() {
+        return None;
+    }
+
+    let mut rows = vec![header_cells];
+    for line in &lines[start + 2..] {
+        match line.strip_prefix(prefix) {
+            Some(row) if line_prefix(line) == prefix && row.contains('|') && !is_caption(row) => {
+                rows.push(split_row(row))
+            }
+            _ => break,
+        }
+    }
+    let count = rows.len() + 1;
+
+    Some((
+        Table {
+            prefix: prefix.to_string(),
+            alignments,
+            rows,
+        },
+        count,
+    ))
+}
+
+/// Pad the cell text to `width` according to the column alignment
+fn align_cell(text: &str, alignment: Alignment, width: usize, options: &TableOptions) -> String {
+    let padding = width.saturating_sub(rendered_width(text, options));
+    let (left, right) = match alignment {
+        Alignment::Right => (padding, 0),
+        Alignment::Center => (padding / 2, padding - padding / 2),
+        Alignment::None | Alignment::Left => (0, padding),
+    };
+    format!(" {}{}{} ", " ".repeat(left), text, " ".repeat(right))
+}
+
+/// Delimiter row cell of the given width
+fn delimiter_cell(alignment: Alignment, width: usize) -> String {
+    match alignment {
+        Alignment::None => format!(" {} ", "-".repeat(width)),
+        Alignment::Left => format!(" :{} ", "-".repeat(width - 1)),
+        Alignment::Right => format!(" {}: ", "-".repeat(width - 1)),
+        Alignment::Center => format!(" :{}: ", "-".repeat(width - 2)),
+    }
+}
+
+/// Render the table with the cells of each column padded to the same width
+fn render_table(table: &mut Table, options: &TableOptions) -> Vec<String> {
+    if options.ragged_rows == RuleMode::Fix {
+        for row in table.rows.iter_mut() {
+            if row.len() < table.alignments.len() {
+                row.resize(table.alignments.len(), String::new());
+            }
+        }
+    }
+
+    let max_width = options.max_column_width;
+    if max_width > 0 && options.wrap_cells {
+        for row in table.rows.iter_mut().skip(1) {
+            for cell in row.iter_mut() {
+                if cell_width(cell, options) > max_width {
+                    *cell = wrap_cell(cell, max_width, options);
+                }
+            }
+        }
+    }
+
+    // Cells wider than the maximum column width are left unpadded
+    let widths = table
+        .alignments
+        .iter()
+        .enumerate()
+        .map(|(column, alignment)| {
+            table
+                .rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| rendered_width(cell, options))
+                .filter(|&width| max_width == 0 || width <= max_width)
+                .max()
+                .unwrap_or(0)
+                .max(alignment.min_width())
+        })
+        .collect::<Vec<_>>();
+
+    let render_row = |row: &Vec<String>| {
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                // Extra cells beyond the delimiter row are not aligned
+                let alignment = table.alignments.get(column).copied().unwrap_or(Alignment::None);
+                let width = widths.get(column).copied().unwrap_or(0);
+                align_cell(cell, alignment, width, options)
+            })
+            .collect::<Vec<_>>();
+        format!("{}|{}|", table.prefix, cells.join("|"))
+    };
+
+    let mut lines = table.rows.iter().map(render_row).collect::<Vec<_>>();
+    let delimiter = table
+        .alignments
+        .iter()
+        .zip(&widths)
+        .map(|(&alignment, &width)| match options.delimiter_style {
+            DelimiterStyle::Full => delimiter_cell(alignment, width),
+            DelimiterStyle::Minimal => delimiter_cell(alignment, alignment.min_width()),
+        })
+        .collect::<Vec<_>>();
+    lines.insert(1, format!("{}|{}|", table.prefix, delimiter.join("|")));
+    lines
+}
+
+/// Report table rows with a different number of cells than the header
+///
+/// Short rows are padded with empty cells in fix mode, extra cells are
+/// always reported since removing them would lose content.
+pub fn check_ragged_rows(input: &str, config: &MdFormatConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let mode = config.tables.ragged_rows;
+    if mode == RuleMode::Ignore || !config.formatting.format_tables {
+        return;
+    }
+
+    let lines = input.lines().collect::<Vec<_>>();
+    let mut in_code = false;
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code && let Some((table, count)) = parse_table(&lines, i) {
+            if disabled_table_len(&lines, i).is_some() {
+                i += count;
+                continue;
+            }
+            let columns = table.alignments.len();
+            for (r, row) in table.rows.iter().enumerate().skip(1) {
+                if row.len() != columns {
+                    diagnostics.push(Diagnostic {
+                        line: i + r + 2,
+                        rule: "table-ragged-row",
+                        message: format!("table row has {} cells, expected {}", row.len(), columns),
+                        fixed: mode == RuleMode::Fix && row.len() < columns,
+                    });
+                }
+            }
+            i += count;
+            continue;
+        }
+        i += 1;
+    }
+}
+
+/// Align all pipe tables of the text, skipping fenced code blocks
+pub fn format_tables(text: &str, config: &MdFormatConfig) -> String {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let mut ret: Vec<String> = Vec::with_capacity(lines.len());
+    let mut in_code = false;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code && let Some((mut table, count)) = parse_table(&lines, i) {
+            for (key, value) in table_directive(&lines, i) {
+                if key == "sort-by"
+                    && let Some(sort_by) = SortBy::parse(value)
+                {
+                    sort_by.sort(&mut table.rows[1..]);
+                }
+            }
+            ret.extend(render_table(&mut table, &config.tables));
+            i += count;
+            continue;
+        }
+        ret.push(line.to_string());
+        i += 1;
+    }
+
+    ret.join("\n")
+}
+
+/// Convert the content of an HTML cell into inline Markdown on a single line
+fn html_cell_to_markdown(html: &str) -> String {
+    let text = html.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = RE_HTML_PARAGRAPH_BREAK.replace_all(&text, "<br>");
+    let text = RE_HTML_PARAGRAPH.replace_all(&text, "");
+    let text = RE_HTML_CODE.replace_all(&text, "`$1`");
+    let text = RE_HTML_STRONG.replace_all(&text, "**$2**");
+    let text = RE_HTML_EMPHASIS.replace_all(&text, "*$2*");
+    let text = RE_HTML_LINK.replace_all(&text, "[$2]($1)");
+    text.trim().replace('|', "\\|")
+}
+
+/// Convert an HTML `<table>` block into pipe table lines
+///
+/// Only simple tables are converted: the first row must be a header of `<th>`
+/// cells, all rows must have the same number of cells and no cell may span
+/// rows or columns.
+fn html_table_to_pipe_table(html: &str) -> Option<Vec<String>> {
+    if RE_HTML_SPAN.is_match(html).unwrap_or(true) || html.to_lowercase().matches("<table").count() != 1 {
+        return None;
+    }
+
+    let mut rows = Vec::new();
+    let mut alignments = Vec::new();
+    for (r, row) in RE_HTML_ROW.captures_iter(html).flatten().enumerate() {
+        let mut cells = Vec::new();
+        for cell in RE_HTML_CELL.captures_iter(&row[1]).flatten() {
+            let header = cell[1].eq_ignore_ascii_case("th");
+            if header != (r == 0) {
+                return None;
+            }
+            if r == 0 {
+                let align = RE_HTML_ALIGN.captures(&cell[2]).ok().flatten();
+                alignments.push(match align.map(|caps| caps[1].to_lowercase()).as_deref() {
+                    Some("left") => ":---",
+                    Some("center") => ":---:",
+                    Some("right") => "---:",
+                    _ => "---",
+                });
+            }
+            cells.push(html_cell_to_markdown(&cell[3]));
+        }
+        if cells.is_empty() || rows.first().is_some_and(|first: &Vec<String>| first.len() != cells.len()) {
+            return None;
+        }
+        rows.push(cells);
+    }
+    if rows.is_empty() {
+        return None;
+    }
+
+    let mut lines = rows.iter().map(|row| format!("| {} |", row.join(" | "))).collect::<Vec<_>>();
+    lines.insert(1, format!("| {} |", alignments.join(" | ")));
+    Some(lines)
+}
+
+/// Replace simple HTML tables with pipe tables, skipping fenced code blocks,
+/// returning the index of the input line each output line comes from
+pub fn convert_html_tables(lines: &mut Vec<String>) -> Vec<usize> {
+    let mut converted = Vec::with_capacity(lines.len());
+    let mut origins = Vec::with_capacity(lines.len());
+    let mut in_code = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code && line.trim_start().to_lowercase().starts_with("<table") {
+            let end = (i..lines.len()).find(|&j| lines[j].to_lowercase().contains("</table>"));
+            if let Some(end) = end
+                && let Some(table) = html_table_to_pipe_table(&lines[i..=end].join("\n"))
+            {
+                origins.resize(origins.len() + table.len(), i);
+                converted.extend(table);
+                i = end + 1;
+                continue;
+            }
+        }
+        converted.push(line.clone());
+        origins.push(i);
+        i += 1;
+    }
+    *lines = converted;
+    origins
+}
+
+/// Parse delimited text (CSV, TSV) into rows of fields
+///
+/// Fields may be quoted with `"`, with `""` standing for a quote; line
+/// breaks inside quoted fields become `<br>`.
+fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            '\r' if !quoted => {}
+            '\n' if quoted => field.push_str("<br>"),
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c if c == delimiter && !quoted => row.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    rows
+}
+
+/// Convert delimited text into an aligned pipe table, the first row being the header
+pub fn from_delimited(text: &str, delimiter: char, config: &MdFormatConfig) -> Vec<String> {
+    let mut rows = parse_delimited(text, delimiter)
+        .into_iter()
+        .map(|row| row.iter().map(|field| field.trim().replace('|', "\\|")).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let Some(columns) = rows.iter().map(Vec::len).max() else {
+        return Vec::new();
+    };
+    for row in rows.iter_mut() {
+        row.resize(columns, String::new());
+    }
+
+    let mut table = Table {
+        prefix: String::new(),
+        alignments: vec![Alignment::None; columns],
+        rows,
+    };
+    render_table(&mut table, &config.tables)
+}
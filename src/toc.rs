@@ -0,0 +1,110 @@
+//! Table of contents generation between `<!-- toc -->` and `<!-- tocstop -->` markers
+
+use crate::{MdFormatConfig, anchors};
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref RE_TOC_START: Regex = Regex::new(r"(?i)^<!--\s*toc\s*-->$").unwrap();
+    static ref RE_TOC_STOP: Regex = Regex::new(r"(?i)^<!--\s*tocstop\s*-->$").unwrap();
+}
+
+/// A heading listed in the table of contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct TocEntry {
+    pub level: usize,
+    pub text: String,
+    pub slug: String,
+}
+
+/// Collect the headings of formatted lines, skipping fenced code blocks,
+/// headings outside the configured levels and headings with the ignore marker
+///
+/// Anchors are assigned over all headings, so duplicate suffixes match the
+/// rendered document even when some headings are not listed.
+pub fn collect_headings<S: AsRef<str>>(lines: &[S], config: &MdFormatConfig) -> Vec<TocEntry> {
+    let levels = config.toc.min_level as usize..=config.toc.max_level as usize;
+    let ignore_marker = config.toc.ignore_marker.as_str();
+
+    anchors::collect_heading_anchors(lines, config.anchors.style)
+        .into_iter()
+        .filter(|heading| {
+            levels.contains(&heading.level)
+                && (ignore_marker.is_empty() || !heading.text.ends_with(ignore_marker))
+        })
+        .map(|heading| TocEntry {
+            level: heading.level,
+            text: heading.text,
+            slug: heading.slug,
+        })
+        .collect()
+}
+
+/// Render the table of contents as a nested list of links
+pub fn render_toc(entries: &[TocEntry], config: &MdFormatConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    // (level, item counter, content column) of the enclosing entries, so
+    // skipped levels don't jump indentation
+    let mut stack: Vec<(usize, usize, usize)> = Vec::new();
+
+    for entry in entries {
+        let mut counter = 1;
+        while let Some(&(level, count, _)) = stack.last() {
+            if level < entry.level {
+                break;
+            }
+            if level == entry.level {
+                counter = count + 1;
+            }
+            stack.pop();
+        }
+        let marker = if config.toc.ordered {
+            format!("{}.", counter)
+        } else {
+            config.lists.unordered_marker.clone()
+        };
+        // Children start at the content of their parent: past "1. " for ordered items
+        let indent = stack.last().map_or(0, |&(_, _, column)| column);
+        lines.push(format!("{}{} [{}](#{})", " ".repeat(indent), marker, entry.text, entry.slug));
+        stack.push((entry.level, counter, indent + config.lists.indent.max(marker.len() + 1)));
+    }
+
+    lines
+}
+
+/// Regenerate the table of contents between the TOC markers
+///
+/// A start marker without a matching stop marker gets one inserted after the
+/// generated list.
+pub fn update_toc(text: &str, config: &MdFormatConfig) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    let Some(start) = lines
+        .iter()
+        .position(|line| RE_TOC_START.is_match(line).unwrap_or(false))
+    else {
+        return text.to_string();
+    };
+    let stop = lines[start + 1..]
+        .iter()
+        .position(|line| RE_TOC_STOP.is_match(line).unwrap_or(false))
+        .map(|pos| start + 1 + pos);
+
+    let after = stop.map_or(start + 1, |stop| stop + 1);
+    let mut outside = lines[..start].to_vec();
+    outside.extend_from_slice(&lines[after..]);
+    let toc = render_toc(&collect_headings(&outside, config), config);
+
+    let mut result = lines[..=start].iter().map(|line| line.to_string()).collect::<Vec<_>>();
+    result.push(String::new());
+    if !toc.is_empty() {
+        result.extend(toc);
+        result.push(String::new());
+    }
+    result.push(stop.map_or("<!-- tocstop -->", |stop| lines[stop]).to_string());
+    if after < lines.len() && !lines[after].is_empty() {
+        result.push(String::new());
+    }
+    result.extend(lines[after..].iter().map(|line| line.to_string()));
+
+    result.join("\n")
+}
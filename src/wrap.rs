@@ -0,0 +1,410 @@
+//! Reflowing of prose paragraphs to a maximum width, one sentence per line or
+//! one line per paragraph, breaking lines between CJK characters without spaces
+//! and following the kinsoku rules
+
+use crate::{LineState, MdFormatConfig, WrapMode, get_line_state, inline, is_block_attribute, tables};
+use fancy_regex::Regex;
+use lazy_static::lazy_static;
+use std::ops::Range;
+use unicode_script::{Script, UnicodeScript};
+use unicode_width::UnicodeWidthStr;
+
+lazy_static! {
+    // Spans never broken across lines: code spans, HTML tags, autolinks and placeholders
+    static ref RE_ATOMIC_SPAN: Regex = Regex::new(r"(`+).+?(?<!`)\1(?!`)|<[^<>\n]+>|\x00[^\x00]*\x00").unwrap();
+    // Words that would start a list item, heading, blockquote, table row, HTML
+    // block, code fence or setext underline at the start of a line
+    static ref RE_BLOCK_START: Regex = Regex::new(r"^(?:[-+*]|\d+[.)]|=+|-+|[#>|<].*|`{3,}.*|~{3,}.*)$").unwrap();
+    // Start of an HTML block: an opening or closing tag, or a comment
+    static ref RE_HTML_BLOCK_START: Regex = Regex::new(r"^(?:</?[A-Za-z][A-Za-z0-9-]*(?:[\s/>]|$)|<!--)").unwrap();
+    // Link reference or footnote definition
+    static ref RE_DEFINITION: Regex = Regex::new(r"^\[[^\]]+\]:").unwrap();
+    // Line ending with a hard break (backslash or <br>)
+    static ref RE_HARD_BREAK: Regex = Regex::new(r"(?:\\|<br\s*/?>)$").unwrap();
+    // Blockquote markers, possibly nested
+    static ref RE_BLOCKQUOTE_PREFIX: Regex = Regex::new(r"^(?:> ?)+").unwrap();
+    // List item marker with its indentation and task checkbox
+    static ref RE_LIST_ITEM_PREFIX: Regex = Regex::new(r"^ *(?:[-+*]|\d+[.)]) +(?:\[[ xX]\] +)?(?=\S)").unwrap();
+}
+
+/// Characters never starting a line (closing punctuation, small kana, the
+/// prolonged sound mark)
+const NO_LINE_START: &str = "，。、；：？！」』）】》〉〕．…‥ー・ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶ";
+/// Characters never ending a line (opening punctuation)
+const NO_LINE_END: &str = "「『（【《〈〔“‘";
+/// Closing quotes and brackets that may follow the end of a sentence
+const SENTENCE_CLOSING: &[char] = &['"', '\'', ')', ']', '”', '’', '」', '』', '）'];
+
+/// Parse the `--wrap` policy: "no", "preserve", "sentence" or a width
+pub fn parse_policy(policy: &str) -> Result<(WrapMode, Option<usize>), String> {
+    match policy {
+        "no" => Ok((WrapMode::No, None)),
+        "preserve" => Ok((WrapMode::Preserve, None)),
+        "sentence" => Ok((WrapMode::Sentence, None)),
+        _ => match policy.parse::<usize>() {
+            Ok(width) if width > 0 => Ok((WrapMode::Width, Some(width))),
+            _ => Err(format!("invalid wrap policy '{}' (expected no, preserve, sentence or a width)", policy)),
+        },
+    }
+}
+
+/// Whether lines may break next to the character without a space: Chinese and
+/// Japanese characters and full-width punctuation
+fn is_cjk(c: char) -> bool {
+    matches!(c.script(), Script::Han | Script::Hiragana | Script::Katakana)
+        || inline::is_fullwidth_punctuation(c)
+        || ('\u{3000}'..='\u{303F}').contains(&c)
+        || ('\u{FF00}'..='\u{FFEF}').contains(&c)
+        || NO_LINE_START.contains(c)
+        || NO_LINE_END.contains(c)
+}
+
+/// How a word is joined to the previous one
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Glue {
+    /// By a space, where the line may break
+    Space,
+    /// Directly, the line may break between the CJK characters
+    Cjk,
+}
+
+/// Split the text into words that are never broken, each with its glue to the
+/// previous word
+fn split_words(text: &str) -> Vec<(String, Glue)> {
+    let mut words: Vec<(String, Glue)> = Vec::new();
+    let mut word = String::new();
+    let mut glue = Glue::Space;
+    let mut space = false;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            space = true;
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let chunk = match RE_ATOMIC_SPAN.find(rest) {
+            Ok(Some(m)) if m.start() == 0 => m.as_str(),
+            _ => &rest[..c.len_utf8()],
+        };
+        rest = &rest[chunk.len()..];
+        let first = chunk.chars().next().unwrap_or(c);
+        let breaks = match word.chars().next_back() {
+            _ if space => Some(Glue::Space),
+            Some(last) if is_cjk(last) && is_cjk(first) => {
+                (!NO_LINE_START.contains(first) && !NO_LINE_END.contains(last)).then_some(Glue::Cjk)
+            }
+            _ => None,
+        };
+        if let Some(next_glue) = breaks
+            && !word.is_empty()
+        {
+            words.push((std::mem::take(&mut word), glue));
+            glue = next_glue;
+        }
+        space = false;
+        word.push_str(chunk);
+    }
+    if !word.is_empty() {
+        words.push((word, glue));
+    }
+    words
+}
+
+/// Fill lines of at most `width` columns with the words of the text, breaking
+/// only where a word may start a line without changing the document structure
+pub fn fill(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for (word, glue) in split_words(text) {
+        let separator = if glue == Glue::Space { " " } else { "" };
+        let fits = line.width() + separator.len() + word.width() <= width;
+        if line.is_empty() {
+            line = word;
+        } else if fits || RE_BLOCK_START.is_match(&word).unwrap_or(false) {
+            line.push_str(separator);
+            line.push_str(&word);
+        } else {
+            lines.push(std::mem::take(&mut line));
+            line = word;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Put each sentence of the text on its own line, breaking after full stops,
+/// question and exclamation marks (ASCII or CJK) and their closing quotes when
+/// the next word does not start in lowercase
+pub fn sentences(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut sentence_end = false;
+    for (word, glue) in split_words(text) {
+        let lowercase = word.starts_with(char::is_lowercase);
+        if line.is_empty() {
+            line = word;
+        } else if sentence_end && !lowercase && !RE_BLOCK_START.is_match(&word).unwrap_or(false) {
+            lines.push(std::mem::replace(&mut line, word));
+        } else {
+            if glue == Glue::Space {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        let last = line.trim_end_matches(SENTENCE_CLOSING).chars().next_back();
+        sentence_end = last.is_some_and(|c| ".!?。！？".contains(c));
+    }
+    lines.push(line);
+    lines
+}
+
+/// Whether each line ends with a trailing-space hard break, two spaces followed
+/// by a non-blank line
+pub fn trailing_space_breaks(lines: &[&str]) -> Vec<bool> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            line.ends_with("  ")
+                && !line.trim().is_empty()
+                && lines.get(i + 1).is_some_and(|next| !next.trim().is_empty())
+        })
+        .collect()
+}
+
+/// Replace the trailing-space hard breaks of prose lines, as found by
+/// [`trailing_space_breaks`], with backslashes, which survive the trimming of
+/// trailing whitespace and the rewrapping
+///
+/// Only lines followed by another line of their paragraph are marked: code,
+/// HTML blocks, headings, tables and placeholders are left alone.
+pub fn mark_hard_breaks(lines: &[&str], hard_breaks: &[bool]) -> Vec<String> {
+    each_paragraph(lines, |range, paragraph, _, _, result| {
+        let last = range.end - 1;
+        for i in range {
+            let line = lines[i];
+            if hard_breaks[i] && i < last && !is_structural(paragraph) && !line.ends_with('\\') {
+                result.push(format!("{}\\", line));
+            } else {
+                result.push(line.to_string());
+            }
+        }
+    })
+}
+
+/// Join the lines of a paragraph, without a space between two CJK characters
+pub fn join(lines: &[&str]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        let line = line.trim();
+        let cjk_join = text.chars().next_back().is_some_and(is_cjk) && line.chars().next().is_some_and(is_cjk);
+        if !text.is_empty() && !cjk_join {
+            text.push(' ');
+        }
+        text.push_str(line);
+    }
+    text
+}
+
+/// Whether the line may be part of a wrapped paragraph
+fn is_prose(line: &str, state: &LineState, prev_state: &LineState) -> bool {
+    let caption = *prev_state == LineState::Table && tables::is_caption(line);
+    *state == LineState::Normal
+        && !caption
+        && !line.starts_with(char::is_whitespace)
+        && !line.starts_with('<')
+        && !is_block_attribute(line)
+        && !RE_DEFINITION.is_match(line).unwrap_or(false)
+}
+
+/// Whether the content of a list item or blockquote line, without its
+/// container prefix, may be part of a wrapped paragraph
+fn is_nested_prose(content: &str) -> bool {
+    !content.is_empty() && is_prose(content, &get_line_state(content, LineState::Normal), &LineState::Normal)
+}
+
+/// Whether the line opens or closes an indented code fence
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Whether the paragraph is a setext heading or a pipe-less table
+fn is_structural(paragraph: &[&str]) -> bool {
+    paragraph.iter().any(|line| {
+        let line = line.trim();
+        line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-') || tables::is_delimiter_row(line)
+    })
+}
+
+/// Rewrap the lines of a paragraph according to the mode, prefixing the first output line with
+/// `first_prefix` and the others with `prefix`
+///
+/// Hard breaks end a run of reflowed lines; paragraphs that are setext
+/// headings or pipe-less tables are left alone.
+fn wrap_paragraph(paragraph: &[&str], first_prefix: &str, prefix: &str, config: &MdFormatConfig, result: &mut Vec<String>) {
+    let mut lines = Vec::new();
+    if is_structural(paragraph) {
+        lines.extend(paragraph.iter().map(|line| line.trim().to_string()));
+    } else {
+        // Hard breaks are kept at the end of their line
+        let width = config.wrap.width.saturating_sub(prefix.width()).max(1);
+        let mut start = 0;
+        for (j, line) in paragraph.iter().enumerate() {
+            if RE_HARD_BREAK.is_match(line).unwrap_or(false) || j == paragraph.len() - 1 {
+                let text = join(&paragraph[start..=j]);
+                match config.wrap.mode {
+                    WrapMode::Width => lines.extend(fill(&text, width)),
+                    WrapMode::Sentence => lines.extend(sentences(&text)),
+                    WrapMode::Preserve | WrapMode::No => lines.push(text),
+                }
+                start = j + 1;
+            }
+        }
+    }
+    for (j, line) in lines.into_iter().enumerate() {
+        let prefix = if j == 0 { first_prefix } else { prefix };
+        result.push(format!("{}{}", prefix, line).trim_end().to_string());
+    }
+}
+
+/// Rewrap the prose paragraphs of the document according to the configured mode,
+/// including those of list items and blockquotes, whose continuation lines
+/// are indented under the item content or keep the quote markers
+pub fn wrap_paragraphs(text: &str, config: &MdFormatConfig) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    each_paragraph(&lines, |_, paragraph, first_prefix, prefix, result| {
+        wrap_paragraph(paragraph, first_prefix, prefix, config, result)
+    })
+    .join("\n")
+}
+
+/// Go through the prose paragraphs of the lines, including those of list items
+/// and blockquotes, passing each one to `visit` with the range of its lines,
+/// their content without the container prefix and the prefixes of its first
+/// and other lines; the other lines are kept as they are
+fn each_paragraph<F>(lines: &[&str], mut visit: F) -> Vec<String>
+where
+    F: FnMut(Range<usize>, &[&str], &str, &str, &mut Vec<String>),
+{
+    let mut result: Vec<String> = Vec::with_capacity(lines.len());
+    let mut state = LineState::Empty;
+    // Content column of the list item the indented lines belong to
+    let mut list_indent = None;
+    let mut in_fence = false;
+    let mut in_html = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let prev_state = state.clone();
+        state = get_line_state(line, prev_state.clone());
+        let indent = line.len() - line.trim_start_matches(' ').len();
+
+        // Fenced code indented in a list item
+        if in_fence || (indent > 0 && is_fence(line)) {
+            in_fence ^= is_fence(line);
+            result.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        // HTML blocks run until the next blank line, their raw lines untouched
+        if in_html || RE_HTML_BLOCK_START.is_match(line.trim_start()).unwrap_or(false) {
+            in_html = !line.trim().is_empty();
+            result.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        if state == LineState::Blockquote
+            && let Ok(Some(m)) = RE_BLOCKQUOTE_PREFIX.find(line)
+            && is_nested_prose(&line[m.end()..])
+        {
+            let marker = m.as_str().trim_end();
+            let mut end = i + 1;
+            while end < lines.len() {
+                match RE_BLOCKQUOTE_PREFIX.find(lines[end]) {
+                    Ok(Some(next)) if next.as_str().trim_end() == marker && is_nested_prose(&lines[end][next.end()..]) => {
+                        end += 1
+                    }
+                    _ => break,
+                }
+            }
+            let paragraph = lines[i..end]
+                .iter()
+                .map(|line| &line[RE_BLOCKQUOTE_PREFIX.find(line).ok().flatten().map_or(0, |m| m.end())..])
+                .collect::<Vec<_>>();
+            let prefix = format!("{} ", marker);
+            visit(i..end, &paragraph, &prefix, &prefix, &mut result);
+            list_indent = None;
+            i = end;
+            continue;
+        }
+
+        if state == LineState::List
+            && let Ok(Some(m)) = RE_LIST_ITEM_PREFIX.find(line)
+        {
+            list_indent = Some(m.as_str().width());
+            if !is_nested_prose(&line[m.end()..]) {
+                result.push(line.to_string());
+                i += 1;
+                continue;
+            }
+            // Indented or lazy continuation lines
+            let mut end = i + 1;
+            while end < lines.len()
+                && is_nested_prose(lines[end].trim_start())
+                && !RE_LIST_ITEM_PREFIX.is_match(lines[end]).unwrap_or(false)
+                && !is_fence(lines[end])
+            {
+                end += 1;
+            }
+            let mut paragraph = lines[i..end].iter().map(|line| line.trim_start()).collect::<Vec<_>>();
+            paragraph[0] = &line[m.end()..];
+            let prefix = " ".repeat(m.as_str().width());
+            visit(i..end, &paragraph, m.as_str(), &prefix, &mut result);
+            state = LineState::Normal;
+            i = end;
+            continue;
+        }
+
+        // Paragraph of a list item after a blank line, indented to its content
+        if indent > 0 && list_indent == Some(indent) && is_nested_prose(&line[indent..]) {
+            let mut end = i + 1;
+            while end < lines.len()
+                && lines[end].len() - lines[end].trim_start_matches(' ').len() == indent
+                && is_nested_prose(&lines[end][indent..])
+            {
+                end += 1;
+            }
+            let paragraph = lines[i..end].iter().map(|line| &line[indent..]).collect::<Vec<_>>();
+            let prefix = " ".repeat(indent);
+            visit(i..end, &paragraph, &prefix, &prefix, &mut result);
+            state = LineState::Normal;
+            i = end;
+            continue;
+        }
+        if indent == 0 && state != LineState::Empty {
+            list_indent = None;
+        }
+
+        if !is_prose(line, &state, &prev_state) {
+            result.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        let mut end = i + 1;
+        while end < lines.len() {
+            let next = get_line_state(lines[end], LineState::Normal);
+            if !is_prose(lines[end], &next, &LineState::Normal) {
+                break;
+            }
+            end += 1;
+        }
+        visit(i..end, &lines[i..end], "", "", &mut result);
+        state = LineState::Normal;
+        i = end;
+    }
+    result
+}
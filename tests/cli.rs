@@ -1,11 +1,11 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
 use tempfile::{NamedTempFile, TempDir};
 
 #[test]
 fn test_file_input_output() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input_file = NamedTempFile::new()?;
     fs::write(input_file.path(), "## title\n\ntext")?;
 
@@ -25,7 +25,7 @@ fn test_file_input_output() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_stdin_stdout() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.write_stdin("## title\n\ntext");
     cmd.assert()
         .success()
@@ -35,7 +35,7 @@ fn test_stdin_stdout() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_non_existent_input_file() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.arg("non_existent_file.md");
     cmd.assert()
         .failure()
@@ -45,7 +45,7 @@ fn test_non_existent_input_file() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_empty_input() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.write_stdin("");
     cmd.assert().success().stdout("\n");
     Ok(())
@@ -53,13 +53,13 @@ fn test_empty_input() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_indent_argument() -> Result<(), Box<dyn std::error::Error>> {
-    let _cmd = Command::cargo_bin("mdformat")?;
+    let _cmd = cargo_bin_cmd!("mdformat");
     let _input = "1. level 1\n  2. level 2";
     let _expected = "1. level 1\n    1. level 2\n"; // Default indent is 4, but the logic seems to be 2 * (level - 1)
     
     // The current implementation seems to have hardcoded indent logic (2 spaces per level).
     // Let's first test the existing behavior.
-    let mut cmd_default = Command::cargo_bin("mdformat")?;
+    let mut cmd_default = cargo_bin_cmd!("mdformat");
     cmd_default.write_stdin("1. level 1\n  2. level 2");
     cmd_default.assert().success().stdout("1. level 1\n  1. level 2\n");
 
@@ -75,7 +75,7 @@ fn test_indent_argument() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_mixed_elements() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "# Title\n\nSome text.\n\n- list1\n- list2\n\n```rust\nlet a = 1;\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\nAnother line.";
     let expected = "# Title\n\nSome text.\n\n- list1\n- list2\n\n```rust\nlet a = 1;\n```\n\n| a   | b   |\n| --- | --- |\n| 1   | 2   |\n\nAnother line.\n";
     cmd.write_stdin(input);
@@ -87,7 +87,7 @@ fn test_mixed_elements() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_indent_argument_works() -> Result<(), Box<dyn std::error::Error>> {
     // Test --indent argument
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "- level 1\n  - level 2";
 
     cmd.write_stdin(input)
@@ -104,7 +104,7 @@ fn test_indent_argument_works() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_unordered_marker_argument() -> Result<(), Box<dyn std::error::Error>> {
     // Test --unordered-marker argument
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "- item 1\n- item 2";
 
     cmd.write_stdin(input)
@@ -121,7 +121,7 @@ fn test_unordered_marker_argument() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_heading_numbering_argument() -> Result<(), Box<dyn std::error::Error>> {
     // Test --heading-numbering argument
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "# Title\n## Sub";
 
     cmd.write_stdin(input)
@@ -138,7 +138,7 @@ fn test_heading_numbering_argument() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_no_format_tables_flag() -> Result<(), Box<dyn std::error::Error>> {
     // Test --no-format-tables flag
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "|a|b|\n|---|---|\n| column 1 | column 2    |";
 
     cmd.write_stdin(input)
@@ -154,7 +154,7 @@ fn test_no_format_tables_flag() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_no_cjk_spacing_flag() -> Result<(), Box<dyn std::error::Error>> {
     // Test --no-cjk-spacing flag
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "123你好world";
 
     cmd.write_stdin(input)
@@ -170,7 +170,7 @@ fn test_no_cjk_spacing_flag() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_multiple_flags_combined() -> Result<(), Box<dyn std::error::Error>> {
     // Test multiple flags combined
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     let input = "# Title\n你好world\n- item";
 
     cmd.write_stdin(input)
@@ -194,7 +194,7 @@ fn test_init_config_command() -> Result<(), Box<dyn std::error::Error>> {
     let temp_dir = TempDir::new()?;
     let config_path = temp_dir.path().join("test.toml");
 
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.arg("--init-config")
         .arg(&config_path);
 
@@ -229,7 +229,7 @@ unordered_marker = "*"
 numbering_start_level = 1
 "#)?;
 
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(temp_dir.path())
         .write_stdin("# Title\n## Sub\n- item\n  - sub");
 
@@ -251,7 +251,7 @@ fn test_explicit_config_file() -> Result<(), Box<dyn std::error::Error>> {
 unordered_marker = "+"
 "#)?;
 
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.arg("--config")
         .arg(&config_path)
         .write_stdin("- item");
@@ -275,7 +275,7 @@ fn test_cli_overrides_config_file() -> Result<(), Box<dyn std::error::Error>> {
 indent = 4
 "#)?;
 
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(temp_dir.path())
         .arg("--indent")
         .arg("2")  // CLI overrides to 2
@@ -297,7 +297,7 @@ fn test_invalid_config_file() -> Result<(), Box<dyn std::error::Error>> {
     // Create incorrectly formatted config
     fs::write(&config_path, "invalid toml content {")?;
 
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(temp_dir.path())
         .write_stdin("text");
 
@@ -332,7 +332,7 @@ unordered_marker = "*"
     fs::write(&input_file, "- item\n")?;
 
     // Run mdformat from subdirectory
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(&sub_dir)
         .arg(&input_file)
         .arg("-o")
@@ -370,7 +370,7 @@ indent = 4
     fs::write(&input_file, "- item\n  - sub\n")?;
 
     // Run from deeply nested directory
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(&nested_dir)
         .arg(&input_file)
         .arg("-o")
@@ -416,7 +416,7 @@ unordered_marker = "*"
     fs::write(&input_file, "- item\n")?;
 
     // Run from subdirectory
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(&sub_dir)
         .arg(&input_file)
         .arg("-o")
@@ -460,7 +460,7 @@ unordered_marker = "+"
     fs::write(&input_file, "- item\n")?;
 
     // Use --config to explicitly specify config
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(temp_dir.path())
         .arg("--config")
         .arg(&explicit_config)
@@ -486,7 +486,7 @@ fn test_config_upward_search_not_found_uses_default() -> Result<(), Box<dyn std:
     let input_file = temp_dir.path().join("test.md");
     fs::write(&input_file, "+ item\n")?;
 
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(temp_dir.path())
         .arg(&input_file)
         .arg("-o")
@@ -535,7 +535,7 @@ unordered_marker = "*"
     fs::write(&input_file, "+ item\n")?;
 
     // Run from deeply nested directory
-    let mut cmd = Command::cargo_bin("mdformat")?;
+    let mut cmd = cargo_bin_cmd!("mdformat");
     cmd.current_dir(&nested_dir)
         .arg(&input_file)
         .arg("-o")
@@ -549,3 +549,467 @@ unordered_marker = "*"
 
     Ok(())
 }
+
+// ===== Added: Check mode tests =====
+
+#[test]
+fn test_check_mode_formatted_input() -> Result<(), Box<dyn std::error::Error>> {
+    // Already formatted input passes the check and nothing is written
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--check").write_stdin("# Title\n\ntext\n");
+
+    cmd.assert().success().stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn test_check_mode_unformatted_input() -> Result<(), Box<dyn std::error::Error>> {
+    // Unformatted input fails the check and the file is left unchanged
+    let input_file = NamedTempFile::new()?;
+    fs::write(input_file.path(), "# Title\ntext")?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--check").arg(input_file.path());
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("would be reformatted"));
+    assert_eq!(fs::read_to_string(input_file.path())?, "# Title\ntext");
+
+    Ok(())
+}
+
+#[test]
+fn test_check_mode_reports_diagnostics() -> Result<(), Box<dyn std::error::Error>> {
+    // Reported rule violations fail the check even if the text is formatted
+    let temp_dir = TempDir::new()?;
+    fs::write(
+        temp_dir.path().join(".mdformat.toml"),
+        "[headings]\nskipped_levels = \"warn\"\n",
+    )?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.current_dir(temp_dir.path())
+        .arg("--check")
+        .write_stdin("# H1\n\n### H3\n");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("<stdin>:3: [heading-increment]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_heading_case_fixed_and_reported_in_check_mode() -> Result<(), Box<dyn std::error::Error>> {
+    // Write mode fixes the heading silently, check mode reports the fix
+    let temp_dir = TempDir::new()?;
+    fs::write(
+        temp_dir.path().join(".mdformat.toml"),
+        "[headings]\ncapitalization = \"sentence\"\n",
+    )?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.current_dir(temp_dir.path()).write_stdin("# Getting Started\n");
+    cmd.assert().success().stdout("# Getting started\n").stderr("");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.current_dir(temp_dir.path())
+        .arg("--check")
+        .write_stdin("# Getting Started\n");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("<stdin>:1: [heading-case]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_outline_json() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--outline").write_stdin("# Title\n## Usage\n");
+
+    cmd.assert().success().stdout(concat!(
+        "[\n",
+        "  {\n",
+        "    \"level\": 1,\n",
+        "    \"text\": \"Title\",\n",
+        "    \"slug\": \"title\",\n",
+        "    \"line\": 1,\n",
+        "    \"children\": [\n",
+        "      {\n",
+        "        \"level\": 2,\n",
+        "        \"text\": \"Usage\",\n",
+        "        \"slug\": \"usage\",\n",
+        "        \"line\": 2,\n",
+        "        \"children\": []\n",
+        "      }\n",
+        "    ]\n",
+        "  }\n",
+        "]\n"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_outline_yaml() -> Result<(), Box<dyn std::error::Error>> {
+    let input_file = NamedTempFile::new()?;
+    fs::write(input_file.path(), "text\n## Usage\n")?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--outline=yaml").arg(input_file.path());
+
+    cmd.assert()
+        .success()
+        .stdout("- level: 2\n  text: Usage\n  slug: usage\n  line: 2\n  children: []\n");
+
+    Ok(())
+}
+
+// ===== Added: toc subcommand tests =====
+
+#[test]
+fn test_toc_subcommand() -> Result<(), Box<dyn std::error::Error>> {
+    // Prints only the TOC of the formatted document
+    let input_file = NamedTempFile::new()?;
+    fs::write(input_file.path(), "# Title\n## 中文Section\n### Sub\n")?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("toc").arg(input_file.path()).arg("--indent").arg("4");
+
+    cmd.assert()
+        .success()
+        .stdout("- [Title](#title)\n    - [中文 Section](#中文-section)\n        - [Sub](#sub)\n");
+
+    // Input file is not modified
+    assert_eq!(fs::read_to_string(input_file.path())?, "# Title\n## 中文Section\n### Sub\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_toc_subcommand_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("toc").write_stdin("text\n## A\n## B");
+
+    cmd.assert().success().stdout("- [A](#a)\n- [B](#b)\n");
+
+    Ok(())
+}
+
+// ===== Added: table subcommand tests =====
+
+#[test]
+fn test_table_from_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let csv = temp_dir.path().join("data.csv");
+    fs::write(&csv, "name,note\r\n\"Smith, J\",\"say \"\"hi\"\"\"\r\n名字,\"a|b\nc\"\r\n")?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("table").arg("--from-csv").arg(&csv);
+
+    cmd.assert().success().stdout(concat!(
+        "| name     | note      |\n",
+        "| -------- | --------- |\n",
+        "| Smith, J | say \"hi\"  |\n",
+        "| 名字     | a\\|b<br>c |\n"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_table_from_tsv_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    // Tab-separated data from stdin needs an explicit delimiter
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["table", "--from-csv", "-", "--delimiter", "\t"])
+        .write_stdin("a\tb\n1\n");
+
+    cmd.assert()
+        .success()
+        .stdout("| a   | b   |\n| --- | --- |\n| 1   |     |\n");
+
+    Ok(())
+}
+
+// ===== Added: link tests =====
+
+#[test]
+fn test_check_mode_reports_missing_link_targets() -> Result<(), Box<dyn std::error::Error>> {
+    // Relative targets are resolved against the directory of the checked file
+    let temp_dir = TempDir::new()?;
+    fs::write(temp_dir.path().join(".mdformat.toml"), "[links]\ncheck_targets = true\n")?;
+    fs::create_dir(temp_dir.path().join("docs"))?;
+    fs::write(temp_dir.path().join("docs/guide.md"), "# Guide\n")?;
+    fs::write(temp_dir.path().join("docs/logo one.png"), "")?;
+    let input = concat!(
+        "# Index\n",
+        "\n",
+        "See [guide](guide.md#intro), ![logo](logo%20one.png) and [site](https://example.com).\n",
+        "\n",
+        "Broken [old](old.md) and `[code](missing.md)`.\n",
+        "\n",
+        "[ref]: ../nowhere/file.md\n"
+    );
+    fs::write(temp_dir.path().join("docs/index.md"), input)?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.current_dir(temp_dir.path())
+        .arg("--check")
+        .arg("docs/index.md");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("index.md:5: [link-target] link target not found: old.md"))
+        .stderr(predicate::str::contains("index.md:7: [link-target] link target not found: ../nowhere/file.md"))
+        .stderr(predicate::str::contains("guide.md").not())
+        .stderr(predicate::str::contains("logo").not())
+        .stderr(predicate::str::contains("missing.md").not());
+
+    Ok(())
+}
+
+#[test]
+fn test_repository_links_relative_to_document() -> Result<(), Box<dyn std::error::Error>> {
+    // The document directory is found from the closest parent with a .git entry
+    let temp_dir = TempDir::new()?;
+    fs::create_dir(temp_dir.path().join(".git"))?;
+    fs::create_dir(temp_dir.path().join("docs"))?;
+    fs::write(
+        temp_dir.path().join(".mdformat.toml"),
+        "[links]\nrepository_url = \"https://github.com/org/repo/blob/main\"\nrepository_links = \"relative\"\n",
+    )?;
+    fs::write(
+        temp_dir.path().join("docs/index.md"),
+        "See [readme](https://github.com/org/repo/blob/main/README.md).\n",
+    )?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.current_dir(temp_dir.path()).arg("docs/index.md");
+
+    cmd.assert().success().stdout("See [readme](../README.md).\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_locale_presets_cjk_options() -> Result<(), Box<dyn std::error::Error>> {
+    // Options set in the file override those preset by the locale
+    let temp_dir = TempDir::new()?;
+    fs::write(
+        temp_dir.path().join(".mdformat.toml"),
+        "locale = \"zh-TW\"\n\n[punctuation]\nmode = \"smart\"\ncjk_quotes = \"curly\"\n",
+    )?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.current_dir(temp_dir.path()).write_stdin("他說\"好\"然後用Rust寫,對吧\n");
+
+    cmd.assert().success().stdout("他說“好”然後用 Rust 寫，對吧\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_gbk_input_is_detected_and_kept() -> Result<(), Box<dyn std::error::Error>> {
+    // "这是一个测试文件" + "abc" + "，用于检查编码检测是否正确。\n" in GBK
+    let head: &[u8] = b"\xd5\xe2\xca\xc7\xd2\xbb\xb8\xf6\xb2\xe2\xca\xd4\xce\xc4\xbc\xfe";
+    let tail: &[u8] = b"\xa3\xac\xd3\xc3\xd3\xda\xbc\xec\xb2\xe9\xb1\xe0\xc2\xeb\xbc\xec\xb2\xe2\xca\xc7\xb7\xf1\xd5\xfd\xc8\xb7\xa1\xa3\n";
+    let input_file = NamedTempFile::new()?;
+    fs::write(input_file.path(), [head, b"abc", tail].concat())?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg(input_file.path());
+    cmd.assert().success().stdout([head, b" abc", tail].concat());
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg(input_file.path()).arg("--to-utf8");
+    cmd.assert()
+        .success()
+        .stdout("这是一个测试文件 abc，用于检查编码检测是否正确。\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_explicit_input_and_output_encodings() -> Result<(), Box<dyn std::error::Error>> {
+    // "中文abc" in GBK, too short to be detected reliably
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--input-encoding", "gbk", "--output-encoding", "utf-8"])
+        .write_stdin(&b"\xd6\xd0\xce\xc4abc\n"[..]);
+    cmd.assert().success().stdout("中文 abc\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--output-encoding", "gbk"]).write_stdin("中文abc\n");
+    cmd.assert().success().stdout(&b"\xd6\xd0\xce\xc4 abc\n"[..]);
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--input-encoding", "klingon"]).write_stdin("text\n");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown encoding 'klingon'"));
+
+    Ok(())
+}
+
+#[test]
+fn test_wrap_flag() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--wrap", "16"])
+        .write_stdin("- a list item that wraps\n\n> a quote that wraps too\n\n| a long table cell |\n| --- |\n");
+    cmd.assert().success().stdout(concat!(
+        "- a list item\n",
+        "  that wraps\n",
+        "\n",
+        "> a quote that\n",
+        "> wraps too\n",
+        "\n",
+        "| a long table cell |\n",
+        "| ----------------- |\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_wrap_policies() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--wrap=sentence").write_stdin("One. Two\nthree.\n");
+    cmd.assert().success().stdout("One.\nTwo three.\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--wrap=no").write_stdin("One. Two\nthree.\n");
+    cmd.assert().success().stdout("One. Two three.\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--wrap=preserve").write_stdin("One. Two\nthree.\n");
+    cmd.assert().success().stdout("One. Two\nthree.\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--wrap=wide").write_stdin("text\n");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid wrap policy 'wide'"));
+
+    Ok(())
+}
+
+#[test]
+fn test_unwrap_flag() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--unwrap").write_stdin("- one\n  two  \n  three\n\n> 第一\n> 第二\n");
+    cmd.assert().success().stdout("- one two\\\n  three\n\n> 第一第二\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--unwrap", "--wrap", "80"]).write_stdin("text\n");
+    cmd.assert().failure();
+
+    Ok(())
+}
+
+#[test]
+fn test_line_endings() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.write_stdin("# Title\r\ntext\r\n");
+    cmd.assert().success().stdout("# Title\r\n\r\ntext\r\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--check").write_stdin("# Title\r\n\r\ntext\r\n");
+    cmd.assert().success();
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg("--eol=lf").write_stdin("# Title\r\ntext\r\n");
+    cmd.assert().success().stdout("# Title\n\ntext\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--eol", "crlf"]).write_stdin("text\n");
+    cmd.assert().success().stdout("text\r\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_mdx_dialect() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+    let input = dir.path().join("page.mdx");
+    fs::write(&input, "import X from './x'\n\n<X>中文abc</X>\n\n中文abc {props.a}\n")?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg(&input);
+    cmd.assert()
+        .success()
+        .stdout("import X from './x'\n\n<X>中文abc</X>\n\n中文 abc {props.a}\n");
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.args(["--dialect", "mdx"]).write_stdin("export const a = 1\n文字{a}\n");
+    cmd.assert().success().stdout("export const a = 1\n\n文字{a}\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_notebook_markdown_cells() -> Result<(), Box<dyn std::error::Error>> {
+    let cells = r##" "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": [
+    "# 标题abc\n",
+    "正文abc"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {},
+   "outputs": [],
+   "source": [
+    "x = '中文abc'"
+   ]
+  },
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": "*  列表abc"
+  }
+ ],"##;
+    let rest = r##" "metadata": {
+  "language_info": {
+   "name": "python"
+  }
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+    let dir = TempDir::new()?;
+    let input = dir.path().join("analysis.ipynb");
+    fs::write(&input, format!("{{\n{}\n{}", cells, rest))?;
+
+    let formatted_cells = cells
+        .replace(r##""# 标题abc\n","##, r##""# 标题 abc\n",
+    "\n","##)
+        .replace("正文abc", "正文 abc")
+        .replace("*  列表abc", "- 列表 abc");
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg(&input);
+    cmd.assert().success().stdout(format!("{{\n{}\n{}", formatted_cells, rest));
+
+    Ok(())
+}
+
+#[test]
+fn test_comment_prefix() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+    let input = dir.path().join("main.cpp");
+    fs::write(&input, "#include <cstdio>\n\n// 入口abc\nint main() {\n    // *  步骤abc\n    return 0;\n}\n")?;
+
+    let mut cmd = cargo_bin_cmd!("mdformat");
+    cmd.arg(&input).arg("--comment-prefix").arg("//");
+    cmd.assert()
+        .success()
+        .stdout("#include <cstdio>\n\n// 入口 abc\nint main() {\n    // - 步骤 abc\n    return 0;\n}\n");
+
+    Ok(())
+}